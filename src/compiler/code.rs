@@ -0,0 +1,257 @@
+// The bytecode: the opcodes, how their instructions are encoded, and a
+// disassembler to read them back.
+use std::fmt;
+
+// An instruction is an opcode byte followed by its operands, big endian, on
+// as many bytes as given by the operand widths of the opcode.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Constant, // Push the constant at the index of the operand.
+    Pop,      // Pop the top of the stack.
+    // Pop the right then the left operand, and push the result.
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    // Pop the operand and push the result.
+    Minus,
+    Bang,
+    True,
+    False,
+    Null,
+    // Get or set the global, local, builtin or free variable at the index.
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,
+    GetBuiltin,
+    GetFree,
+    // Build an array, or a hash, from the number of values of the operand.
+    Array,
+    Hash,
+    Index, // Pop the index then the indexed value, and push the element.
+    // Continue at the position of the operand, for JumpNotTruthy only when
+    // the value popped is falsy.
+    JumpNotTruthy,
+    Jump,
+    // Call the function below the arguments, their number is the operand.
+    Call,
+    ReturnValue, // Return the top of the stack from the function.
+    Return,      // Return null from the function.
+    // Push a closure of the compiled function at the constant index of the
+    // first operand, capturing the free variables it lists, their number is
+    // the second operand.
+    Closure,
+    CurrentClosure, // Push the closure being run, for recursive calls.
+}
+
+// All the opcodes, at the index of their byte.
+const OPCODES: [Opcode; 33] = [
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Mod,
+    Opcode::Pow,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::GreaterThan,
+    Opcode::LessThan,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Null,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::GetBuiltin,
+    Opcode::GetFree,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Index,
+    Opcode::JumpNotTruthy,
+    Opcode::Jump,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Return,
+    Opcode::Closure,
+    Opcode::CurrentClosure,
+];
+
+impl Opcode {
+    // The name used by the disassembler.
+    pub fn name(self) -> &'static str {
+        match self {
+            Opcode::Constant => "OpConstant",
+            Opcode::Pop => "OpPop",
+            Opcode::Add => "OpAdd",
+            Opcode::Sub => "OpSub",
+            Opcode::Mul => "OpMul",
+            Opcode::Div => "OpDiv",
+            Opcode::Mod => "OpMod",
+            Opcode::Pow => "OpPow",
+            Opcode::Equal => "OpEqual",
+            Opcode::NotEqual => "OpNotEqual",
+            Opcode::GreaterThan => "OpGreaterThan",
+            Opcode::LessThan => "OpLessThan",
+            Opcode::Minus => "OpMinus",
+            Opcode::Bang => "OpBang",
+            Opcode::True => "OpTrue",
+            Opcode::False => "OpFalse",
+            Opcode::Null => "OpNull",
+            Opcode::GetGlobal => "OpGetGlobal",
+            Opcode::SetGlobal => "OpSetGlobal",
+            Opcode::GetLocal => "OpGetLocal",
+            Opcode::SetLocal => "OpSetLocal",
+            Opcode::GetBuiltin => "OpGetBuiltin",
+            Opcode::GetFree => "OpGetFree",
+            Opcode::Array => "OpArray",
+            Opcode::Hash => "OpHash",
+            Opcode::Index => "OpIndex",
+            Opcode::JumpNotTruthy => "OpJumpNotTruthy",
+            Opcode::Jump => "OpJump",
+            Opcode::Call => "OpCall",
+            Opcode::ReturnValue => "OpReturnValue",
+            Opcode::Return => "OpReturn",
+            Opcode::Closure => "OpClosure",
+            Opcode::CurrentClosure => "OpCurrentClosure",
+        }
+    }
+
+    // The number of bytes of each operand.
+    pub fn operand_widths(self) -> &'static [usize] {
+        match self {
+            Opcode::Constant
+            | Opcode::GetGlobal
+            | Opcode::SetGlobal
+            | Opcode::Array
+            | Opcode::Hash
+            | Opcode::JumpNotTruthy
+            | Opcode::Jump => &[2],
+            Opcode::GetLocal
+            | Opcode::SetLocal
+            | Opcode::GetBuiltin
+            | Opcode::GetFree
+            | Opcode::Call => &[1],
+            Opcode::Closure => &[2, 1],
+            _ => &[],
+        }
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = u8;
+
+    // Fails with the byte when it isn't an opcode.
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        OPCODES.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+// The largest operand that can be encoded on width bytes.
+pub fn max_operand(width: usize) -> usize {
+    match width {
+        0 => 0,
+        width if width >= usize::BITS as usize / 8 => usize::MAX,
+        width => (1 << (8 * width)) - 1,
+    }
+}
+
+// Encodes the instruction. Operands are truncated to the width they are
+// encoded on, the callers check them with max_operand() first. Missing ones
+// are 0 and extra ones are ignored.
+pub fn make(op: Opcode, operands: &[usize]) -> Vec<u8> {
+    let widths = op.operand_widths();
+    let mut instruction = Vec::with_capacity(1 + widths.iter().sum::<usize>());
+    instruction.push(op as u8);
+    for (i, &width) in widths.iter().enumerate() {
+        let operand = operands.get(i).copied().unwrap_or(0);
+        let bytes = operand.to_be_bytes();
+        instruction.extend_from_slice(&bytes[bytes.len() - width..]);
+    }
+    instruction
+}
+
+// Decodes the operands of op found at the start of ins, and gives them with
+// the number of bytes they were encoded on.
+pub fn read_operands(op: Opcode, ins: &[u8]) -> (Vec<usize>, usize) {
+    let mut offset = 0;
+    let operands = op
+        .operand_widths()
+        .iter()
+        .map(|&width| {
+            let operand = read_operand(&ins[offset..], width);
+            offset += width;
+            operand
+        })
+        .collect();
+    (operands, offset)
+}
+
+// The big endian unsigned integer on the first width bytes of ins.
+pub fn read_operand(ins: &[u8], width: usize) -> usize {
+    ins[..width]
+        .iter()
+        .fold(0, |value, &byte| value << 8 | byte as usize)
+}
+
+// The encoded instructions of a program, or of a function.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Instructions(pub Vec<u8>);
+
+impl Instructions {
+    pub fn new() -> Self {
+        Instructions(Vec::new())
+    }
+}
+
+impl From<Vec<Vec<u8>>> for Instructions {
+    // Concatenates the instructions, as made by make().
+    fn from(instructions: Vec<Vec<u8>>) -> Self {
+        Instructions(instructions.concat())
+    }
+}
+
+// The disassembled instructions, one per line with its offset:
+//
+// 0000 OpConstant 1
+// 0003 OpAdd
+impl fmt::Display for Instructions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut offset = 0;
+        while offset < self.0.len() {
+            let op = match Opcode::try_from(self.0[offset]) {
+                Ok(op) => op,
+                Err(byte) => {
+                    writeln!(f, "{:04} ERROR: unknown opcode {}", offset, byte)?;
+                    offset += 1;
+                    continue;
+                }
+            };
+            let width: usize = op.operand_widths().iter().sum();
+            if offset + 1 + width > self.0.len() {
+                writeln!(f, "{:04} ERROR: truncated {}", offset, op.name())?;
+                break;
+            }
+            let (operands, read) = read_operands(op, &self.0[offset + 1..]);
+            write!(f, "{:04} {}", offset, op.name())?;
+            for operand in operands {
+                write!(f, " {}", operand)?;
+            }
+            writeln!(f)?;
+            offset += 1 + read;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,412 @@
+// looking for src/compiler/code.rs
+// looking for src/compiler/symbol_table.rs
+pub mod code;
+pub mod symbol_table;
+
+// The compiler turns the AST into bytecode for the VM.
+use std::rc::Rc;
+
+use crate::interpreter::ast::{BlockStatement, Expression, FunctionLiteral, Program, Statement};
+use crate::interpreter::builtins;
+use crate::interpreter::object::{CompiledFunction, Object};
+use crate::interpreter::token::Position;
+use code::{Instructions, Opcode};
+use symbol_table::{Symbol, SymbolScope, SymbolTable};
+
+// What the VM runs: the instructions of the program and the constants they
+// refer to by index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub constants: Vec<Object>,
+    // Where the calls of the program are in the source, by offset of their
+    // instruction.
+    pub call_positions: Vec<(usize, Position)>,
+}
+
+// The instructions of the program, or of the function being compiled.
+#[derive(Debug, Default)]
+struct CompilationScope {
+    instructions: Vec<u8>,
+    // The opcode and position of the last two instructions emitted, for the
+    // blocks ending with an expression.
+    last_instruction: Option<(Opcode, usize)>,
+    previous_instruction: Option<(Opcode, usize)>,
+    // The position of each call, for the builtins reporting where they were
+    // called like with the evaluator.
+    call_positions: Vec<(usize, Position)>,
+}
+
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    // The builtins of the evaluator are known by the compiler by their index
+    // in its list.
+    pub fn new() -> Self {
+        let mut symbol_table = SymbolTable::new();
+        for (i, (name, _)) in builtins::all().enumerate() {
+            symbol_table.define_builtin(i, name);
+        }
+        Compiler {
+            constants: Vec::new(),
+            symbol_table,
+            scopes: vec![CompilationScope::default()],
+        }
+    }
+
+    // Compiles the program, stopping at the first error. Names must be bound
+    // before they are used, except in the body of a function for the
+    // function itself.
+    pub fn compile(&mut self, program: &Program) -> Result<(), String> {
+        for stmt in program.statements.iter() {
+            self.compile_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    pub fn bytecode(&self) -> Bytecode {
+        Bytecode {
+            instructions: Instructions(self.scope().instructions.clone()),
+            constants: self.constants.clone(),
+            call_positions: self.scope().call_positions.clone(),
+        }
+    }
+
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<(), String> {
+        match stmt {
+            Statement::Let(stmt) => self.compile_binding(stmt.name(), stmt.value()),
+            Statement::Const(stmt) => self.compile_binding(stmt.name(), Some(stmt.value())),
+            Statement::Return(stmt) => {
+                self.compile_expression(&stmt.return_value)?;
+                self.emit(Opcode::ReturnValue, &[])?;
+                Ok(())
+            }
+            Statement::Expression(stmt) => {
+                self.compile_expression(&stmt.expression)?;
+                self.emit(Opcode::Pop, &[])?;
+                Ok(())
+            }
+            _ => Err(format!("unsupported statement: {}", stmt.kind())),
+        }
+    }
+
+    // The name is defined once the value is compiled, so the value still
+    // sees the previous binding of the name. A function sees itself through
+    // its name. A let without a value binds null.
+    fn compile_binding(&mut self, name: &str, value: Option<&Expression>) -> Result<(), String> {
+        match value {
+            Some(Expression::FunctionLiteral(lit)) => self.compile_function(lit, Some(name))?,
+            Some(value) => self.compile_expression(value)?,
+            None => {
+                self.emit(Opcode::Null, &[])?;
+            }
+        }
+        let symbol = self.symbol_table.define(name);
+        let op = match symbol.scope {
+            SymbolScope::Global => Opcode::SetGlobal,
+            _ => Opcode::SetLocal,
+        };
+        self.emit(op, &[symbol.index])?;
+        Ok(())
+    }
+
+    fn compile_block(&mut self, block: &BlockStatement) -> Result<(), String> {
+        for stmt in block.statements.iter() {
+            self.compile_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expr: &Expression) -> Result<(), String> {
+        match expr {
+            Expression::IntegerLiteral(lit) => {
+                self.emit_constant(Object::Integer(lit.value()))?;
+            }
+            Expression::FloatLiteral(lit) => {
+                self.emit_constant(Object::Float(lit.value()))?;
+            }
+            Expression::StringLiteral(lit) => {
+                self.emit_constant(Object::Str(lit.value().to_string()))?;
+            }
+            Expression::Boolean(lit) => {
+                self.emit(
+                    if lit.value() {
+                        Opcode::True
+                    } else {
+                        Opcode::False
+                    },
+                    &[],
+                )?;
+            }
+            Expression::Identifier(ident) => {
+                let Some(symbol) = self.symbol_table.resolve(ident.value()) else {
+                    return Err(format!("identifier not found: {}", ident.value()));
+                };
+                self.load_symbol(&symbol)?;
+            }
+            Expression::ArrayLiteral(lit) => {
+                for element in lit.elements.iter() {
+                    self.compile_expression(element)?;
+                }
+                self.emit(Opcode::Array, &[lit.elements.len()])?;
+            }
+            Expression::HashLiteral(lit) => {
+                for (key, value) in lit.pairs.iter() {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+                self.emit(Opcode::Hash, &[lit.pairs.len() * 2])?;
+            }
+            Expression::Index(expr) => {
+                self.compile_expression(&expr.left)?;
+                self.compile_expression(&expr.index)?;
+                self.emit(Opcode::Index, &[])?;
+            }
+            Expression::Prefix(expr) => {
+                self.compile_expression(&expr.right)?;
+                let op = match expr.operator.as_str() {
+                    "-" => Opcode::Minus,
+                    "!" => Opcode::Bang,
+                    op => return Err(format!("unsupported operator: {}", op)),
+                };
+                self.emit(op, &[])?;
+            }
+            Expression::Infix(expr) => {
+                let op = match expr.operator.as_str() {
+                    "+" => Opcode::Add,
+                    "-" => Opcode::Sub,
+                    "*" => Opcode::Mul,
+                    "/" => Opcode::Div,
+                    "%" => Opcode::Mod,
+                    "**" => Opcode::Pow,
+                    "==" => Opcode::Equal,
+                    "!=" => Opcode::NotEqual,
+                    ">" => Opcode::GreaterThan,
+                    "<" => Opcode::LessThan,
+                    op => return Err(format!("unsupported operator: {}", op)),
+                };
+                self.compile_expression(&expr.left)?;
+                self.compile_expression(&expr.right)?;
+                self.emit(op, &[])?;
+            }
+            // The condition is followed by a jump over the consequence when
+            // it is falsy, and the consequence by a jump over the
+            // alternative. Their positions are only known once the branches
+            // are compiled. Without an alternative the value is null.
+            Expression::If(expr) => {
+                self.compile_expression(&expr.condition)?;
+                let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[0])?;
+                self.compile_branch(&expr.consequence)?;
+                let jump = self.emit(Opcode::Jump, &[0])?;
+                self.patch_jump(jump_not_truthy)?;
+                match &expr.alternative {
+                    Some(alternative) => self.compile_branch(alternative)?,
+                    None => {
+                        self.emit(Opcode::Null, &[])?;
+                    }
+                }
+                self.patch_jump(jump)?;
+            }
+            Expression::Ternary(expr) => {
+                self.compile_expression(&expr.condition)?;
+                let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[0])?;
+                self.compile_expression(&expr.consequence)?;
+                let jump = self.emit(Opcode::Jump, &[0])?;
+                self.patch_jump(jump_not_truthy)?;
+                self.compile_expression(&expr.alternative)?;
+                self.patch_jump(jump)?;
+            }
+            Expression::FunctionLiteral(lit) => self.compile_function(lit, None)?,
+            Expression::Call(expr) => {
+                self.compile_expression(&expr.function)?;
+                for arg in expr.arguments.iter() {
+                    self.compile_expression(arg)?;
+                }
+                let call = self.emit(Opcode::Call, &[expr.arguments.len()])?;
+                let position = expr.token.position;
+                self.scope_mut().call_positions.push((call, position));
+            }
+            _ => return Err(format!("unsupported expression: {}", expr.kind())),
+        }
+        Ok(())
+    }
+
+    // A branch of an if leaves its value on the stack: the one of its last
+    // expression statement, or null. Like with the evaluator the lets of the
+    // branch don't leak out of it.
+    fn compile_branch(&mut self, block: &BlockStatement) -> Result<(), String> {
+        let start = self.scope().instructions.len();
+        self.symbol_table.enter_block();
+        let compiled = self.compile_block(block);
+        self.symbol_table.leave_block();
+        compiled?;
+        match self.scope().last_instruction {
+            Some((Opcode::Pop, position)) if position >= start => {
+                let scope = self.scope_mut();
+                scope.instructions.truncate(position);
+                scope.last_instruction = scope.previous_instruction.take();
+            }
+            _ => {
+                self.emit(Opcode::Null, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    // Makes the jump at the position go to the next instruction emitted.
+    fn patch_jump(&mut self, position: usize) -> Result<(), String> {
+        let scope = self.scope_mut();
+        let target = scope.instructions.len();
+        let Ok(op) = Opcode::try_from(scope.instructions[position]) else {
+            unreachable!("not an instruction at {}", position);
+        };
+        check_operands(op, &[target])?;
+        let instruction = code::make(op, &[target]);
+        scope.instructions[position..position + instruction.len()].copy_from_slice(&instruction);
+        Ok(())
+    }
+
+    // The body is compiled in a scope of its own, the function is then added
+    // to the constants and the enclosing code creates a closure of it,
+    // capturing the free variables. The value of the last
+    // expression statement is returned, or null.
+    fn compile_function(
+        &mut self,
+        lit: &FunctionLiteral,
+        name: Option<&str>,
+    ) -> Result<(), String> {
+        self.enter_scope();
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+        for param in lit.parameters.iter() {
+            self.symbol_table.define(param.value());
+        }
+        if let Err(err) = self.compile_block(&lit.body) {
+            self.leave_scope();
+            return Err(err);
+        }
+        match self.scope().last_instruction {
+            Some((Opcode::Pop, position)) => {
+                self.scope_mut().instructions[position] = Opcode::ReturnValue as u8;
+            }
+            Some((Opcode::ReturnValue, _)) => {}
+            _ => {
+                self.emit(Opcode::Return, &[])?;
+            }
+        }
+
+        let free = self.symbol_table.free_symbols().to_vec();
+        let num_locals = self.symbol_table.num_definitions();
+        let (instructions, call_positions) = self.leave_scope();
+
+        let num_free = free.len();
+        let function = CompiledFunction {
+            instructions,
+            num_locals,
+            parameters: lit
+                .parameters
+                .iter()
+                .map(|param| param.value().to_string())
+                .collect(),
+            free,
+            call_positions,
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(function)));
+        self.emit(Opcode::Closure, &[index, num_free])?;
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) -> Result<(), String> {
+        let op = match symbol.scope {
+            SymbolScope::Global => Opcode::GetGlobal,
+            SymbolScope::Local => Opcode::GetLocal,
+            SymbolScope::Builtin => Opcode::GetBuiltin,
+            SymbolScope::Free => Opcode::GetFree,
+            SymbolScope::Function => Opcode::CurrentClosure,
+        };
+        self.emit(op, &[symbol.index])?;
+        Ok(())
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        let outer = std::mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::new_enclosed(outer);
+    }
+
+    // Gives the instructions of the function compiled in the scope, and the
+    // positions of its calls.
+    fn leave_scope(&mut self) -> (Instructions, Vec<(usize, Position)>) {
+        let scope = self.scopes.pop().expect("leaving the scope of the program");
+        let table = std::mem::take(&mut self.symbol_table);
+        self.symbol_table = table.into_outer().expect("leaving the global symbol table");
+        (Instructions(scope.instructions), scope.call_positions)
+    }
+
+    fn scope(&self) -> &CompilationScope {
+        self.scopes.last().expect("no compilation scope")
+    }
+
+    fn scope_mut(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().expect("no compilation scope")
+    }
+
+    fn add_constant(&mut self, constant: Object) -> usize {
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    fn emit_constant(&mut self, constant: Object) -> Result<(), String> {
+        let index = self.add_constant(constant);
+        self.emit(Opcode::Constant, &[index])?;
+        Ok(())
+    }
+
+    // Appends the instruction and gives its position. It fails when an
+    // operand doesn't fit in the bytes it is encoded on.
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> Result<usize, String> {
+        check_operands(op, operands)?;
+        let instruction = code::make(op, operands);
+        let scope = self.scope_mut();
+        let position = scope.instructions.len();
+        scope.instructions.extend(instruction);
+        scope.previous_instruction = scope.last_instruction.replace((op, position));
+        Ok(position)
+    }
+}
+
+// The operands must fit in their width, make() would truncate them.
+fn check_operands(op: Opcode, operands: &[usize]) -> Result<(), String> {
+    for (i, (&operand, &width)) in operands.iter().zip(op.operand_widths()).enumerate() {
+        if operand > code::max_operand(width) {
+            return Err(format!("too many {}", operand_limit(op, i)));
+        }
+    }
+    Ok(())
+}
+
+// What the operand of op counts, for the errors.
+fn operand_limit(op: Opcode, operand: usize) -> &'static str {
+    match (op, operand) {
+        (Opcode::Constant, _) | (Opcode::Closure, 0) => "constants",
+        (Opcode::GetGlobal | Opcode::SetGlobal, _) => "globals",
+        (Opcode::GetLocal | Opcode::SetLocal, _) => "locals",
+        (Opcode::GetBuiltin, _) => "builtins",
+        (Opcode::GetFree, _) | (Opcode::Closure, _) => "free variables",
+        (Opcode::Array | Opcode::Hash, _) => "elements",
+        (Opcode::Call, _) => "arguments",
+        (Opcode::Jump | Opcode::JumpNotTruthy, _) => "instructions to jump over",
+        _ => "operands",
+    }
+}
@@ -0,0 +1,168 @@
+// The symbol table tells the compiler where the value of each name is, so
+// the compiled code refers to it by index instead of by name.
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolScope {
+    Global,  // Bound at the top level, in the globals of the VM.
+    Local,   // Bound in the function being compiled, on the stack.
+    Builtin, // A builtin function, by its index in the list of builtins.
+    Free,    // Bound in an enclosing function, captured by the closure.
+    // The name a function is bound to, in its own body. It refers to the
+    // closure being run, so the function can call itself before the name
+    // is bound.
+    Function,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+// The table of the top level, or of a function with the table of the code
+// enclosing it as outer.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    outer: Option<Box<SymbolTable>>,
+    store: HashMap<String, Symbol>,
+    num_definitions: usize,
+    // The symbols of the enclosing functions used by this one, in the order
+    // they are captured. Their index in this table is their index here.
+    free_symbols: Vec<Symbol>,
+    // For each block being compiled, the names defined in it with the
+    // symbol they hide, to bring it back at the end of the block.
+    blocks: Vec<HashMap<String, Option<Symbol>>>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable::default()
+    }
+
+    // The table of a function defined in the code of outer.
+    pub fn new_enclosed(outer: SymbolTable) -> Self {
+        SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..SymbolTable::default()
+        }
+    }
+
+    // Gives back the table of the enclosing code, when we are done with the
+    // function.
+    pub fn into_outer(self) -> Option<SymbolTable> {
+        self.outer.map(|outer| *outer)
+    }
+
+    // Binds the name in this table, hiding the same name of the enclosing
+    // ones. Defining a name again reuses its index, so the code compiled
+    // before, and the closures sharing it, see the new value like with the
+    // evaluator. In a block only the names defined in the block are reused,
+    // the others get a new index that hides them until the end of the block.
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let scope = match self.outer {
+            Some(_) => SymbolScope::Local,
+            None => SymbolScope::Global,
+        };
+        let outside_block = self
+            .blocks
+            .last()
+            .is_some_and(|block| !block.contains_key(name));
+        if outside_block {
+            let hidden = self.store.get(name).cloned();
+            if let Some(block) = self.blocks.last_mut() {
+                block.insert(name.to_string(), hidden);
+            }
+        } else if let Some(symbol) = self.store.get(name).filter(|symbol| symbol.scope == scope) {
+            return symbol.clone();
+        }
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope,
+            index: self.num_definitions,
+        };
+        self.num_definitions += 1;
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    pub fn define_builtin(&mut self, index: usize, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Builtin,
+            index,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Function,
+            index: 0,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    // Finds the symbol of the name in this table or the enclosing ones. A
+    // local of an enclosing function becomes a free symbol of this one, and
+    // of the functions in between.
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(symbol.clone());
+        }
+        let symbol = self.outer.as_mut()?.resolve(name)?;
+        match symbol.scope {
+            SymbolScope::Global | SymbolScope::Builtin => Some(symbol),
+            SymbolScope::Local | SymbolScope::Free | SymbolScope::Function => {
+                Some(self.define_free(symbol))
+            }
+        }
+    }
+
+    // The names defined from here to the matching leave_block() are only
+    // bound in the block.
+    pub fn enter_block(&mut self) {
+        self.blocks.push(HashMap::new());
+    }
+
+    // Unbinds the names defined in the block and brings back the ones they
+    // were hiding. Their indexes are not given again: a closure created in
+    // the block may still share the value.
+    pub fn leave_block(&mut self) {
+        let Some(block) = self.blocks.pop() else {
+            return;
+        };
+        for (name, hidden) in block {
+            match hidden {
+                Some(symbol) => self.store.insert(name, symbol),
+                None => self.store.remove(&name),
+            };
+        }
+    }
+
+    // The number of locals, for the room to leave on the stack.
+    pub fn num_definitions(&self) -> usize {
+        self.num_definitions
+    }
+
+    // The symbols, as resolved in the enclosing table, of the values the
+    // closure has to capture.
+    pub fn free_symbols(&self) -> &[Symbol] {
+        &self.free_symbols
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol {
+            name: original.name.clone(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len(),
+        };
+        self.free_symbols.push(original);
+        self.store.insert(symbol.name.clone(), symbol.clone());
+        symbol
+    }
+}
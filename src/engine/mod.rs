@@ -0,0 +1,115 @@
+// An engine runs programs with one of the two backends, the evaluator
+// walking the AST or the VM running the compiled bytecode. Both give the
+// same values and runtime errors, so the callers don't have to care which
+// one ran. What the compiler doesn't support yet, like some operators, fails
+// to compile with the VM instead of failing when it is run.
+use std::cell::RefCell;
+use std::error;
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::compiler::Compiler;
+use crate::interpreter::ast::Program;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::evaluator::Evaluator;
+use crate::interpreter::lexer::Lexer;
+use crate::interpreter::object::Object;
+use crate::interpreter::parser::{Parser, ParserError};
+use crate::vm::Vm;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    #[default]
+    Eval,
+    Vm,
+}
+
+// Why a program could not give a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Parse(Vec<ParserError>),
+    // The compiler doesn't support everything the evaluator does yet.
+    Compile(String),
+    Runtime(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(errors) => {
+                for (i, err) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", err)?;
+                }
+                Ok(())
+            }
+            Error::Compile(message) | Error::Runtime(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl Engine {
+    pub fn name(self) -> &'static str {
+        match self {
+            Engine::Eval => "eval",
+            Engine::Vm => "vm",
+        }
+    }
+
+    // Parses and runs the input.
+    pub fn run(self, input: &str) -> Result<Object, Error> {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        if !parser.errors.is_empty() {
+            return Err(Error::Parse(parser.errors));
+        }
+        self.run_program(&program)
+    }
+
+    pub fn run_program(self, program: &Program) -> Result<Object, Error> {
+        self.run_with(program, Evaluator::new())
+    }
+
+    // Runs the program with the evaluator given, for its output and its
+    // builtins. The VM calls the builtins with it.
+    pub fn run_with(self, program: &Program, mut evaluator: Evaluator) -> Result<Object, Error> {
+        let result = match self {
+            Engine::Eval => {
+                let env = Rc::new(RefCell::new(Environment::new()));
+                evaluator.eval(program, &env)
+            }
+            Engine::Vm => {
+                let mut compiler = Compiler::new();
+                compiler.compile(program).map_err(Error::Compile)?;
+                Vm::with_evaluator(compiler.bytecode(), evaluator).run()
+            }
+        };
+        match result {
+            Object::Error(message) => Err(Error::Runtime(message)),
+            value => Ok(value),
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eval" => Ok(Engine::Eval),
+            "vm" => Ok(Engine::Vm),
+            _ => Err(format!("unknown engine: {} (expected eval or vm)", s)),
+        }
+    }
+}
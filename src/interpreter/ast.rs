@@ -1,32 +1,422 @@
 // AST is Nodes connected each other.
-use super::token::Token;
-use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+use super::lexer::Lexer;
+use super::parser::ParserWarning;
+use super::token::{Position, Span, Token, TokenType, Trivia};
 
 // Every node in our AST has to implement the Node trait.
-pub trait Node {
+// Nodes are printed through Display, for debugging and to compare them with
+// other AST nodes. Children are written straight into the formatter so
+// printing a program does not build a String for each subexpression.
+// Nodes are compared on their structure: tokens are left out so a node built
+// by hand is equal to the same node parsed from a source, wherever it is.
+pub trait Node: fmt::Display {
     // Returns the literal value of the token.
     fn token_literal(&self) -> String;
-    // print AST nodes for debugging and to compare them with other AST nodes.
-    fn string(&self) -> String;
 }
 
+// Writes the items separated by commas, as in parameters and arguments.
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+// Builders are used to set the fields of a node one by one while parsing.
+// They are typestates: a builder has a type parameter per required field that
+// is Missing until the field is set, and build() only exists once they are
+// all set. So building an incomplete node doesn't compile.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Missing;
+
 // Statement does not produce value.
-// We will have
-//   - LetStatement
-//   - ReturnStatement
-//   - ExpressionStatement: An expression statement is one that evaluates an
-//   expression and ignores its result
-pub trait Statement: Node {
-    // This dummy method is used for debugging.
-    fn statement_node(&self);
-    fn as_any(&self) -> &dyn Any;
+// Each kind of statement is a variant holding its node so we can match on
+// it, the compiler tells us when one is forgotten.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Let(LetStatement),
+    Const(ConstStatement),
+    Import(ImportStatement),
+    Return(ReturnStatement),
+    // An expression statement is one that evaluates an expression and ignores
+    // its result.
+    Expression(ExpressionStatement),
+    ForIn(ForInStatement),
+}
+
+impl Node for Statement {
+    fn token_literal(&self) -> String {
+        self.node().token_literal()
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.node(), f)
+    }
+}
+
+impl Statement {
+    // The name of the variant, as used by Program::stats().
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Statement::Let(_) => "Let",
+            Statement::Const(_) => "Const",
+            Statement::Import(_) => "Import",
+            Statement::Return(_) => "Return",
+            Statement::Expression(_) => "Expression",
+            Statement::ForIn(_) => "ForIn",
+        }
+    }
+
+    // The as_* accessors return the node if the statement is of that kind,
+    // for the places that expect one kind only like tests.
+    pub fn as_let(&self) -> Option<&LetStatement> {
+        match self {
+            Statement::Let(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_const(&self) -> Option<&ConstStatement> {
+        match self {
+            Statement::Const(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_import(&self) -> Option<&ImportStatement> {
+        match self {
+            Statement::Import(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_return(&self) -> Option<&ReturnStatement> {
+        match self {
+            Statement::Return(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_expression_statement(&self) -> Option<&ExpressionStatement> {
+        match self {
+            Statement::Expression(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_for_in(&self) -> Option<&ForInStatement> {
+        match self {
+            Statement::ForIn(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    // The bytes of the source the statement was parsed from, from its first
+    // token to its last one, the semicolon included. A statement built by hand
+    // only covers its first token.
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let(stmt) => stmt.span,
+            Statement::Const(stmt) => stmt.span,
+            Statement::Import(stmt) => stmt.span,
+            Statement::Return(stmt) => stmt.span,
+            Statement::Expression(stmt) => stmt.span,
+            Statement::ForIn(stmt) => stmt.span,
+        }
+    }
+
+    // The comments on the lines before the statement and the ones after it on
+    // its last line. The comments inside the statement are only found on the
+    // tokens that follow them.
+    pub fn comments(&self) -> &[Trivia] {
+        match self {
+            Statement::Let(stmt) => &stmt.comments,
+            Statement::Const(stmt) => &stmt.comments,
+            Statement::Import(stmt) => &stmt.comments,
+            Statement::Return(stmt) => &stmt.comments,
+            Statement::Expression(stmt) => &stmt.comments,
+            Statement::ForIn(stmt) => &stmt.comments,
+        }
+    }
+
+    pub(crate) fn set_comments(&mut self, comments: Vec<Trivia>) {
+        match self {
+            Statement::Let(stmt) => stmt.comments = comments,
+            Statement::Const(stmt) => stmt.comments = comments,
+            Statement::Import(stmt) => stmt.comments = comments,
+            Statement::Return(stmt) => stmt.comments = comments,
+            Statement::Expression(stmt) => stmt.comments = comments,
+            Statement::ForIn(stmt) => stmt.comments = comments,
+        }
+    }
+
+    // Set by the parser once the last token of the statement is known.
+    pub(crate) fn set_span(&mut self, span: Span) {
+        match self {
+            Statement::Let(stmt) => stmt.span = span,
+            Statement::Const(stmt) => stmt.span = span,
+            Statement::Import(stmt) => stmt.span = span,
+            Statement::Return(stmt) => stmt.span = span,
+            Statement::Expression(stmt) => stmt.span = span,
+            Statement::ForIn(stmt) => stmt.span = span,
+        }
+    }
+
+    // Where the statement starts in the source.
+    pub fn position(&self) -> Position {
+        match self {
+            Statement::Let(stmt) => stmt.token.position,
+            Statement::Const(stmt) => stmt.token.position,
+            Statement::Import(stmt) => stmt.token.position,
+            Statement::Return(stmt) => stmt.token.position,
+            Statement::Expression(stmt) => stmt.token.position,
+            Statement::ForIn(stmt) => stmt.token.position,
+        }
+    }
+
+    // The node held by the variant, for what all nodes have in common.
+    fn node(&self) -> &dyn Node {
+        match self {
+            Statement::Let(stmt) => stmt,
+            Statement::Const(stmt) => stmt,
+            Statement::Import(stmt) => stmt,
+            Statement::Return(stmt) => stmt,
+            Statement::Expression(stmt) => stmt,
+            Statement::ForIn(stmt) => stmt,
+        }
+    }
 }
 
 // Expression produces value.
-pub trait Expression: Node {
-    // This dummy method is used for debugging.
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn Any;
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expression {
+    Identifier(Identifier),
+    IntegerLiteral(IntegerLiteral),
+    FloatLiteral(FloatLiteral),
+    Boolean(Boolean),
+    StringLiteral(StringLiteral),
+    ArrayLiteral(ArrayLiteral),
+    HashLiteral(HashLiteral),
+    Prefix(PrefixExpression),
+    Postfix(PostfixExpression),
+    Infix(InfixExpression),
+    If(IfExpression),
+    FunctionLiteral(FunctionLiteral),
+    Call(CallExpression),
+    Index(IndexExpression),
+    Ternary(TernaryExpression),
+    PropertyAccess(PropertyAccess),
+    MethodCall(MethodCallExpression),
+    MacroLiteral(MacroLiteral),
+    Assign(AssignExpression),
+}
+
+impl Node for Expression {
+    fn token_literal(&self) -> String {
+        self.node().token_literal()
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.node(), f)
+    }
+}
+
+impl Expression {
+    // The as_* accessors return the node if the expression is of that kind.
+    pub fn as_identifier(&self) -> Option<&Identifier> {
+        match self {
+            Expression::Identifier(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer_literal(&self) -> Option<&IntegerLiteral> {
+        match self {
+            Expression::IntegerLiteral(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_float_literal(&self) -> Option<&FloatLiteral> {
+        match self {
+            Expression::FloatLiteral(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_boolean(&self) -> Option<&Boolean> {
+        match self {
+            Expression::Boolean(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_string_literal(&self) -> Option<&StringLiteral> {
+        match self {
+            Expression::StringLiteral(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_literal(&self) -> Option<&ArrayLiteral> {
+        match self {
+            Expression::ArrayLiteral(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_hash_literal(&self) -> Option<&HashLiteral> {
+        match self {
+            Expression::HashLiteral(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_prefix(&self) -> Option<&PrefixExpression> {
+        match self {
+            Expression::Prefix(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_postfix(&self) -> Option<&PostfixExpression> {
+        match self {
+            Expression::Postfix(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_infix(&self) -> Option<&InfixExpression> {
+        match self {
+            Expression::Infix(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_if(&self) -> Option<&IfExpression> {
+        match self {
+            Expression::If(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_function_literal(&self) -> Option<&FunctionLiteral> {
+        match self {
+            Expression::FunctionLiteral(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_call(&self) -> Option<&CallExpression> {
+        match self {
+            Expression::Call(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_index(&self) -> Option<&IndexExpression> {
+        match self {
+            Expression::Index(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_ternary(&self) -> Option<&TernaryExpression> {
+        match self {
+            Expression::Ternary(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_property_access(&self) -> Option<&PropertyAccess> {
+        match self {
+            Expression::PropertyAccess(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_method_call(&self) -> Option<&MethodCallExpression> {
+        match self {
+            Expression::MethodCall(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_macro_literal(&self) -> Option<&MacroLiteral> {
+        match self {
+            Expression::MacroLiteral(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn as_assign(&self) -> Option<&AssignExpression> {
+        match self {
+            Expression::Assign(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    // The name of the variant, as used by Program::stats().
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Expression::Identifier(_) => "Identifier",
+            Expression::IntegerLiteral(_) => "IntegerLiteral",
+            Expression::FloatLiteral(_) => "FloatLiteral",
+            Expression::Boolean(_) => "Boolean",
+            Expression::StringLiteral(_) => "StringLiteral",
+            Expression::ArrayLiteral(_) => "ArrayLiteral",
+            Expression::HashLiteral(_) => "HashLiteral",
+            Expression::Prefix(_) => "Prefix",
+            Expression::Postfix(_) => "Postfix",
+            Expression::Infix(_) => "Infix",
+            Expression::If(_) => "If",
+            Expression::FunctionLiteral(_) => "FunctionLiteral",
+            Expression::Call(_) => "Call",
+            Expression::Index(_) => "Index",
+            Expression::Ternary(_) => "Ternary",
+            Expression::PropertyAccess(_) => "PropertyAccess",
+            Expression::MethodCall(_) => "MethodCall",
+            Expression::MacroLiteral(_) => "MacroLiteral",
+            Expression::Assign(_) => "Assign",
+        }
+    }
+
+    // The node held by the variant, for what all nodes have in common.
+    fn node(&self) -> &dyn Node {
+        match self {
+            Expression::Identifier(expr) => expr,
+            Expression::IntegerLiteral(expr) => expr,
+            Expression::FloatLiteral(expr) => expr,
+            Expression::Boolean(expr) => expr,
+            Expression::StringLiteral(expr) => expr,
+            Expression::ArrayLiteral(expr) => expr,
+            Expression::HashLiteral(expr) => expr,
+            Expression::Prefix(expr) => expr,
+            Expression::Postfix(expr) => expr,
+            Expression::Infix(expr) => expr,
+            Expression::If(expr) => expr,
+            Expression::FunctionLiteral(expr) => expr,
+            Expression::Call(expr) => expr,
+            Expression::Index(expr) => expr,
+            Expression::Ternary(expr) => expr,
+            Expression::PropertyAccess(expr) => expr,
+            Expression::MethodCall(expr) => expr,
+            Expression::MacroLiteral(expr) => expr,
+            Expression::Assign(expr) => expr,
+        }
+    }
 }
 
 // ============================================================================
@@ -34,14 +424,19 @@ pub trait Expression: Node {
 // ============================================================================
 // This is the root of our AST.
 #[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
-    // 1. As we are using a trait as a type we need to use dynamic dispatch to
-    // allow compiler to decide at runtime which type to use.
-    // 2. Size of Statement is not known at compile time because different types
-    // can implement the Statement. To solve that we can use Box smartpointer
-    // that allocates the data on the Heap. So know the size is the size of the
-    // smart pointer and it is known at compile time.
-    pub statements: Vec<Box<dyn Statement>>,
+    pub statements: Vec<Statement>,
+    // The comments after the last statement, the other ones are kept by the
+    // statements they are next to.
+    pub comments: Vec<Trivia>,
+}
+
+impl PartialEq for Program {
+    fn eq(&self, other: &Self) -> bool {
+        self.statements == other.statements
+    }
 }
 
 impl Default for Program {
@@ -55,13 +450,108 @@ impl Program {
     pub fn new() -> Self {
         Program {
             statements: Vec::new(),
+            comments: Vec::new(),
         }
     }
 
-    pub fn push(&mut self, stmt: Box<dyn Statement>) {
+    pub fn push(&mut self, stmt: Statement) {
         self.statements.push(stmt);
     }
 
+    // Print the program as source, one statement per line and the content of
+    // blocks indented by indent_width spaces per level. Operators keep the
+    // parentheses of Display so the precedence stays visible.
+    pub fn pretty(&self, indent_width: usize) -> String {
+        let mut printer = PrettyPrinter::new(indent_width);
+        for stmt in &self.statements {
+            printer.statement(stmt);
+        }
+        printer.comments(&self.comments);
+        printer.out
+    }
+
+    // The top-level statement whose span contains the byte offset, e.g. to
+    // find the statement under the cursor of an editor.
+    pub fn statement_at(&self, offset: usize) -> Option<&Statement> {
+        self.statements.iter().find(|stmt| {
+            let span = stmt.span();
+            span.start <= offset && offset < span.end
+        })
+    }
+
+    // All the statements of the program in pre-order, the ones nested in
+    // blocks included.
+    pub fn statements(&self) -> impl Iterator<Item = &Statement> {
+        PreOrder::new(self).filter_map(|node| match node {
+            NodeRef::Statement(stmt) => Some(stmt),
+            NodeRef::Expression(_) => None,
+        })
+    }
+
+    // All the expressions of the program in pre-order: an expression comes
+    // before its operands, and the ones in nested blocks are included.
+    pub fn expressions(&self) -> impl Iterator<Item = &Expression> {
+        PreOrder::new(self).filter_map(|node| match node {
+            NodeRef::Statement(_) => None,
+            NodeRef::Expression(expr) => Some(expr),
+        })
+    }
+
+    // The identifiers used as expressions, the names being bound by let,
+    // const, for-in or parameters are not.
+    pub fn find_identifiers(&self) -> impl Iterator<Item = &Identifier> {
+        self.expressions().filter_map(Expression::as_identifier)
+    }
+
+    // All the let statements, including the ones in functions and blocks.
+    pub fn lets(&self) -> impl Iterator<Item = &LetStatement> {
+        self.statements().filter_map(Statement::as_let)
+    }
+
+    // Count the nodes of the program, see AstStats.
+    pub fn stats(&self) -> AstStats {
+        let mut stats = AstStats::default();
+        let mut counter = StatsCounter {
+            stats: &mut stats,
+            depth: 0,
+        };
+        counter.visit_program(self);
+        stats
+    }
+
+    // Print the program as it was written in source, byte for byte, where
+    // pretty() normalizes it. The statements are copied from the source
+    // through their span with the whitespace and comments around them.
+    // Statements that don't come from the source, because they were built by
+    // hand or moved, are pretty-printed in their place, and the code of the
+    // statements removed from the program is left out with the whitespace
+    // and comments that follow it. So a tool can rewrite a few statements and
+    // leave the rest of the file untouched.
+    pub fn print_lossless(&self, source: &str) -> String {
+        let mut out = String::new();
+        let mut pos = 0;
+
+        for stmt in &self.statements {
+            let span = stmt.span();
+            let trivia = leading_trivia(&source[pos..]);
+            out.push_str(trivia);
+
+            if span.start < pos || span.start == span.end || span.end > source.len() {
+                pos += trivia.len();
+                let mut printer = PrettyPrinter::new(4);
+                printer.statement(stmt);
+                out.push_str(&printer.out);
+                continue;
+            }
+
+            out.push_str(&source[span.start..span.end]);
+            pos = span.end;
+        }
+
+        out.push_str(leading_trivia(&source[pos..]));
+        out
+    }
+
     pub fn token_literal(&self) -> String {
         if !self.statements.is_empty() {
             self.statements[0].token_literal()
@@ -69,13 +559,179 @@ impl Program {
             String::from("")
         }
     }
+}
 
-    pub fn string(&self) -> String {
-        let mut out = String::new();
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for stmt in &self.statements {
-            out.push_str(&stmt.string());
+            write!(f, "{}", stmt)?;
+        }
+        Ok(())
+    }
+}
+
+// The shape of a program, to limit what is accepted from untrusted sources or
+// to check what the parser produces.
+// - statements counts all statements, the ones nested in blocks too.
+// - max_depth is the deepest nesting of statements and expressions, a
+//   top-level statement being at depth 1.
+// - node_counts gives the number of statements and expressions by kind, see
+//   Statement::kind() and Expression::kind().
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AstStats {
+    pub statements: usize,
+    pub max_depth: usize,
+    pub node_counts: BTreeMap<&'static str, usize>,
+}
+
+struct StatsCounter<'a> {
+    stats: &'a mut AstStats,
+    depth: usize,
+}
+
+impl StatsCounter<'_> {
+    fn enter(&mut self, kind: &'static str) {
+        self.depth += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+        *self.stats.node_counts.entry(kind).or_default() += 1;
+    }
+}
+
+impl Visitor for StatsCounter<'_> {
+    fn visit_statement(&mut self, stmt: &Statement) {
+        self.stats.statements += 1;
+        self.enter(stmt.kind());
+        walk_statement(self, stmt);
+        self.depth -= 1;
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        self.enter(expr.kind());
+        walk_expression(self, expr);
+        self.depth -= 1;
+    }
+}
+
+// The whitespace and comments at the start of the text, as written.
+fn leading_trivia(text: &str) -> &str {
+    let token = Lexer::with_trivia(text).next_token();
+    let len = token.leading_trivia.iter().map(|t| t.text.len()).sum();
+    &text[..len]
+}
+
+// The pre-order walk behind Program::statements() and expressions(). The
+// nodes still to be visited are kept on a stack, the next one on top, so the
+// walk doesn't recurse.
+enum NodeRef<'a> {
+    Statement(&'a Statement),
+    Expression(&'a Expression),
+}
+
+struct PreOrder<'a> {
+    stack: Vec<NodeRef<'a>>,
+}
+
+impl<'a> PreOrder<'a> {
+    fn new(program: &'a Program) -> Self {
+        let mut walk = PreOrder { stack: Vec::new() };
+        walk.push_statements(&program.statements);
+        walk
+    }
+
+    fn push_statements(&mut self, statements: &'a [Statement]) {
+        self.stack
+            .extend(statements.iter().rev().map(NodeRef::Statement));
+    }
+
+    fn push_expressions(&mut self, exprs: &'a [Expression]) {
+        self.stack
+            .extend(exprs.iter().rev().map(NodeRef::Expression));
+    }
+
+    fn push_expression(&mut self, expr: &'a Expression) {
+        self.stack.push(NodeRef::Expression(expr));
+    }
+
+    // Children are pushed last first so they come out in source order.
+    fn push_children(&mut self, node: &NodeRef<'a>) {
+        match *node {
+            NodeRef::Statement(stmt) => match stmt {
+                Statement::Let(stmt) => {
+                    if let Some(value) = &stmt.value {
+                        self.push_expression(value);
+                    }
+                }
+                Statement::Const(stmt) => self.push_expression(&stmt.value),
+                Statement::Import(_) => {}
+                Statement::Return(stmt) => self.push_expression(&stmt.return_value),
+                Statement::Expression(stmt) => self.push_expression(&stmt.expression),
+                Statement::ForIn(stmt) => {
+                    self.push_statements(&stmt.body.statements);
+                    self.push_expression(&stmt.iterable);
+                }
+            },
+            NodeRef::Expression(expr) => match expr {
+                Expression::Identifier(_)
+                | Expression::IntegerLiteral(_)
+                | Expression::FloatLiteral(_)
+                | Expression::Boolean(_)
+                | Expression::StringLiteral(_) => {}
+                Expression::ArrayLiteral(lit) => self.push_expressions(&lit.elements),
+                Expression::HashLiteral(lit) => {
+                    for (key, value) in lit.pairs.iter().rev() {
+                        self.push_expression(value);
+                        self.push_expression(key);
+                    }
+                }
+                Expression::Prefix(expr) => self.push_expression(&expr.right),
+                Expression::Postfix(expr) => self.push_expression(&expr.left),
+                Expression::Infix(expr) => {
+                    self.push_expression(&expr.right);
+                    self.push_expression(&expr.left);
+                }
+                Expression::If(expr) => {
+                    if let Some(alternative) = &expr.alternative {
+                        self.push_statements(&alternative.statements);
+                    }
+                    self.push_statements(&expr.consequence.statements);
+                    self.push_expression(&expr.condition);
+                }
+                Expression::FunctionLiteral(lit) => self.push_statements(&lit.body.statements),
+                Expression::Call(expr) => {
+                    self.push_expressions(&expr.arguments);
+                    self.push_expression(&expr.function);
+                }
+                Expression::Index(expr) => {
+                    self.push_expression(&expr.index);
+                    self.push_expression(&expr.left);
+                }
+                Expression::Ternary(expr) => {
+                    self.push_expression(&expr.alternative);
+                    self.push_expression(&expr.consequence);
+                    self.push_expression(&expr.condition);
+                }
+                Expression::PropertyAccess(expr) => self.push_expression(&expr.object),
+                Expression::MethodCall(expr) => {
+                    self.push_expressions(&expr.arguments);
+                    self.push_expression(&expr.object);
+                }
+                Expression::MacroLiteral(lit) => self.push_statements(&lit.body.statements),
+                Expression::Assign(expr) => {
+                    self.push_expression(&expr.value);
+                    self.push_expression(&expr.target);
+                }
+            },
         }
-        out
+    }
+}
+
+impl<'a> Iterator for PreOrder<'a> {
+    type Item = NodeRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_children(&node);
+        Some(node)
     }
 }
 
@@ -86,13 +742,14 @@ impl Program {
 // Let's have a look to `let x = 5 * 5;`
 // - We need a node for the token `let`.
 // - We need a node for the variable name `x`.
-// - We need a node for the expression that produces the value.
+// - We need a node for the expression that produces the value. It is
+//   optional: `let x;` declares a binding that is assigned later.
 
 #[allow(dead_code)]
-pub struct LetStatementBuilder {
+pub struct LetStatementBuilder<Name = Missing> {
     token: Token,
-    name: Option<Identifier>,
-    value: Option<Box<dyn Expression>>,
+    name: Name,
+    value: Option<Expression>,
 }
 
 #[allow(dead_code)]
@@ -100,58 +757,71 @@ impl LetStatementBuilder {
     pub fn new(token: &Token) -> Self {
         LetStatementBuilder {
             token: token.clone(),
-            name: None,
+            name: Missing,
             value: None,
         }
     }
+}
 
-    pub fn name(&mut self, name: Identifier) {
-        self.name = Some(name);
+impl<Name> LetStatementBuilder<Name> {
+    pub fn name(self, name: Identifier) -> LetStatementBuilder<Identifier> {
+        LetStatementBuilder {
+            token: self.token,
+            name,
+            value: self.value,
+        }
     }
 
-    pub fn value(&mut self, value: Option<Box<dyn Expression>>) {
+    pub fn value(mut self, value: Option<Expression>) -> Self {
         self.value = value;
+        self
     }
+}
 
+impl LetStatementBuilder<Identifier> {
     pub fn build(self) -> LetStatement {
         LetStatement {
+            span: self.token.span,
+            comments: Vec::new(),
             token: self.token,
-            name: self.name.unwrap(),
-            value: self.value.unwrap(),
+            name: self.name,
+            value: self.value.map(Box::new),
         }
     }
 }
 
 #[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LetStatement {
     token: Token, // The token.LET token.
     name: Identifier,
-    value: Box<dyn Expression>,
+    value: Option<Box<Expression>>, // None for `let x;`, assigned later.
+    span: Span,
+    comments: Vec<Trivia>,
 }
 
 impl Node for LetStatement {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
+}
 
-    fn string(&self) -> String {
-        let mut out = String::new();
-        out.push_str(&self.token_literal());
-        out.push(' ');
-        out.push_str(&self.name.value);
-        out.push_str(" = ");
+impl fmt::Display for LetStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.token_literal(), self.name.value)?;
 
-        out.push_str(&self.value.string());
+        if let Some(value) = &self.value {
+            write!(f, " = {}", value)?;
+        }
 
-        out.push(';');
-        out
+        f.write_str(";")
     }
 }
 
-impl Statement for LetStatement {
-    fn statement_node(&self) {}
-    fn as_any(&self) -> &dyn Any {
-        self
+impl PartialEq for LetStatement {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value == other.value
     }
 }
 
@@ -160,117 +830,361 @@ impl LetStatement {
     pub fn name(&self) -> &str {
         self.name.value.as_str()
     }
+
+    pub fn value(&self) -> Option<&Expression> {
+        self.value.as_deref()
+    }
 }
 
 // ============================================================================
-// RETURN STATEMENT
+// CONST STATEMENT
 // ============================================================================
+// ConstStatement binds a value to a name that cannot be rebound.
+// `const x = 5;` looks like a let statement but is kept apart so that the
+// binding can be enforced as immutable.
+
 #[allow(dead_code)]
-pub struct ReturnStatementBuilder {
-    token: Token,
-    return_value: Option<Box<dyn Expression>>,
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstStatement {
+    token: Token, // The token.CONST token.
+    name: Identifier,
+    value: Box<Expression>,
+    span: Span,
+    comments: Vec<Trivia>,
 }
 
-impl ReturnStatementBuilder {
-    pub fn new(token: &Token) -> Self {
-        ReturnStatementBuilder {
+impl Node for ConstStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for ConstStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} = {};",
+            self.token_literal(),
+            self.name.value,
+            self.value
+        )
+    }
+}
+
+impl PartialEq for ConstStatement {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value == other.value
+    }
+}
+
+#[allow(dead_code)]
+impl ConstStatement {
+    pub fn new(token: &Token, name: Identifier, value: Expression) -> Self {
+        ConstStatement {
             token: token.clone(),
-            return_value: None,
+            name,
+            value: Box::new(value),
+            span: token.span,
+            comments: Vec::new(),
         }
     }
 
-    pub fn return_value(&mut self, return_value: Option<Box<dyn Expression>>) {
-        self.return_value = return_value;
+    pub fn name(&self) -> &str {
+        self.name.value.as_str()
     }
 
-    pub fn build(self) -> ReturnStatement {
-        ReturnStatement {
-            token: self.token,
-            return_value: self.return_value.unwrap(),
-        }
+    pub fn value(&self) -> &Expression {
+        self.value.as_ref()
     }
 }
 
-#[allow(dead_code)]
+// ============================================================================
+// IMPORT STATEMENT
+// ============================================================================
+// import "<path>";
+// It names a module to load, for example `import "std/math";`.
 
-pub struct ReturnStatement {
-    pub token: Token, // The token.RETURN token.
-    pub return_value: Box<dyn Expression>,
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImportStatement {
+    token: Token, // The token.IMPORT token.
+    path: String,
+    span: Span,
+    comments: Vec<Trivia>,
 }
 
-impl Node for ReturnStatement {
+impl Node for ImportStatement {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
+}
 
-    fn string(&self) -> String {
-        let mut out = String::new();
-        out.push_str(&self.token_literal());
-        out.push(' ');
-        out.push_str(&self.return_value.string());
-        out.push(';');
-        out
+impl fmt::Display for ImportStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} \"{}\";", self.token_literal(), self.path)
     }
 }
 
-impl Statement for ReturnStatement {
-    fn statement_node(&self) {}
-    fn as_any(&self) -> &dyn Any {
-        self
+impl PartialEq for ImportStatement {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl ImportStatement {
+    pub fn new(token: &Token, path: &str) -> Self {
+        ImportStatement {
+            token: token.clone(),
+            path: path.to_string(),
+            span: token.span,
+            comments: Vec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        self.path.as_str()
     }
 }
 
 // ============================================================================
-// EXPRESSION STATEMENT
+// RETURN STATEMENT
 // ============================================================================
 #[allow(dead_code)]
-pub struct ExpressionStatementBuilder {
+pub struct ReturnStatementBuilder<Value = Missing> {
     token: Token,
-    expression: Option<Box<dyn Expression>>,
+    return_value: Value,
 }
 
-#[allow(dead_code)]
-impl ExpressionStatementBuilder {
+impl ReturnStatementBuilder {
     pub fn new(token: &Token) -> Self {
-        ExpressionStatementBuilder {
+        ReturnStatementBuilder {
             token: token.clone(),
-            expression: None,
+            return_value: Missing,
         }
     }
+}
 
-    pub fn expression(&mut self, expression: Option<Box<dyn Expression>>) {
-        self.expression = expression;
+impl<Value> ReturnStatementBuilder<Value> {
+    pub fn return_value(self, return_value: Expression) -> ReturnStatementBuilder<Expression> {
+        ReturnStatementBuilder {
+            token: self.token,
+            return_value,
+        }
     }
+}
 
+impl ReturnStatementBuilder<Expression> {
+    pub fn build(self) -> ReturnStatement {
+        ReturnStatement {
+            span: self.token.span,
+            comments: Vec::new(),
+            token: self.token,
+            return_value: Box::new(self.return_value),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReturnStatement {
+    pub token: Token, // The token.RETURN token.
+    pub return_value: Box<Expression>,
+    pub span: Span,
+    pub comments: Vec<Trivia>,
+}
+
+impl Node for ReturnStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for ReturnStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {};", self.token_literal(), self.return_value)
+    }
+}
+
+impl PartialEq for ReturnStatement {
+    fn eq(&self, other: &Self) -> bool {
+        self.return_value == other.return_value
+    }
+}
+
+// ============================================================================
+// EXPRESSION STATEMENT
+// ============================================================================
+#[allow(dead_code)]
+pub struct ExpressionStatementBuilder<Expr = Missing> {
+    token: Token,
+    expression: Expr,
+}
+
+#[allow(dead_code)]
+impl ExpressionStatementBuilder {
+    pub fn new(token: &Token) -> Self {
+        ExpressionStatementBuilder {
+            token: token.clone(),
+            expression: Missing,
+        }
+    }
+}
+
+impl<Expr> ExpressionStatementBuilder<Expr> {
+    pub fn expression(self, expression: Expression) -> ExpressionStatementBuilder<Expression> {
+        ExpressionStatementBuilder {
+            token: self.token,
+            expression,
+        }
+    }
+}
+
+impl ExpressionStatementBuilder<Expression> {
     pub fn build(self) -> ExpressionStatement {
         ExpressionStatement {
+            span: self.token.span,
+            comments: Vec::new(),
             token: self.token,
-            expression: self.expression.unwrap(),
+            expression: Box::new(self.expression),
         }
     }
 }
 
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExpressionStatement {
     pub token: Token, // The first token of the expression.
-    pub expression: Box<dyn Expression>,
+    pub expression: Box<Expression>,
+    pub span: Span,
+    pub comments: Vec<Trivia>,
 }
 
 impl Node for ExpressionStatement {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
+}
 
-    fn string(&self) -> String {
-        let mut out = String::new();
-        out.push_str(&self.expression.string());
-        out
+impl fmt::Display for ExpressionStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expression)
     }
 }
 
-impl Statement for ExpressionStatement {
-    fn statement_node(&self) {}
-    fn as_any(&self) -> &dyn Any {
-        self
+impl PartialEq for ExpressionStatement {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression
+    }
+}
+
+// ============================================================================
+// BLOCK STATEMENT
+// ============================================================================
+// A block is a list of statements enclosed in braces, it is used as the body
+// of loops, functions and conditionals.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockStatement {
+    pub token: Token, // The '{' token.
+    pub statements: Vec<Statement>,
+    pub comments: Vec<Trivia>, // The comments after the last statement.
+}
+
+impl Node for BlockStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for stmt in &self.statements {
+            write!(f, "{}", stmt)?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for BlockStatement {
+    fn eq(&self, other: &Self) -> bool {
+        self.statements == other.statements
+    }
+}
+
+impl BlockStatement {
+    pub fn new(token: &Token, statements: Vec<Statement>) -> Self {
+        BlockStatement {
+            token: token.clone(),
+            statements,
+            comments: Vec::new(),
+        }
+    }
+}
+
+// ============================================================================
+// FOR-IN STATEMENT
+// ============================================================================
+// for (<identifier> in <expression>) <block statement>
+// The identifier is bound to each element of what the expression produces
+// before running the body.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForInStatement {
+    pub token: Token, // The 'for' token.
+    pub variable: Identifier,
+    pub iterable: Box<Expression>,
+    pub body: BlockStatement,
+    pub span: Span,
+    pub comments: Vec<Trivia>,
+}
+
+impl Node for ForInStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for ForInStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} in {}) {}",
+            self.token_literal(),
+            self.variable,
+            self.iterable,
+            self.body
+        )
+    }
+}
+
+impl PartialEq for ForInStatement {
+    fn eq(&self, other: &Self) -> bool {
+        self.variable == other.variable
+            && self.iterable == other.iterable
+            && self.body == other.body
+    }
+}
+
+impl ForInStatement {
+    pub fn new(
+        token: &Token,
+        variable: Identifier,
+        iterable: Expression,
+        body: BlockStatement,
+    ) -> Self {
+        ForInStatement {
+            token: token.clone(),
+            variable,
+            iterable: Box::new(iterable),
+            body,
+            span: token.span,
+            comments: Vec::new(),
+        }
     }
 }
 
@@ -279,6 +1193,8 @@ impl Statement for ExpressionStatement {
 // ============================================================================
 // Identifier is a node that holds the name of the variable.
 #[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Identifier {
     token: Token,  // The token.IDENT token.
     value: String, // The value of the identifier.
@@ -288,16 +1204,17 @@ impl Node for Identifier {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
+}
 
-    fn string(&self) -> String {
-        self.value.clone()
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.value)
     }
 }
 
-impl Expression for Identifier {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn Any {
-        self
+impl PartialEq for Identifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
     }
 }
 
@@ -309,12 +1226,18 @@ impl Identifier {
             value: token.literal(),
         }
     }
+
+    pub fn value(&self) -> &str {
+        self.value.as_str()
+    }
 }
 
 // ============================================================================
 // INTEGER LITERAL EXPRESSION
 // ============================================================================
 #[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IntegerLiteral {
     token: Token, // The token.INT token.
     value: i64,   // The value of the integer literal.
@@ -324,16 +1247,17 @@ impl Node for IntegerLiteral {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
+}
 
-    fn string(&self) -> String {
-        self.token.literal()
+impl fmt::Display for IntegerLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.token.literal())
     }
 }
 
-impl Expression for IntegerLiteral {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn Any {
-        self
+impl PartialEq for IntegerLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
     }
 }
 
@@ -352,144 +1276,2082 @@ impl IntegerLiteral {
 }
 
 // ============================================================================
-// PREFIX EXPRESSION
+// FLOAT LITERAL EXPRESSION
 // ============================================================================
 #[allow(dead_code)]
-pub struct PrefixExpressionBuilder {
-    pub token: Token, // The prefix token, Monkey has only ! and -.
-    pub operator: Option<String>,
-    pub right: Option<Box<dyn Expression>>,
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FloatLiteral {
+    token: Token, // The token.FLOAT token.
+    value: f64,   // The value of the float literal.
 }
 
-impl PrefixExpressionBuilder {
-    pub fn new(token: &Token) -> Self {
-        PrefixExpressionBuilder {
+impl Node for FloatLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+// Written as in the source, 1e3 stays 1e3.
+impl fmt::Display for FloatLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.token.literal())
+    }
+}
+
+impl PartialEq for FloatLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+#[allow(dead_code)]
+impl FloatLiteral {
+    pub fn new(token: &Token, value: f64) -> Self {
+        FloatLiteral {
+            token: token.clone(),
+            value,
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+// ============================================================================
+// BOOLEAN EXPRESSION
+// ============================================================================
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Boolean {
+    token: Token, // The token.TRUE or token.FALSE token.
+    value: bool,
+}
+
+impl Node for Boolean {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for Boolean {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.token.literal())
+    }
+}
+
+impl PartialEq for Boolean {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+#[allow(dead_code)]
+impl Boolean {
+    pub fn new(token: &Token, value: bool) -> Self {
+        Boolean {
             token: token.clone(),
-            operator: None,
-            right: None,
+            value,
         }
     }
 
-    pub fn operator(&mut self, operator: String) {
-        self.operator = Some(operator);
+    pub fn value(&self) -> bool {
+        self.value
     }
+}
+
+// ============================================================================
+// STRING LITERAL EXPRESSION
+// ============================================================================
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StringLiteral {
+    token: Token,  // The token.STRING token.
+    value: String, // The decoded content, without the quotes.
+}
 
-    pub fn right(&mut self, right: Option<Box<dyn Expression>>) {
-        self.right = right;
+impl Node for StringLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal()
     }
+}
 
-    pub fn build(self) -> PrefixExpression {
-        PrefixExpression {
-            token: self.token,
-            operator: self.operator.unwrap(),
-            right: self.right.unwrap(),
+// The string is written back quoted, with the escape sequences the lexer
+// knows, so it can't be taken for an identifier.
+impl fmt::Display for StringLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_char('"')?;
+        for c in self.value.chars() {
+            match c {
+                '\n' => f.write_str("\\n")?,
+                '\t' => f.write_str("\\t")?,
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+impl PartialEq for StringLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+#[allow(dead_code)]
+impl StringLiteral {
+    pub fn new(token: &Token, value: &str) -> Self {
+        StringLiteral {
+            token: token.clone(),
+            value: value.to_string(),
         }
     }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
 }
 
+// ============================================================================
+// ARRAY LITERAL EXPRESSION
+// ============================================================================
+// An array literal is a comma separated list of expressions enclosed in
+// brackets: [1, 2 * 3, fn(x) { x }]
 #[allow(dead_code)]
-pub struct PrefixExpression {
-    pub token: Token, // The prefix token, Monkey has only ! and -.
-    pub operator: String,
-    pub right: Box<dyn Expression>,
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArrayLiteral {
+    pub token: Token, // The '[' token.
+    pub elements: Vec<Expression>,
 }
 
-impl Node for PrefixExpression {
+impl Node for ArrayLiteral {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
+}
 
-    fn string(&self) -> String {
-        let mut out = String::new();
+impl fmt::Display for ArrayLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[")?;
+        write_list(f, &self.elements)?;
+        f.write_str("]")
+    }
+}
 
-        out.push('(');
-        out.push_str(self.operator.as_str());
-        out.push_str(&self.right.string());
-        out.push(')');
-        out
+impl PartialEq for ArrayLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        self.elements == other.elements
     }
 }
 
-impl Expression for PrefixExpression {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn Any {
-        self
+impl ArrayLiteral {
+    pub fn new(token: &Token, elements: Vec<Expression>) -> Self {
+        ArrayLiteral {
+            token: token.clone(),
+            elements,
+        }
     }
 }
 
 // ============================================================================
-// INFIX EXPRESSION
+// HASH LITERAL EXPRESSION
 // ============================================================================
+// A hash literal is a comma separated list of key: value pairs enclosed in
+// braces: {"one": 1, 2: fn(x) { x }}
 #[allow(dead_code)]
-pub struct InfixExpressionBuilder {
-    pub token: Token, // The prefix token: +, -, *, /, <, > ...
-    pub left: Option<Box<dyn Expression>>,
-    pub operator: Option<String>,
-    pub right: Option<Box<dyn Expression>>,
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HashLiteral {
+    pub token: Token, // The '{' token.
+    pub pairs: Vec<(Expression, Expression)>,
 }
 
-impl InfixExpressionBuilder {
-    pub fn new(token: &Token) -> Self {
-        InfixExpressionBuilder {
+impl Node for HashLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for HashLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("{")?;
+        for (i, (key, value)) in self.pairs.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}: {}", key, value)?;
+        }
+        f.write_str("}")
+    }
+}
+
+impl PartialEq for HashLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        self.pairs == other.pairs
+    }
+}
+
+impl HashLiteral {
+    pub fn new(token: &Token, pairs: Vec<(Expression, Expression)>) -> Self {
+        HashLiteral {
             token: token.clone(),
-            left: None,
-            operator: None,
-            right: None,
+            pairs,
         }
     }
+}
+
+// ============================================================================
+// PREFIX EXPRESSION
+// ============================================================================
+#[allow(dead_code)]
+pub struct PrefixExpressionBuilder<Operator = Missing, Right = Missing> {
+    token: Token, // The prefix token, Monkey has only ! and -.
+    operator: Operator,
+    right: Right,
+}
 
-    pub fn left(&mut self, left: Option<Box<dyn Expression>>) {
-        self.left = left;
+impl PrefixExpressionBuilder {
+    pub fn new(token: &Token) -> Self {
+        PrefixExpressionBuilder {
+            token: token.clone(),
+            operator: Missing,
+            right: Missing,
+        }
     }
+}
 
-    pub fn operator(&mut self, operator: String) {
-        self.operator = Some(operator);
+impl<Operator, Right> PrefixExpressionBuilder<Operator, Right> {
+    pub fn operator(self, operator: String) -> PrefixExpressionBuilder<String, Right> {
+        PrefixExpressionBuilder {
+            token: self.token,
+            operator,
+            right: self.right,
+        }
     }
 
-    pub fn right(&mut self, right: Option<Box<dyn Expression>>) {
-        self.right = right;
+    pub fn right(self, right: Expression) -> PrefixExpressionBuilder<Operator, Expression> {
+        PrefixExpressionBuilder {
+            token: self.token,
+            operator: self.operator,
+            right,
+        }
     }
+}
 
-    pub fn build(self) -> InfixExpression {
-        InfixExpression {
+impl PrefixExpressionBuilder<String, Expression> {
+    pub fn build(self) -> PrefixExpression {
+        PrefixExpression {
             token: self.token,
-            left: self.left.unwrap(),
-            operator: self.operator.unwrap(),
-            right: self.right.unwrap(),
+            operator: self.operator,
+            right: Box::new(self.right),
         }
     }
 }
 
 #[allow(dead_code)]
-pub struct InfixExpression {
-    pub token: Token, // The prefix token: +, -, *, /, <, > ...
-    pub left: Box<dyn Expression>,
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrefixExpression {
+    pub token: Token, // The prefix token, Monkey has only ! and -.
     pub operator: String,
-    pub right: Box<dyn Expression>,
+    pub right: Box<Expression>,
 }
 
-impl Node for InfixExpression {
+impl Node for PrefixExpression {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
+}
 
-    fn string(&self) -> String {
-        let mut out = String::new();
+impl PrefixExpression {
+    // The operand can be a negative literal left by folding, and -- would be
+    // read back as one token. It is the only operand printed starting with -.
+    fn operand_separator(&self) -> &'static str {
+        let negative = match &*self.right {
+            Expression::IntegerLiteral(lit) => lit.value < 0,
+            Expression::FloatLiteral(lit) => lit.value.is_sign_negative(),
+            _ => false,
+        };
+        if self.operator == "-" && negative {
+            " "
+        } else {
+            ""
+        }
+    }
+}
 
-        out.push('(');
-        out.push_str(&self.left.string());
-        out.push(' ');
-        out.push_str(self.operator.as_str());
-        out.push(' ');
-        out.push_str(&self.right.string());
-        out.push(')');
-        out
+impl fmt::Display for PrefixExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({}{}{})",
+            self.operator,
+            self.operand_separator(),
+            self.right
+        )
     }
 }
 
-impl Expression for InfixExpression {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn Any {
-        self
+impl PartialEq for PrefixExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.operator == other.operator && self.right == other.right
+    }
+}
+
+// ============================================================================
+// POSTFIX EXPRESSION
+// ============================================================================
+// <expression><postfix operator>
+// Postfix operators are `++` and `--`, as in `i++`.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PostfixExpression {
+    pub token: Token, // The postfix token: ++ or --.
+    pub left: Box<Expression>,
+    pub operator: String,
+}
+
+impl Node for PostfixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for PostfixExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}{})", self.left, self.operator)
+    }
+}
+
+impl PartialEq for PostfixExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.left == other.left && self.operator == other.operator
+    }
+}
+
+impl PostfixExpression {
+    pub fn new(token: &Token, left: Expression) -> Self {
+        PostfixExpression {
+            token: token.clone(),
+            left: Box::new(left),
+            operator: token.literal(),
+        }
+    }
+}
+
+// ============================================================================
+// INFIX EXPRESSION
+// ============================================================================
+#[allow(dead_code)]
+pub struct InfixExpressionBuilder<Left = Missing, Operator = Missing, Right = Missing> {
+    token: Token, // The prefix token: +, -, *, /, <, > ...
+    left: Left,
+    operator: Operator,
+    right: Right,
+}
+
+impl InfixExpressionBuilder {
+    pub fn new(token: &Token) -> Self {
+        InfixExpressionBuilder {
+            token: token.clone(),
+            left: Missing,
+            operator: Missing,
+            right: Missing,
+        }
+    }
+}
+
+impl<Left, Operator, Right> InfixExpressionBuilder<Left, Operator, Right> {
+    pub fn left(self, left: Expression) -> InfixExpressionBuilder<Expression, Operator, Right> {
+        InfixExpressionBuilder {
+            token: self.token,
+            left,
+            operator: self.operator,
+            right: self.right,
+        }
+    }
+
+    pub fn operator(self, operator: String) -> InfixExpressionBuilder<Left, String, Right> {
+        InfixExpressionBuilder {
+            token: self.token,
+            left: self.left,
+            operator,
+            right: self.right,
+        }
+    }
+
+    pub fn right(self, right: Expression) -> InfixExpressionBuilder<Left, Operator, Expression> {
+        InfixExpressionBuilder {
+            token: self.token,
+            left: self.left,
+            operator: self.operator,
+            right,
+        }
+    }
+}
+
+impl InfixExpressionBuilder<Expression, String, Expression> {
+    pub fn build(self) -> InfixExpression {
+        InfixExpression {
+            token: self.token,
+            left: Box::new(self.left),
+            operator: self.operator,
+            right: Box::new(self.right),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InfixExpression {
+    pub token: Token, // The prefix token: +, -, *, /, <, > ...
+    pub left: Box<Expression>,
+    pub operator: String,
+    pub right: Box<Expression>,
+}
+
+impl Node for InfixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for InfixExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({} {} {})", self.left, self.operator, self.right)
+    }
+}
+
+impl PartialEq for InfixExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.left == other.left && self.operator == other.operator && self.right == other.right
+    }
+}
+
+// ============================================================================
+// IF EXPRESSION
+// ============================================================================
+// if (<condition>) <consequence> else <alternative>
+// The else part is optional. The expression produces the value of the block
+// that is run.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IfExpression {
+    pub token: Token, // The 'if' token.
+    pub condition: Box<Expression>,
+    pub consequence: BlockStatement,
+    pub alternative: Option<BlockStatement>,
+}
+
+impl Node for IfExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for IfExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "if{} {}", self.condition, self.consequence)?;
+
+        if let Some(alternative) = &self.alternative {
+            write!(f, "else {}", alternative)?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for IfExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.condition == other.condition
+            && self.consequence == other.consequence
+            && self.alternative == other.alternative
+    }
+}
+
+impl IfExpression {
+    pub fn new(
+        token: &Token,
+        condition: Expression,
+        consequence: BlockStatement,
+        alternative: Option<BlockStatement>,
+    ) -> Self {
+        IfExpression {
+            token: token.clone(),
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+        }
+    }
+}
+
+// ============================================================================
+// FUNCTION LITERAL
+// ============================================================================
+// fn(<comma separated identifiers>) <block statement>
+// For example `fn(x, y) { return x + y; }`. The lambda `|x, y| x + y` is
+// parsed into a function literal too.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionLiteral {
+    pub token: Token, // The 'fn' token, or the '|' of a lambda.
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+}
+
+impl Node for FunctionLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for FunctionLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("fn(")?;
+        write_list(f, &self.parameters)?;
+        write!(f, ") {}", self.body)
+    }
+}
+
+impl PartialEq for FunctionLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        self.parameters == other.parameters && self.body == other.body
+    }
+}
+
+impl FunctionLiteral {
+    pub fn new(token: &Token, parameters: Vec<Identifier>, body: BlockStatement) -> Self {
+        FunctionLiteral {
+            token: token.clone(),
+            parameters,
+            body,
+        }
+    }
+}
+
+// ============================================================================
+// CALL EXPRESSION
+// ============================================================================
+// A call is <expression>(<comma separated expressions>). The function can be
+// any expression that produces a function: an identifier as in `add(1, 2)` or
+// a function literal as in `fn(x) { x }(5)`.
+#[allow(dead_code)]
+pub struct CallExpressionBuilder<Function = Missing> {
+    token: Token, // The '(' token.
+    function: Function,
+    arguments: Vec<Expression>,
+}
+
+impl CallExpressionBuilder {
+    pub fn new(token: &Token) -> Self {
+        CallExpressionBuilder {
+            token: token.clone(),
+            function: Missing,
+            arguments: Vec::new(),
+        }
+    }
+}
+
+impl<Function> CallExpressionBuilder<Function> {
+    pub fn function(self, function: Expression) -> CallExpressionBuilder<Expression> {
+        CallExpressionBuilder {
+            token: self.token,
+            function,
+            arguments: self.arguments,
+        }
+    }
+
+    pub fn arguments(mut self, arguments: Vec<Expression>) -> Self {
+        self.arguments = arguments;
+        self
+    }
+}
+
+impl CallExpressionBuilder<Expression> {
+    pub fn build(self) -> CallExpression {
+        CallExpression {
+            token: self.token,
+            function: Box::new(self.function),
+            arguments: self.arguments,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CallExpression {
+    pub token: Token, // The '(' token.
+    pub function: Box<Expression>,
+    pub arguments: Vec<Expression>,
+}
+
+impl Node for CallExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for CallExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(", self.function)?;
+        write_list(f, &self.arguments)?;
+        f.write_str(")")
+    }
+}
+
+impl PartialEq for CallExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.function == other.function && self.arguments == other.arguments
+    }
+}
+
+impl CallExpression {
+    // The name of the called function when it is an identifier. It is how
+    // `quote(...)` and `unquote(...)` calls are recognized.
+    pub fn function_name(&self) -> Option<&str> {
+        match self.function.as_ref() {
+            Expression::Identifier(ident) => Some(ident.value()),
+            _ => None,
+        }
+    }
+}
+
+// ============================================================================
+// INDEX EXPRESSION
+// ============================================================================
+// An index expression is <expression>[<expression>], e.g. `myArray[1 + 1]`.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexExpression {
+    pub token: Token, // The '[' token.
+    pub left: Box<Expression>,
+    pub index: Box<Expression>,
+}
+
+impl Node for IndexExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for IndexExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}[{}])", self.left, self.index)
+    }
+}
+
+impl PartialEq for IndexExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.left == other.left && self.index == other.index
+    }
+}
+
+impl IndexExpression {
+    pub fn new(token: &Token, left: Expression, index: Expression) -> Self {
+        IndexExpression {
+            token: token.clone(),
+            left: Box::new(left),
+            index: Box::new(index),
+        }
+    }
+}
+
+// ============================================================================
+// TERNARY EXPRESSION
+// ============================================================================
+// <condition> ? <consequence> : <alternative>
+// It produces the consequence if the condition holds, the alternative
+// otherwise.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TernaryExpression {
+    pub token: Token, // The '?' token.
+    pub condition: Box<Expression>,
+    pub consequence: Box<Expression>,
+    pub alternative: Box<Expression>,
+}
+
+impl Node for TernaryExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for TernaryExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({} ? {} : {})",
+            self.condition, self.consequence, self.alternative
+        )
+    }
+}
+
+impl PartialEq for TernaryExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.condition == other.condition
+            && self.consequence == other.consequence
+            && self.alternative == other.alternative
+    }
+}
+
+impl TernaryExpression {
+    pub fn new(
+        token: &Token,
+        condition: Expression,
+        consequence: Expression,
+        alternative: Expression,
+    ) -> Self {
+        TernaryExpression {
+            token: token.clone(),
+            condition: Box::new(condition),
+            consequence: Box::new(consequence),
+            alternative: Box::new(alternative),
+        }
+    }
+}
+
+// ============================================================================
+// PROPERTY ACCESS
+// ============================================================================
+// <object>.<property>
+// For example `hash.keys`.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PropertyAccess {
+    pub token: Token, // The '.' token.
+    pub object: Box<Expression>,
+    pub property: Identifier,
+}
+
+impl Node for PropertyAccess {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for PropertyAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}.{})", self.object, self.property)
+    }
+}
+
+impl PartialEq for PropertyAccess {
+    fn eq(&self, other: &Self) -> bool {
+        self.object == other.object && self.property == other.property
+    }
+}
+
+impl PropertyAccess {
+    pub fn new(token: &Token, object: Expression, property: Identifier) -> Self {
+        PropertyAccess {
+            token: token.clone(),
+            object: Box::new(object),
+            property,
+        }
+    }
+}
+
+// ============================================================================
+// METHOD CALL EXPRESSION
+// ============================================================================
+// <object>.<method>(<comma separated expressions>)
+// For example `arr.len()`. It is the method style of calling `len(arr)`.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MethodCallExpression {
+    pub token: Token, // The '.' token.
+    pub object: Box<Expression>,
+    pub method: Identifier,
+    pub arguments: Vec<Expression>,
+}
+
+impl Node for MethodCallExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for MethodCallExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}.{}(", self.object, self.method)?;
+        write_list(f, &self.arguments)?;
+        f.write_str("))")
+    }
+}
+
+impl PartialEq for MethodCallExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.object == other.object
+            && self.method == other.method
+            && self.arguments == other.arguments
+    }
+}
+
+impl MethodCallExpression {
+    pub fn new(
+        token: &Token,
+        object: Expression,
+        method: Identifier,
+        arguments: Vec<Expression>,
+    ) -> Self {
+        MethodCallExpression {
+            token: token.clone(),
+            object: Box::new(object),
+            method,
+            arguments,
+        }
+    }
+}
+
+// ============================================================================
+// MACRO LITERAL
+// ============================================================================
+// macro(<comma separated identifiers>) <block statement>
+// For example `macro(x, y) { quote(unquote(y) - unquote(x)); }`. The
+// arguments of a macro are passed unevaluated, as quoted code.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MacroLiteral {
+    pub token: Token, // The 'macro' token.
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+}
+
+impl Node for MacroLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for MacroLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(", self.token_literal())?;
+        write_list(f, &self.parameters)?;
+        write!(f, ") {}", self.body)
+    }
+}
+
+impl PartialEq for MacroLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        self.parameters == other.parameters && self.body == other.body
+    }
+}
+
+impl MacroLiteral {
+    pub fn new(token: &Token, parameters: Vec<Identifier>, body: BlockStatement) -> Self {
+        MacroLiteral {
+            token: token.clone(),
+            parameters,
+            body,
+        }
+    }
+}
+
+// ============================================================================
+// ASSIGN EXPRESSION
+// ============================================================================
+// <target> = <expression>
+// The target is an identifier, an index expression or a property. The
+// expression produces the assigned value so assignments can be chained.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssignExpression {
+    pub token: Token, // The '=' token.
+    pub target: Box<Expression>,
+    pub value: Box<Expression>,
+}
+
+impl Node for AssignExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+}
+
+impl fmt::Display for AssignExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({} = {})", self.target, self.value)
+    }
+}
+
+impl PartialEq for AssignExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.target == other.target && self.value == other.value
+    }
+}
+
+impl AssignExpression {
+    pub fn new(token: &Token, target: Expression, value: Expression) -> Self {
+        AssignExpression {
+            token: token.clone(),
+            target: Box::new(target),
+            value: Box::new(value),
+        }
+    }
+}
+
+// ============================================================================
+// VISITOR
+// ============================================================================
+// A Visitor goes through the AST without each analysis having to write the
+// traversal again. There is a visit method per kind of node, by default it
+// walks the children of the node. An implementation overrides the methods of
+// the nodes it is interested in and calls the matching walk function if it
+// wants to go on with the children.
+// visit_identifier() is called for identifiers used as expressions. The names
+// being bound are found on let, const, for-in, function and macro nodes.
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_let(&mut self, stmt: &LetStatement) {
+        walk_let(self, stmt);
+    }
+
+    fn visit_const(&mut self, stmt: &ConstStatement) {
+        walk_const(self, stmt);
+    }
+
+    fn visit_import(&mut self, _stmt: &ImportStatement) {}
+
+    fn visit_return(&mut self, stmt: &ReturnStatement) {
+        walk_return(self, stmt);
+    }
+
+    fn visit_expression_statement(&mut self, stmt: &ExpressionStatement) {
+        walk_expression_statement(self, stmt);
+    }
+
+    fn visit_for_in(&mut self, stmt: &ForInStatement) {
+        walk_for_in(self, stmt);
+    }
+
+    fn visit_block(&mut self, block: &BlockStatement) {
+        walk_block(self, block);
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        walk_expression(self, expr);
+    }
+
+    fn visit_identifier(&mut self, _ident: &Identifier) {}
+
+    fn visit_integer_literal(&mut self, _lit: &IntegerLiteral) {}
+
+    fn visit_float_literal(&mut self, _lit: &FloatLiteral) {}
+
+    fn visit_boolean(&mut self, _lit: &Boolean) {}
+
+    fn visit_string_literal(&mut self, _lit: &StringLiteral) {}
+
+    fn visit_array_literal(&mut self, lit: &ArrayLiteral) {
+        walk_array_literal(self, lit);
+    }
+
+    fn visit_hash_literal(&mut self, lit: &HashLiteral) {
+        walk_hash_literal(self, lit);
+    }
+
+    fn visit_prefix(&mut self, expr: &PrefixExpression) {
+        walk_prefix(self, expr);
+    }
+
+    fn visit_postfix(&mut self, expr: &PostfixExpression) {
+        walk_postfix(self, expr);
+    }
+
+    fn visit_infix(&mut self, expr: &InfixExpression) {
+        walk_infix(self, expr);
+    }
+
+    fn visit_if(&mut self, expr: &IfExpression) {
+        walk_if(self, expr);
+    }
+
+    fn visit_function_literal(&mut self, lit: &FunctionLiteral) {
+        walk_function_literal(self, lit);
+    }
+
+    fn visit_call(&mut self, expr: &CallExpression) {
+        walk_call(self, expr);
+    }
+
+    fn visit_index(&mut self, expr: &IndexExpression) {
+        walk_index(self, expr);
+    }
+
+    fn visit_ternary(&mut self, expr: &TernaryExpression) {
+        walk_ternary(self, expr);
+    }
+
+    fn visit_property_access(&mut self, expr: &PropertyAccess) {
+        walk_property_access(self, expr);
+    }
+
+    fn visit_method_call(&mut self, expr: &MethodCallExpression) {
+        walk_method_call(self, expr);
+    }
+
+    fn visit_macro_literal(&mut self, lit: &MacroLiteral) {
+        walk_macro_literal(self, lit);
+    }
+
+    fn visit_assign(&mut self, expr: &AssignExpression) {
+        walk_assign(self, expr);
+    }
+}
+
+// The walk functions visit the children of a node, in source order.
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for stmt in &program.statements {
+        visitor.visit_statement(stmt);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Statement) {
+    match stmt {
+        Statement::Let(stmt) => visitor.visit_let(stmt),
+        Statement::Const(stmt) => visitor.visit_const(stmt),
+        Statement::Import(stmt) => visitor.visit_import(stmt),
+        Statement::Return(stmt) => visitor.visit_return(stmt),
+        Statement::Expression(stmt) => visitor.visit_expression_statement(stmt),
+        Statement::ForIn(stmt) => visitor.visit_for_in(stmt),
+    }
+}
+
+pub fn walk_let<V: Visitor + ?Sized>(visitor: &mut V, stmt: &LetStatement) {
+    if let Some(value) = &stmt.value {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_const<V: Visitor + ?Sized>(visitor: &mut V, stmt: &ConstStatement) {
+    visitor.visit_expression(&stmt.value);
+}
+
+pub fn walk_return<V: Visitor + ?Sized>(visitor: &mut V, stmt: &ReturnStatement) {
+    visitor.visit_expression(&stmt.return_value);
+}
+
+pub fn walk_expression_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &ExpressionStatement) {
+    visitor.visit_expression(&stmt.expression);
+}
+
+pub fn walk_for_in<V: Visitor + ?Sized>(visitor: &mut V, stmt: &ForInStatement) {
+    visitor.visit_expression(&stmt.iterable);
+    visitor.visit_block(&stmt.body);
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &BlockStatement) {
+    for stmt in &block.statements {
+        visitor.visit_statement(stmt);
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expression) {
+    match expr {
+        Expression::Identifier(ident) => visitor.visit_identifier(ident),
+        Expression::IntegerLiteral(lit) => visitor.visit_integer_literal(lit),
+        Expression::FloatLiteral(lit) => visitor.visit_float_literal(lit),
+        Expression::Boolean(lit) => visitor.visit_boolean(lit),
+        Expression::StringLiteral(lit) => visitor.visit_string_literal(lit),
+        Expression::ArrayLiteral(lit) => visitor.visit_array_literal(lit),
+        Expression::HashLiteral(lit) => visitor.visit_hash_literal(lit),
+        Expression::Prefix(expr) => visitor.visit_prefix(expr),
+        Expression::Postfix(expr) => visitor.visit_postfix(expr),
+        Expression::Infix(expr) => visitor.visit_infix(expr),
+        Expression::If(expr) => visitor.visit_if(expr),
+        Expression::FunctionLiteral(lit) => visitor.visit_function_literal(lit),
+        Expression::Call(expr) => visitor.visit_call(expr),
+        Expression::Index(expr) => visitor.visit_index(expr),
+        Expression::Ternary(expr) => visitor.visit_ternary(expr),
+        Expression::PropertyAccess(expr) => visitor.visit_property_access(expr),
+        Expression::MethodCall(expr) => visitor.visit_method_call(expr),
+        Expression::MacroLiteral(lit) => visitor.visit_macro_literal(lit),
+        Expression::Assign(expr) => visitor.visit_assign(expr),
+    }
+}
+
+pub fn walk_array_literal<V: Visitor + ?Sized>(visitor: &mut V, lit: &ArrayLiteral) {
+    for element in &lit.elements {
+        visitor.visit_expression(element);
+    }
+}
+
+pub fn walk_hash_literal<V: Visitor + ?Sized>(visitor: &mut V, lit: &HashLiteral) {
+    for (key, value) in &lit.pairs {
+        visitor.visit_expression(key);
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_prefix<V: Visitor + ?Sized>(visitor: &mut V, expr: &PrefixExpression) {
+    visitor.visit_expression(&expr.right);
+}
+
+pub fn walk_postfix<V: Visitor + ?Sized>(visitor: &mut V, expr: &PostfixExpression) {
+    visitor.visit_expression(&expr.left);
+}
+
+pub fn walk_infix<V: Visitor + ?Sized>(visitor: &mut V, expr: &InfixExpression) {
+    visitor.visit_expression(&expr.left);
+    visitor.visit_expression(&expr.right);
+}
+
+pub fn walk_if<V: Visitor + ?Sized>(visitor: &mut V, expr: &IfExpression) {
+    visitor.visit_expression(&expr.condition);
+    visitor.visit_block(&expr.consequence);
+    if let Some(alternative) = &expr.alternative {
+        visitor.visit_block(alternative);
+    }
+}
+
+pub fn walk_function_literal<V: Visitor + ?Sized>(visitor: &mut V, lit: &FunctionLiteral) {
+    visitor.visit_block(&lit.body);
+}
+
+pub fn walk_call<V: Visitor + ?Sized>(visitor: &mut V, expr: &CallExpression) {
+    visitor.visit_expression(&expr.function);
+    for argument in &expr.arguments {
+        visitor.visit_expression(argument);
+    }
+}
+
+pub fn walk_index<V: Visitor + ?Sized>(visitor: &mut V, expr: &IndexExpression) {
+    visitor.visit_expression(&expr.left);
+    visitor.visit_expression(&expr.index);
+}
+
+pub fn walk_ternary<V: Visitor + ?Sized>(visitor: &mut V, expr: &TernaryExpression) {
+    visitor.visit_expression(&expr.condition);
+    visitor.visit_expression(&expr.consequence);
+    visitor.visit_expression(&expr.alternative);
+}
+
+pub fn walk_property_access<V: Visitor + ?Sized>(visitor: &mut V, expr: &PropertyAccess) {
+    visitor.visit_expression(&expr.object);
+}
+
+pub fn walk_method_call<V: Visitor + ?Sized>(visitor: &mut V, expr: &MethodCallExpression) {
+    visitor.visit_expression(&expr.object);
+    for argument in &expr.arguments {
+        visitor.visit_expression(argument);
+    }
+}
+
+pub fn walk_macro_literal<V: Visitor + ?Sized>(visitor: &mut V, lit: &MacroLiteral) {
+    visitor.visit_block(&lit.body);
+}
+
+pub fn walk_assign<V: Visitor + ?Sized>(visitor: &mut V, expr: &AssignExpression) {
+    visitor.visit_expression(&expr.target);
+    visitor.visit_expression(&expr.value);
+}
+
+// ============================================================================
+// VISITOR MUT
+// ============================================================================
+// Same as Visitor but the nodes can be changed in place while going through
+// them.
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+        walk_statement_mut(self, stmt);
+    }
+
+    fn visit_let_mut(&mut self, stmt: &mut LetStatement) {
+        walk_let_mut(self, stmt);
+    }
+
+    fn visit_const_mut(&mut self, stmt: &mut ConstStatement) {
+        walk_const_mut(self, stmt);
+    }
+
+    fn visit_import_mut(&mut self, _stmt: &mut ImportStatement) {}
+
+    fn visit_return_mut(&mut self, stmt: &mut ReturnStatement) {
+        walk_return_mut(self, stmt);
+    }
+
+    fn visit_expression_statement_mut(&mut self, stmt: &mut ExpressionStatement) {
+        walk_expression_statement_mut(self, stmt);
+    }
+
+    fn visit_for_in_mut(&mut self, stmt: &mut ForInStatement) {
+        walk_for_in_mut(self, stmt);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut BlockStatement) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr);
+    }
+
+    fn visit_identifier_mut(&mut self, _ident: &mut Identifier) {}
+
+    fn visit_integer_literal_mut(&mut self, _lit: &mut IntegerLiteral) {}
+
+    fn visit_float_literal_mut(&mut self, _lit: &mut FloatLiteral) {}
+
+    fn visit_boolean_mut(&mut self, _lit: &mut Boolean) {}
+
+    fn visit_string_literal_mut(&mut self, _lit: &mut StringLiteral) {}
+
+    fn visit_array_literal_mut(&mut self, lit: &mut ArrayLiteral) {
+        walk_array_literal_mut(self, lit);
+    }
+
+    fn visit_hash_literal_mut(&mut self, lit: &mut HashLiteral) {
+        walk_hash_literal_mut(self, lit);
+    }
+
+    fn visit_prefix_mut(&mut self, expr: &mut PrefixExpression) {
+        walk_prefix_mut(self, expr);
+    }
+
+    fn visit_postfix_mut(&mut self, expr: &mut PostfixExpression) {
+        walk_postfix_mut(self, expr);
+    }
+
+    fn visit_infix_mut(&mut self, expr: &mut InfixExpression) {
+        walk_infix_mut(self, expr);
+    }
+
+    fn visit_if_mut(&mut self, expr: &mut IfExpression) {
+        walk_if_mut(self, expr);
+    }
+
+    fn visit_function_literal_mut(&mut self, lit: &mut FunctionLiteral) {
+        walk_function_literal_mut(self, lit);
+    }
+
+    fn visit_call_mut(&mut self, expr: &mut CallExpression) {
+        walk_call_mut(self, expr);
+    }
+
+    fn visit_index_mut(&mut self, expr: &mut IndexExpression) {
+        walk_index_mut(self, expr);
+    }
+
+    fn visit_ternary_mut(&mut self, expr: &mut TernaryExpression) {
+        walk_ternary_mut(self, expr);
+    }
+
+    fn visit_property_access_mut(&mut self, expr: &mut PropertyAccess) {
+        walk_property_access_mut(self, expr);
+    }
+
+    fn visit_method_call_mut(&mut self, expr: &mut MethodCallExpression) {
+        walk_method_call_mut(self, expr);
+    }
+
+    fn visit_macro_literal_mut(&mut self, lit: &mut MacroLiteral) {
+        walk_macro_literal_mut(self, lit);
+    }
+
+    fn visit_assign_mut(&mut self, expr: &mut AssignExpression) {
+        walk_assign_mut(self, expr);
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for stmt in &mut program.statements {
+        visitor.visit_statement_mut(stmt);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Statement) {
+    match stmt {
+        Statement::Let(stmt) => visitor.visit_let_mut(stmt),
+        Statement::Const(stmt) => visitor.visit_const_mut(stmt),
+        Statement::Import(stmt) => visitor.visit_import_mut(stmt),
+        Statement::Return(stmt) => visitor.visit_return_mut(stmt),
+        Statement::Expression(stmt) => visitor.visit_expression_statement_mut(stmt),
+        Statement::ForIn(stmt) => visitor.visit_for_in_mut(stmt),
+    }
+}
+
+pub fn walk_let_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut LetStatement) {
+    if let Some(value) = &mut stmt.value {
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_const_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut ConstStatement) {
+    visitor.visit_expression_mut(&mut stmt.value);
+}
+
+pub fn walk_return_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut ReturnStatement) {
+    visitor.visit_expression_mut(&mut stmt.return_value);
+}
+
+pub fn walk_expression_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    stmt: &mut ExpressionStatement,
+) {
+    visitor.visit_expression_mut(&mut stmt.expression);
+}
+
+pub fn walk_for_in_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut ForInStatement) {
+    visitor.visit_expression_mut(&mut stmt.iterable);
+    visitor.visit_block_mut(&mut stmt.body);
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut BlockStatement) {
+    for stmt in &mut block.statements {
+        visitor.visit_statement_mut(stmt);
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expression) {
+    match expr {
+        Expression::Identifier(ident) => visitor.visit_identifier_mut(ident),
+        Expression::IntegerLiteral(lit) => visitor.visit_integer_literal_mut(lit),
+        Expression::FloatLiteral(lit) => visitor.visit_float_literal_mut(lit),
+        Expression::Boolean(lit) => visitor.visit_boolean_mut(lit),
+        Expression::StringLiteral(lit) => visitor.visit_string_literal_mut(lit),
+        Expression::ArrayLiteral(lit) => visitor.visit_array_literal_mut(lit),
+        Expression::HashLiteral(lit) => visitor.visit_hash_literal_mut(lit),
+        Expression::Prefix(expr) => visitor.visit_prefix_mut(expr),
+        Expression::Postfix(expr) => visitor.visit_postfix_mut(expr),
+        Expression::Infix(expr) => visitor.visit_infix_mut(expr),
+        Expression::If(expr) => visitor.visit_if_mut(expr),
+        Expression::FunctionLiteral(lit) => visitor.visit_function_literal_mut(lit),
+        Expression::Call(expr) => visitor.visit_call_mut(expr),
+        Expression::Index(expr) => visitor.visit_index_mut(expr),
+        Expression::Ternary(expr) => visitor.visit_ternary_mut(expr),
+        Expression::PropertyAccess(expr) => visitor.visit_property_access_mut(expr),
+        Expression::MethodCall(expr) => visitor.visit_method_call_mut(expr),
+        Expression::MacroLiteral(lit) => visitor.visit_macro_literal_mut(lit),
+        Expression::Assign(expr) => visitor.visit_assign_mut(expr),
+    }
+}
+
+pub fn walk_array_literal_mut<V: VisitorMut + ?Sized>(visitor: &mut V, lit: &mut ArrayLiteral) {
+    for element in &mut lit.elements {
+        visitor.visit_expression_mut(element);
+    }
+}
+
+pub fn walk_hash_literal_mut<V: VisitorMut + ?Sized>(visitor: &mut V, lit: &mut HashLiteral) {
+    for (key, value) in &mut lit.pairs {
+        visitor.visit_expression_mut(key);
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_prefix_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut PrefixExpression) {
+    visitor.visit_expression_mut(&mut expr.right);
+}
+
+pub fn walk_postfix_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut PostfixExpression) {
+    visitor.visit_expression_mut(&mut expr.left);
+}
+
+pub fn walk_infix_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut InfixExpression) {
+    visitor.visit_expression_mut(&mut expr.left);
+    visitor.visit_expression_mut(&mut expr.right);
+}
+
+pub fn walk_if_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut IfExpression) {
+    visitor.visit_expression_mut(&mut expr.condition);
+    visitor.visit_block_mut(&mut expr.consequence);
+    if let Some(alternative) = &mut expr.alternative {
+        visitor.visit_block_mut(alternative);
+    }
+}
+
+pub fn walk_function_literal_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    lit: &mut FunctionLiteral,
+) {
+    visitor.visit_block_mut(&mut lit.body);
+}
+
+pub fn walk_call_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut CallExpression) {
+    visitor.visit_expression_mut(&mut expr.function);
+    for argument in &mut expr.arguments {
+        visitor.visit_expression_mut(argument);
+    }
+}
+
+pub fn walk_index_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut IndexExpression) {
+    visitor.visit_expression_mut(&mut expr.left);
+    visitor.visit_expression_mut(&mut expr.index);
+}
+
+pub fn walk_ternary_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut TernaryExpression) {
+    visitor.visit_expression_mut(&mut expr.condition);
+    visitor.visit_expression_mut(&mut expr.consequence);
+    visitor.visit_expression_mut(&mut expr.alternative);
+}
+
+pub fn walk_property_access_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expr: &mut PropertyAccess,
+) {
+    visitor.visit_expression_mut(&mut expr.object);
+}
+
+pub fn walk_method_call_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expr: &mut MethodCallExpression,
+) {
+    visitor.visit_expression_mut(&mut expr.object);
+    for argument in &mut expr.arguments {
+        visitor.visit_expression_mut(argument);
+    }
+}
+
+pub fn walk_macro_literal_mut<V: VisitorMut + ?Sized>(visitor: &mut V, lit: &mut MacroLiteral) {
+    visitor.visit_block_mut(&mut lit.body);
+}
+
+pub fn walk_assign_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut AssignExpression) {
+    visitor.visit_expression_mut(&mut expr.target);
+    visitor.visit_expression_mut(&mut expr.value);
+}
+
+// ============================================================================
+// TRANSFORM
+// ============================================================================
+// A Transform is a pass that rewrites the AST, like constant folding or
+// desugaring. transform_program() goes through the program bottom-up and hands
+// each expression to rewrite_expression() once its children are rewritten, so
+// a pass only has to look at one node at a time.
+pub trait Transform {
+    fn rewrite_expression(&mut self, expr: Expression) -> Expression {
+        expr
+    }
+}
+
+pub fn transform_program<T: Transform + ?Sized>(transform: &mut T, program: Program) -> Program {
+    Program {
+        statements: transform_statements(transform, program.statements),
+        comments: program.comments,
+    }
+}
+
+fn transform_statements<T: Transform + ?Sized>(
+    transform: &mut T,
+    statements: Vec<Statement>,
+) -> Vec<Statement> {
+    statements
+        .into_iter()
+        .map(|stmt| transform_statement(transform, stmt))
+        .collect()
+}
+
+fn transform_statement<T: Transform + ?Sized>(transform: &mut T, stmt: Statement) -> Statement {
+    match stmt {
+        Statement::Let(mut stmt) => {
+            stmt.value = stmt
+                .value
+                .map(|value| Box::new(transform_expression(transform, *value)));
+            Statement::Let(stmt)
+        }
+        Statement::Const(mut stmt) => {
+            stmt.value = Box::new(transform_expression(transform, *stmt.value));
+            Statement::Const(stmt)
+        }
+        Statement::Import(stmt) => Statement::Import(stmt),
+        Statement::Return(mut stmt) => {
+            stmt.return_value = Box::new(transform_expression(transform, *stmt.return_value));
+            Statement::Return(stmt)
+        }
+        Statement::Expression(mut stmt) => {
+            stmt.expression = Box::new(transform_expression(transform, *stmt.expression));
+            Statement::Expression(stmt)
+        }
+        Statement::ForIn(mut stmt) => {
+            stmt.iterable = Box::new(transform_expression(transform, *stmt.iterable));
+            stmt.body = transform_block(transform, stmt.body);
+            Statement::ForIn(stmt)
+        }
+    }
+}
+
+fn transform_block<T: Transform + ?Sized>(
+    transform: &mut T,
+    mut block: BlockStatement,
+) -> BlockStatement {
+    block.statements = transform_statements(transform, block.statements);
+    block
+}
+
+fn transform_expressions<T: Transform + ?Sized>(
+    transform: &mut T,
+    exprs: Vec<Expression>,
+) -> Vec<Expression> {
+    exprs
+        .into_iter()
+        .map(|expr| transform_expression(transform, expr))
+        .collect()
+}
+
+fn transform_boxed<T: Transform + ?Sized>(
+    transform: &mut T,
+    expr: Box<Expression>,
+) -> Box<Expression> {
+    Box::new(transform_expression(transform, *expr))
+}
+
+fn transform_expression<T: Transform + ?Sized>(transform: &mut T, expr: Expression) -> Expression {
+    let expr = match expr {
+        Expression::Identifier(_)
+        | Expression::IntegerLiteral(_)
+        | Expression::FloatLiteral(_)
+        | Expression::Boolean(_)
+        | Expression::StringLiteral(_) => expr,
+        Expression::ArrayLiteral(mut lit) => {
+            lit.elements = transform_expressions(transform, lit.elements);
+            Expression::ArrayLiteral(lit)
+        }
+        Expression::HashLiteral(mut lit) => {
+            lit.pairs = lit
+                .pairs
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = transform_expression(transform, key);
+                    (key, transform_expression(transform, value))
+                })
+                .collect();
+            Expression::HashLiteral(lit)
+        }
+        Expression::Prefix(mut expr) => {
+            expr.right = transform_boxed(transform, expr.right);
+            Expression::Prefix(expr)
+        }
+        Expression::Postfix(mut expr) => {
+            expr.left = transform_boxed(transform, expr.left);
+            Expression::Postfix(expr)
+        }
+        Expression::Infix(mut expr) => {
+            expr.left = transform_boxed(transform, expr.left);
+            expr.right = transform_boxed(transform, expr.right);
+            Expression::Infix(expr)
+        }
+        Expression::If(mut expr) => {
+            expr.condition = transform_boxed(transform, expr.condition);
+            expr.consequence = transform_block(transform, expr.consequence);
+            expr.alternative = expr
+                .alternative
+                .map(|alternative| transform_block(transform, alternative));
+            Expression::If(expr)
+        }
+        Expression::FunctionLiteral(mut lit) => {
+            lit.body = transform_block(transform, lit.body);
+            Expression::FunctionLiteral(lit)
+        }
+        Expression::Call(mut expr) => {
+            expr.function = transform_boxed(transform, expr.function);
+            expr.arguments = transform_expressions(transform, expr.arguments);
+            Expression::Call(expr)
+        }
+        Expression::Index(mut expr) => {
+            expr.left = transform_boxed(transform, expr.left);
+            expr.index = transform_boxed(transform, expr.index);
+            Expression::Index(expr)
+        }
+        Expression::Ternary(mut expr) => {
+            expr.condition = transform_boxed(transform, expr.condition);
+            expr.consequence = transform_boxed(transform, expr.consequence);
+            expr.alternative = transform_boxed(transform, expr.alternative);
+            Expression::Ternary(expr)
+        }
+        Expression::PropertyAccess(mut expr) => {
+            expr.object = transform_boxed(transform, expr.object);
+            Expression::PropertyAccess(expr)
+        }
+        Expression::MethodCall(mut expr) => {
+            expr.object = transform_boxed(transform, expr.object);
+            expr.arguments = transform_expressions(transform, expr.arguments);
+            Expression::MethodCall(expr)
+        }
+        Expression::MacroLiteral(mut lit) => {
+            lit.body = transform_block(transform, lit.body);
+            Expression::MacroLiteral(lit)
+        }
+        Expression::Assign(mut expr) => {
+            expr.target = transform_boxed(transform, expr.target);
+            expr.value = transform_boxed(transform, expr.value);
+            Expression::Assign(expr)
+        }
+    };
+    transform.rewrite_expression(expr)
+}
+
+// ============================================================================
+// CONSTANT FOLDING
+// ============================================================================
+// Replace the operations whose operands are literals by their result, so
+// `3 + 4 * 5` becomes `23` and `!true` becomes `false` before the program is
+// run. Operations that would fail at runtime, like a division by zero or an
+// overflow, are left as they are so the error is still reported there. Only
+// the operators the backends implement are folded, the others fail there.
+pub fn fold_constants(program: Program) -> Program {
+    transform_program(&mut ConstantFolder, program)
+}
+
+struct ConstantFolder;
+
+impl Transform for ConstantFolder {
+    fn rewrite_expression(&mut self, expr: Expression) -> Expression {
+        let folded = match &expr {
+            Expression::Prefix(prefix) => fold_prefix(prefix),
+            Expression::Infix(infix) => fold_infix(infix),
+            _ => None,
+        };
+        folded.unwrap_or(expr)
+    }
+}
+
+fn fold_prefix(prefix: &PrefixExpression) -> Option<Expression> {
+    match (prefix.operator.as_str(), &*prefix.right) {
+        ("-", Expression::IntegerLiteral(right)) => {
+            integer(&prefix.token, right.value.checked_neg()?)
+        }
+        ("!", Expression::Boolean(right)) => Some(boolean(&prefix.token, !right.value)),
+        _ => None,
+    }
+}
+
+fn fold_infix(infix: &InfixExpression) -> Option<Expression> {
+    let token = &infix.token;
+    match (&*infix.left, &*infix.right) {
+        (Expression::IntegerLiteral(left), Expression::IntegerLiteral(right)) => {
+            let (left, right) = (left.value, right.value);
+            match infix.operator.as_str() {
+                "+" => integer(token, left.checked_add(right)?),
+                "-" => integer(token, left.checked_sub(right)?),
+                "*" => integer(token, left.checked_mul(right)?),
+                "/" => integer(token, left.checked_div(right)?),
+                "%" => integer(token, left.checked_rem(right)?),
+                "**" => integer(token, left.checked_pow(u32::try_from(right).ok()?)?),
+                "<" => Some(boolean(token, left < right)),
+                ">" => Some(boolean(token, left > right)),
+                "==" => Some(boolean(token, left == right)),
+                "!=" => Some(boolean(token, left != right)),
+                _ => None,
+            }
+        }
+        (Expression::Boolean(left), Expression::Boolean(right)) => {
+            let (left, right) = (left.value, right.value);
+            match infix.operator.as_str() {
+                "==" => Some(boolean(token, left == right)),
+                "!=" => Some(boolean(token, left != right)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// The literals produced by folding keep the place of the operator they
+// replace, so errors about them still point into the source. The smallest
+// integer is not folded: the program printed would not parse, its digits
+// without the minus sign don't fit in an i64.
+fn integer(at: &Token, value: i64) -> Option<Expression> {
+    if value == i64::MIN {
+        return None;
+    }
+    let token = folded_token(at, TokenType::Int, &value.to_string());
+    Some(Expression::IntegerLiteral(IntegerLiteral::new(
+        &token, value,
+    )))
+}
+
+fn boolean(at: &Token, value: bool) -> Expression {
+    let token = if value {
+        folded_token(at, TokenType::True, "true")
+    } else {
+        folded_token(at, TokenType::False, "false")
+    };
+    Expression::Boolean(Boolean::new(&token, value))
+}
+
+fn folded_token(at: &Token, token_type: TokenType, literal: &str) -> Token {
+    let mut token = Token::new(token_type, literal);
+    token.position = at.position;
+    token.span = at.span;
+    token
+}
+
+// ============================================================================
+// DEAD CODE ELIMINATION
+// ============================================================================
+// Remove the code that can't run: the statements following a return in the
+// same block, and the branch of an if whose condition is a boolean literal,
+// as left by fold_constants(). An if always false keeps its else branch as
+// an if always true. Without else nothing is left of it, its statement is
+// removed unless it gives its null value to the block. Each removal of code
+// that could have run is reported by a warning.
+pub fn eliminate_dead_code(program: &mut Program) -> Vec<ParserWarning> {
+    let mut eliminator = DeadCodeEliminator {
+        warnings: Vec::new(),
+    };
+    eliminator.visit_program_mut(program);
+    eliminator.warnings
+}
+
+struct DeadCodeEliminator {
+    warnings: Vec<ParserWarning>,
+}
+
+impl DeadCodeEliminator {
+    fn remove_unreachable(&mut self, statements: &mut Vec<Statement>) {
+        let Some(ret) = statements
+            .iter()
+            .position(|stmt| matches!(stmt, Statement::Return(_)))
+        else {
+            return;
+        };
+
+        if let Some(first) = statements.get(ret + 1) {
+            self.warnings.push(ParserWarning::UnreachableCode {
+                count: statements.len() - ret - 1,
+                position: first.position(),
+            });
+            statements.truncate(ret + 1);
+        }
+    }
+}
+
+// Removes the statements of ifs always false without else, except the last
+// one, whose value is the one of the block.
+fn remove_empty_ifs(statements: &mut Vec<Statement>) {
+    let last = statements.len().saturating_sub(1);
+    let mut index = 0;
+    statements.retain(|stmt| {
+        let keep = index == last || !is_empty_if(stmt);
+        index += 1;
+        keep
+    });
+}
+
+fn is_empty_if(stmt: &Statement) -> bool {
+    let Statement::Expression(stmt) = stmt else {
+        return false;
+    };
+    let Expression::If(expr) = &*stmt.expression else {
+        return false;
+    };
+    matches!(&*expr.condition, Expression::Boolean(condition) if !condition.value)
+        && expr.consequence.statements.is_empty()
+        && expr.alternative.is_none()
+}
+
+impl VisitorMut for DeadCodeEliminator {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        self.remove_unreachable(&mut program.statements);
+        walk_program_mut(self, program);
+        remove_empty_ifs(&mut program.statements);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut BlockStatement) {
+        self.remove_unreachable(&mut block.statements);
+        walk_block_mut(self, block);
+        remove_empty_ifs(&mut block.statements);
+    }
+
+    fn visit_if_mut(&mut self, expr: &mut IfExpression) {
+        if let Expression::Boolean(condition) = &*expr.condition {
+            let position = expr.token.position;
+            if condition.value {
+                if expr.alternative.take().is_some() {
+                    self.warnings.push(ParserWarning::DeadBranch {
+                        condition: true,
+                        position,
+                    });
+                }
+            } else if !expr.consequence.statements.is_empty() {
+                self.warnings.push(ParserWarning::DeadBranch {
+                    condition: false,
+                    position,
+                });
+                match expr.alternative.take() {
+                    Some(alternative) => {
+                        expr.consequence = alternative;
+                        *expr.condition = boolean(&condition.token, true);
+                    }
+                    None => expr.consequence.statements.clear(),
+                }
+            }
+        }
+        walk_if_mut(self, expr);
+    }
+}
+
+// ============================================================================
+// PRETTY PRINTER
+// ============================================================================
+// The printer behind Program::pretty(). Nodes without blocks are written
+// with Display, the others are laid out across lines.
+struct PrettyPrinter {
+    out: String,
+    indent_width: usize,
+    level: usize,
+}
+
+impl PrettyPrinter {
+    fn new(indent_width: usize) -> Self {
+        PrettyPrinter {
+            out: String::new(),
+            indent_width,
+            level: 0,
+        }
+    }
+
+    fn indent(&mut self) {
+        let width = self.level * self.indent_width;
+        self.out.extend(std::iter::repeat_n(' ', width));
+    }
+
+    // Comments are written on their own lines, at the indentation of the
+    // code that follows them.
+    fn comments(&mut self, comments: &[Trivia]) {
+        for comment in comments {
+            self.indent();
+            self.out.push_str(&comment.text);
+            self.out.push('\n');
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        // The comments found after the statement stay on its line.
+        let start = stmt.span().start;
+        let (leading, trailing): (Vec<Trivia>, Vec<Trivia>) = stmt
+            .comments()
+            .iter()
+            .cloned()
+            .partition(|comment| comment.span.start < start);
+
+        self.comments(&leading);
+        self.indent();
+        match stmt {
+            Statement::Let(stmt) => {
+                self.out.push_str("let ");
+                self.out.push_str(&stmt.name.value);
+                if let Some(value) = &stmt.value {
+                    self.out.push_str(" = ");
+                    self.expression(value);
+                }
+                self.out.push(';');
+            }
+            Statement::Const(stmt) => {
+                self.out.push_str("const ");
+                self.out.push_str(&stmt.name.value);
+                self.out.push_str(" = ");
+                self.expression(&stmt.value);
+                self.out.push(';');
+            }
+            Statement::Import(stmt) => {
+                let _ = write!(self.out, "{}", stmt);
+            }
+            Statement::Return(stmt) => {
+                self.out.push_str("return ");
+                self.expression(&stmt.return_value);
+                self.out.push(';');
+            }
+            Statement::Expression(stmt) => {
+                self.expression(&stmt.expression);
+                // An if reads as a statement, there is no need to end it.
+                if !matches!(*stmt.expression, Expression::If(_)) {
+                    self.out.push(';');
+                }
+            }
+            Statement::ForIn(stmt) => {
+                let _ = write!(self.out, "for ({} in ", stmt.variable);
+                self.expression(&stmt.iterable);
+                self.out.push_str(") ");
+                self.block(&stmt.body);
+            }
+        }
+        for comment in trailing {
+            self.out.push(' ');
+            self.out.push_str(&comment.text);
+        }
+        self.out.push('\n');
+    }
+
+    fn block(&mut self, block: &BlockStatement) {
+        if block.statements.is_empty() && block.comments.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.level += 1;
+        for stmt in &block.statements {
+            self.statement(stmt);
+        }
+        self.comments(&block.comments);
+        self.level -= 1;
+        self.indent();
+        self.out.push('}');
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::If(expr) => {
+                self.out.push_str("if ");
+                // Operators already print their own parentheses.
+                if matches!(
+                    *expr.condition,
+                    Expression::Infix(_) | Expression::Prefix(_)
+                ) {
+                    self.expression(&expr.condition);
+                } else {
+                    self.out.push('(');
+                    self.expression(&expr.condition);
+                    self.out.push(')');
+                }
+                self.out.push(' ');
+                self.block(&expr.consequence);
+                if let Some(alternative) = &expr.alternative {
+                    self.out.push_str(" else ");
+                    self.block(alternative);
+                }
+            }
+            Expression::FunctionLiteral(lit) => {
+                self.out.push_str("fn(");
+                self.parameters(&lit.parameters);
+                self.out.push_str(") ");
+                self.block(&lit.body);
+            }
+            Expression::MacroLiteral(lit) => {
+                self.out.push_str("macro(");
+                self.parameters(&lit.parameters);
+                self.out.push_str(") ");
+                self.block(&lit.body);
+            }
+            Expression::ArrayLiteral(lit) => {
+                self.out.push('[');
+                self.list(&lit.elements);
+                self.out.push(']');
+            }
+            Expression::HashLiteral(lit) => {
+                self.out.push('{');
+                for (i, (key, value)) in lit.pairs.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expression(key);
+                    self.out.push_str(": ");
+                    self.expression(value);
+                }
+                self.out.push('}');
+            }
+            Expression::Prefix(expr) => {
+                let _ = write!(self.out, "({}{}", expr.operator, expr.operand_separator());
+                self.expression(&expr.right);
+                self.out.push(')');
+            }
+            Expression::Postfix(expr) => {
+                self.out.push('(');
+                self.expression(&expr.left);
+                let _ = write!(self.out, "{})", expr.operator);
+            }
+            Expression::Infix(expr) => {
+                self.out.push('(');
+                self.expression(&expr.left);
+                let _ = write!(self.out, " {} ", expr.operator);
+                self.expression(&expr.right);
+                self.out.push(')');
+            }
+            Expression::Call(expr) => {
+                self.expression(&expr.function);
+                self.out.push('(');
+                self.list(&expr.arguments);
+                self.out.push(')');
+            }
+            Expression::Index(expr) => {
+                self.out.push('(');
+                self.expression(&expr.left);
+                self.out.push('[');
+                self.expression(&expr.index);
+                self.out.push_str("])");
+            }
+            Expression::Ternary(expr) => {
+                self.out.push('(');
+                self.expression(&expr.condition);
+                self.out.push_str(" ? ");
+                self.expression(&expr.consequence);
+                self.out.push_str(" : ");
+                self.expression(&expr.alternative);
+                self.out.push(')');
+            }
+            Expression::PropertyAccess(expr) => {
+                self.out.push('(');
+                self.expression(&expr.object);
+                let _ = write!(self.out, ".{})", expr.property);
+            }
+            Expression::MethodCall(expr) => {
+                self.out.push('(');
+                self.expression(&expr.object);
+                let _ = write!(self.out, ".{}(", expr.method);
+                self.list(&expr.arguments);
+                self.out.push_str("))");
+            }
+            Expression::Assign(expr) => {
+                self.out.push('(');
+                self.expression(&expr.target);
+                self.out.push_str(" = ");
+                self.expression(&expr.value);
+                self.out.push(')');
+            }
+            Expression::Identifier(_)
+            | Expression::IntegerLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::Boolean(_)
+            | Expression::StringLiteral(_) => {
+                let _ = write!(self.out, "{}", expr);
+            }
+        }
+    }
+
+    fn list(&mut self, items: &[Expression]) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expression(item);
+        }
+    }
+
+    fn parameters(&mut self, parameters: &[Identifier]) {
+        for (i, param) in parameters.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&param.value);
+        }
     }
 }
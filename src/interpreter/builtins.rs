@@ -0,0 +1,598 @@
+// The builtin functions, found when a name isn't bound in the environment.
+use std::collections::BTreeMap;
+
+use super::evaluator::Evaluator;
+use super::json;
+use super::object::{BuiltinFn, HashKey, Object};
+
+// The list of the standard builtins with their name, every evaluator starts
+// with them.
+pub const BUILTINS: [(&str, BuiltinFn); 30] = [
+    ("len", len),
+    ("first", first),
+    ("last", last),
+    ("rest", rest),
+    ("push", push),
+    ("puts", puts),
+    ("range", range),
+    ("split", split),
+    ("join", join),
+    ("upper", upper),
+    ("lower", lower),
+    ("trim", trim),
+    ("contains", contains),
+    ("replace", replace),
+    ("map", map),
+    ("filter", filter),
+    ("reduce", reduce),
+    ("keys", keys),
+    ("values", values),
+    ("has_key", has_key),
+    ("delete", delete),
+    ("merge", merge),
+    ("type", type_of),
+    ("inspect", inspect),
+    ("assert", assert),
+    ("error", error),
+    ("try", try_call),
+    ("sort", sort),
+    ("json_parse", json_parse),
+    ("json_stringify", json_stringify),
+];
+
+// The builtins only there with the rand feature.
+#[cfg(feature = "rand")]
+pub const RAND_BUILTINS: [(&str, BuiltinFn); 2] = [("rand", rand), ("rand_int", rand_int)];
+
+// The builtins only there with the io feature.
+#[cfg(feature = "io")]
+pub const IO_BUILTINS: [(&str, BuiltinFn); 3] = [
+    ("read_file", read_file),
+    ("write_file", write_file),
+    ("read_line", read_line),
+];
+
+// The standard builtins with the ones of the enabled features.
+pub(crate) fn all() -> impl Iterator<Item = &'static (&'static str, BuiltinFn)> {
+    let all = BUILTINS.iter();
+    #[cfg(feature = "rand")]
+    let all = all.chain(RAND_BUILTINS.iter());
+    #[cfg(feature = "io")]
+    let all = all.chain(IO_BUILTINS.iter());
+    all
+}
+
+fn wrong_arity(want: usize, got: usize) -> Object {
+    Object::Error(format!(
+        "wrong number of arguments: want={}, got={}",
+        want, got
+    ))
+}
+
+// The error returned when an argument should be an array and is not.
+fn must_be_array(name: &str, got: &Object) -> Object {
+    Object::Error(format!(
+        "argument to `{}` must be ARRAY, got {}",
+        name,
+        got.type_name()
+    ))
+}
+
+fn unusable_key(key: &Object) -> Object {
+    Object::Error(format!("unusable as hash key: {}", key.type_name()))
+}
+
+// The error returned when the arguments are not of the types expected, want
+// lists them the way they are printed.
+fn wrong_types(name: &str, want: &str, args: &[Object]) -> Object {
+    let got: Vec<&str> = args.iter().map(|arg| arg.type_name()).collect();
+    Object::Error(format!(
+        "argument{} to `{}` must be {}, got {}",
+        if args.len() > 1 { "s" } else { "" },
+        name,
+        want,
+        got.join(", ")
+    ))
+}
+
+// The length of a string is its number of characters, not of bytes.
+fn len(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(value)] => Object::Integer(value.chars().count() as i64),
+        [Object::Array(elements)] => Object::Integer(elements.len() as i64),
+        [Object::Hash(pairs)] => Object::Integer(pairs.len() as i64),
+        [Object::Range(start, end)] if end <= start => Object::Integer(0),
+        // The bounds can be far enough apart for the length to overflow.
+        [Object::Range(start, end)] => match end.checked_sub(*start) {
+            Some(len) => Object::Integer(len),
+            None => Object::Error(format!("integer overflow: len(range({}, {}))", start, end)),
+        },
+        [arg] => Object::Error(format!(
+            "argument to `len` not supported, got {}",
+            arg.type_name()
+        )),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+fn first(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Array(elements)] => elements.first().cloned().unwrap_or(Object::Null),
+        [arg] => must_be_array("first", arg),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+fn last(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Array(elements)] => elements.last().cloned().unwrap_or(Object::Null),
+        [arg] => must_be_array("last", arg),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// A new array without the first element, null for an empty one.
+fn rest(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Array(elements)] if elements.is_empty() => Object::Null,
+        [Object::Array(elements)] => Object::Array(elements[1..].to_vec()),
+        [arg] => must_be_array("rest", arg),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// A new array with the element added at the end, the array given is left
+// untouched.
+fn push(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Array(elements), element] => {
+            let mut elements = elements.clone();
+            elements.push(element.clone());
+            Object::Array(elements)
+        }
+        [arg, _] => must_be_array("push", arg),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// Writes each argument on its own line to the output of the evaluator.
+fn puts(eval: &mut Evaluator, args: &[Object]) -> Object {
+    let mut output = eval.output().borrow_mut();
+    for arg in args {
+        if let Err(err) = writeln!(output, "{}", arg) {
+            return Object::Error(format!("puts: {}", err));
+        }
+    }
+    Object::Null
+}
+
+// range(end) gives the integers from 0 to end excluded, range(start, end)
+// the ones from start.
+fn range(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Integer(end)] => Object::Range(0, *end),
+        [Object::Integer(start), Object::Integer(end)] => Object::Range(*start, *end),
+        [_] => wrong_types("range", "INTEGER", args),
+        [_, _] => wrong_types("range", "INTEGER, INTEGER", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// split(str, sep) gives the array of the parts of str separated by sep. An
+// empty separator splits the string in characters.
+fn split(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(value), Object::Str(sep)] if sep.is_empty() => {
+            Object::Array(value.chars().map(|c| Object::Str(c.to_string())).collect())
+        }
+        [Object::Str(value), Object::Str(sep)] => Object::Array(
+            value
+                .split(sep.as_str())
+                .map(|part| Object::Str(part.to_string()))
+                .collect(),
+        ),
+        [_, _] => wrong_types("split", "STRING, STRING", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// join(arr, sep) gives the elements of arr, printed, separated by sep.
+fn join(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Array(elements), Object::Str(sep)] => {
+            let parts: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+            Object::Str(parts.join(sep))
+        }
+        [_, _] => wrong_types("join", "ARRAY, STRING", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+fn upper(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(value)] => Object::Str(value.to_uppercase()),
+        [_] => wrong_types("upper", "STRING", args),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+fn lower(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(value)] => Object::Str(value.to_lowercase()),
+        [_] => wrong_types("lower", "STRING", args),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// Removes the whitespace at both ends.
+fn trim(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(value)] => Object::Str(value.trim().to_string()),
+        [_] => wrong_types("trim", "STRING", args),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// contains(str, part) tells if part is found in str.
+fn contains(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(value), Object::Str(part)] => Object::Boolean(value.contains(part.as_str())),
+        [_, _] => wrong_types("contains", "STRING, STRING", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// replace(str, from, to) replaces all the occurrences of from in str.
+fn replace(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(value), Object::Str(from), Object::Str(to)] => {
+            Object::Str(value.replace(from.as_str(), to))
+        }
+        [_, _, _] => wrong_types("replace", "STRING, STRING, STRING", args),
+        _ => wrong_arity(3, args.len()),
+    }
+}
+
+// map(arr, f) gives the array of the results of f called on each element.
+fn map(eval: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Array(elements), f] if f.is_callable() => {
+            let mut results = Vec::with_capacity(elements.len());
+            for element in elements {
+                let result = eval.apply_function(f.clone(), vec![element.clone()]);
+                if result.is_error() {
+                    return result;
+                }
+                results.push(result);
+            }
+            Object::Array(results)
+        }
+        [_, _] => wrong_types("map", "ARRAY, FUNCTION", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// filter(arr, f) gives the array of the elements for which f is truthy.
+fn filter(eval: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Array(elements), f] if f.is_callable() => {
+            let mut kept = Vec::new();
+            for element in elements {
+                let result = eval.apply_function(f.clone(), vec![element.clone()]);
+                if result.is_error() {
+                    return result;
+                }
+                if result.is_truthy() {
+                    kept.push(element.clone());
+                }
+            }
+            Object::Array(kept)
+        }
+        [_, _] => wrong_types("filter", "ARRAY, FUNCTION", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// reduce(arr, init, f) calls f(acc, element) on each element, acc being init
+// for the first one and the previous result for the others.
+fn reduce(eval: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Array(elements), init, f] if f.is_callable() => {
+            let mut acc = init.clone();
+            for element in elements {
+                acc = eval.apply_function(f.clone(), vec![acc, element.clone()]);
+                if acc.is_error() {
+                    return acc;
+                }
+            }
+            acc
+        }
+        [_, _, _] => wrong_types("reduce", "ARRAY, ANY, FUNCTION", args),
+        _ => wrong_arity(3, args.len()),
+    }
+}
+
+// The keys of a hash, in the order it is iterated.
+fn keys(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Hash(pairs)] => Object::Array(pairs.keys().map(|k| k.to_object()).collect()),
+        [_] => wrong_types("keys", "HASH", args),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// The values of a hash, in the order of their keys.
+fn values(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Hash(pairs)] => Object::Array(pairs.values().cloned().collect()),
+        [_] => wrong_types("values", "HASH", args),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+fn has_key(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Hash(pairs), key] => match HashKey::from_object(key) {
+            Some(key) => Object::Boolean(pairs.contains_key(&key)),
+            None => unusable_key(key),
+        },
+        [_, _] => wrong_types("has_key", "HASH, ANY", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// A new hash without the key, the hash given is left untouched. Deleting a
+// key that is not there is not an error.
+fn delete(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Hash(pairs), key] => match HashKey::from_object(key) {
+            Some(key) => {
+                let mut pairs = pairs.clone();
+                pairs.remove(&key);
+                Object::Hash(pairs)
+            }
+            None => unusable_key(key),
+        },
+        [_, _] => wrong_types("delete", "HASH, ANY", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// A new hash with the pairs of both, the values of the second one win when
+// they have the same key.
+fn merge(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Hash(first), Object::Hash(second)] => {
+            let mut pairs = first.clone();
+            pairs.extend(second.iter().map(|(k, v)| (k.clone(), v.clone())));
+            Object::Hash(pairs)
+        }
+        [_, _] => wrong_types("merge", "HASH, HASH", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// type(x) gives the name of the type of x, as in the error messages.
+fn type_of(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [arg] => Object::Str(arg.type_name().to_string()),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// inspect(x) gives the debugging representation of x as a string, see
+// Object::inspect.
+fn inspect(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [arg] => Object::Str(arg.inspect()),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// assert(cond, message) gives null when cond is truthy and an error with the
+// message and the position of the call otherwise. The message is optional.
+fn assert(eval: &mut Evaluator, args: &[Object]) -> Object {
+    let message = match args {
+        [_] => "assertion failed".to_string(),
+        [_, Object::Str(message)] => format!("assertion failed: {}", message),
+        [_, _] => return wrong_types("assert", "any, STRING", args),
+        _ => return wrong_arity(2, args.len()),
+    };
+    if args[0].is_truthy() {
+        return Object::Null;
+    }
+    let position = eval.call_position();
+    Object::Error(format!(
+        "line {}, column {}: {}",
+        position.line, position.column, message
+    ))
+}
+
+// error(message) raises an error with the message, it can be caught by try.
+fn error(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(message)] => Object::Error(message.clone()),
+        [_] => wrong_types("error", "STRING", args),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// try(f) calls f without arguments and catches the error it raises. It gives
+// {"ok": true, "value": value} when f succeeds and {"ok": false, "message":
+// message} when it fails.
+fn try_call(eval: &mut Evaluator, args: &[Object]) -> Object {
+    let f = match args {
+        [f] if f.is_callable() => f,
+        [_] => return wrong_types("try", "FUNCTION", args),
+        _ => return wrong_arity(1, args.len()),
+    };
+    let (ok, field, value) = match eval.apply_function(f.clone(), Vec::new()) {
+        Object::Error(message) => (false, "message", Object::Str(message)),
+        value => (true, "value", value),
+    };
+    Object::Hash(BTreeMap::from([
+        (HashKey::Str("ok".to_string()), Object::Boolean(ok)),
+        (HashKey::Str(field.to_string()), value),
+    ]))
+}
+
+// sort(arr) gives a new array with the elements of arr in increasing order,
+// they must all be integers or all be strings. sort(arr, f) orders them with
+// f(a, b), returning a negative integer or true when a goes before b. The sort
+// is stable.
+fn sort(eval: &mut Evaluator, args: &[Object]) -> Object {
+    let sorted = match args {
+        [Object::Array(elements)] => {
+            let comparable = elements.iter().all(|e| matches!(e, Object::Integer(_)))
+                || elements.iter().all(|e| matches!(e, Object::Str(_)));
+            if !comparable {
+                return Object::Error(
+                    "elements to `sort` must all be INTEGER or all be STRING".to_string(),
+                );
+            }
+            merge_sort(elements.clone(), &mut |a, b| {
+                Ok(match (a, b) {
+                    (Object::Integer(a), Object::Integer(b)) => a < b,
+                    (Object::Str(a), Object::Str(b)) => a < b,
+                    _ => false,
+                })
+            })
+        }
+        [Object::Array(elements), f] if f.is_callable() => {
+            merge_sort(elements.clone(), &mut |a, b| match eval
+                .apply_function(f.clone(), vec![a.clone(), b.clone()])
+            {
+                Object::Integer(order) => Ok(order < 0),
+                Object::Boolean(before) => Ok(before),
+                err @ Object::Error(_) => Err(err),
+                other => Err(Object::Error(format!(
+                    "comparator of `sort` must return INTEGER or BOOLEAN, got {}",
+                    other.type_name()
+                ))),
+            })
+        }
+        [_] => return must_be_array("sort", &args[0]),
+        [_, _] => return wrong_types("sort", "ARRAY, FUNCTION", args),
+        _ => return wrong_arity(2, args.len()),
+    };
+    match sorted {
+        Ok(sorted) => Object::Array(sorted),
+        Err(err) => err,
+    }
+}
+
+// A merge sort stopping at the first error of before(a, b), which tells if a
+// goes strictly before b. Unlike the sort of the standard library it doesn't
+// panic when the order given by a user function is inconsistent.
+fn merge_sort(
+    mut items: Vec<Object>,
+    before: &mut impl FnMut(&Object, &Object) -> Result<bool, Object>,
+) -> Result<Vec<Object>, Object> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let mut left = merge_sort(items, before)?.into_iter().peekable();
+    let mut right = merge_sort(right, before)?.into_iter().peekable();
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        // Equal elements are taken from the left first to keep their order.
+        let next = if before(r, l)? { &mut right } else { &mut left };
+        merged.extend(next.next());
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+// json_parse(s) gives the value written in JSON in s.
+fn json_parse(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(input)] => match json::parse(input) {
+            Ok(value) => value,
+            Err(err) => Object::Error(format!("invalid JSON: {}", err)),
+        },
+        [_] => wrong_types("json_parse", "STRING", args),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// json_stringify(x) gives x written in JSON.
+fn json_stringify(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [arg] => json::stringify(arg).map_or_else(Object::Error, Object::Str),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// rand() gives a random float in [0, 1).
+#[cfg(feature = "rand")]
+fn rand(eval: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [] => Object::Float(eval.rng().next_f64()),
+        _ => wrong_arity(0, args.len()),
+    }
+}
+
+// rand_int(low, high) gives a random integer in [low, high), like the
+// integers of range(low, high).
+#[cfg(feature = "rand")]
+fn rand_int(eval: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Integer(low), Object::Integer(high)] if low < high => {
+            Object::Integer(eval.rng().next_in(*low, *high))
+        }
+        [Object::Integer(low), Object::Integer(high)] => {
+            Object::Error(format!("empty range: rand_int({}, {})", low, high))
+        }
+        [_, _] => wrong_types("rand_int", "INTEGER, INTEGER", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// read_file(path) gives the content of the file as a string.
+#[cfg(feature = "io")]
+fn read_file(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(path)] => match std::fs::read_to_string(path) {
+            Ok(content) => Object::Str(content),
+            Err(err) => Object::Error(format!("could not read {}: {}", path, err)),
+        },
+        [_] => wrong_types("read_file", "STRING", args),
+        _ => wrong_arity(1, args.len()),
+    }
+}
+
+// write_file(path, content) replaces the content of the file, creating it if
+// needed.
+#[cfg(feature = "io")]
+fn write_file(_: &mut Evaluator, args: &[Object]) -> Object {
+    match args {
+        [Object::Str(path), Object::Str(content)] => match std::fs::write(path, content) {
+            Ok(()) => Object::Null,
+            Err(err) => Object::Error(format!("could not write {}: {}", path, err)),
+        },
+        [_, _] => wrong_types("write_file", "STRING, STRING", args),
+        _ => wrong_arity(2, args.len()),
+    }
+}
+
+// read_line() gives the next line of the standard input without its end of
+// line, or null at the end of the input.
+#[cfg(feature = "io")]
+fn read_line(_: &mut Evaluator, args: &[Object]) -> Object {
+    if !args.is_empty() {
+        return wrong_arity(0, args.len());
+    }
+    let mut line = String::new();
+    match std::io::stdin().read_line(&mut line) {
+        Ok(0) => Object::Null,
+        Ok(_) => {
+            let end = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(end);
+            Object::Str(line)
+        }
+        Err(err) => Object::Error(format!("could not read the standard input: {}", err)),
+    }
+}
@@ -0,0 +1,111 @@
+// The environment holds the values bound to names by let statements.
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use super::object::Object;
+
+// Environments are shared: a function keeps the one it was defined in, and
+// the scopes created for its calls point to it.
+pub type Env = Rc<RefCell<Environment>>;
+
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    // The names of the store bound by const statements, they can't be
+    // assigned.
+    constants: HashSet<String>,
+    // The enclosing scope, None for the global one.
+    outer: Option<Env>,
+}
+
+// Why a name couldn't be assigned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssignError {
+    Undeclared,
+    Constant,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment::default()
+    }
+
+    // A new scope inside outer. Names are looked up in outer when they are
+    // not bound in it, and the ones bound in it don't leak into outer.
+    pub fn new_enclosed(outer: Env) -> Self {
+        Environment {
+            outer: Some(outer),
+            ..Environment::default()
+        }
+    }
+
+    // Returns the value bound to the name in this scope or the nearest
+    // enclosing one, if any.
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref()?.borrow().get(name),
+        }
+    }
+
+    // Binds the value to the name in this scope, replacing the previous
+    // binding if there is one. A binding of an enclosing scope is shadowed,
+    // not replaced.
+    pub fn set(&mut self, name: &str, value: Object) {
+        self.constants.remove(name);
+        self.store.insert(name.to_string(), value);
+    }
+
+    // Same as set() but the binding can't be assigned afterwards.
+    pub fn set_const(&mut self, name: &str, value: Object) {
+        self.store.insert(name.to_string(), value);
+        self.constants.insert(name.to_string());
+    }
+
+    // Changes the value of an existing binding, in this scope or the nearest
+    // enclosing one that has it. Unlike set() it never creates a binding.
+    pub fn assign(&mut self, name: &str, value: Object) -> Result<(), AssignError> {
+        if self.constants.contains(name) {
+            return Err(AssignError::Constant);
+        }
+        if let Some(slot) = self.store.get_mut(name) {
+            *slot = value;
+            return Ok(());
+        }
+        match &self.outer {
+            Some(outer) => outer.borrow_mut().assign(name, value),
+            None => Err(AssignError::Undeclared),
+        }
+    }
+
+    // The names bound in this scope or the enclosing ones, sorted and without
+    // duplicates.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = match &self.outer {
+            Some(outer) => outer.borrow().names(),
+            None => Vec::new(),
+        };
+        names.extend(self.store.keys().cloned());
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // The values bound in this scope only, and its enclosing scope. They are
+    // what the garbage collection of the evaluator goes through.
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Object> {
+        self.store.values()
+    }
+
+    pub(crate) fn outer(&self) -> Option<&Env> {
+        self.outer.as_ref()
+    }
+
+    // Removes the bindings and the enclosing scope, which breaks the cycles
+    // the scope is in. They are returned to be dropped once the scope isn't
+    // borrowed anymore.
+    pub(crate) fn clear(&mut self) -> (HashMap<String, Object>, Option<Env>) {
+        (std::mem::take(&mut self.store), self.outer.take())
+    }
+}
@@ -0,0 +1,691 @@
+// The evaluator walks the AST and computes the value of a program.
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::ast::{
+    AssignExpression, BlockStatement, CallExpression, Expression, ForInStatement, HashLiteral,
+    Identifier, IfExpression, IndexExpression, InfixExpression, LetStatement, PrefixExpression,
+    Program, Statement,
+};
+use super::builtins;
+use super::environment::{AssignError, Env, Environment};
+use super::object::{Builtin, Closure, Function, HashKey, Object};
+#[cfg(feature = "rand")]
+use super::random::Rng;
+use super::token::Position;
+
+// Evaluates the program with an evaluator writing to the standard output.
+pub fn eval(program: &Program, env: &Env) -> Object {
+    Evaluator::new().eval(program, env)
+}
+
+// Each function call is evaluated by recursive calls that use some of the
+// Rust stack. We limit how deep function calls can be nested so a function
+// recursing forever gives an error instead of overflowing it. The default
+// fits in the 2MB stack of a spawned thread with a debug build, it can be
+// raised when evaluating on a bigger stack.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 100;
+
+// Runs a closure of the VM, for the builtins given one, like map.
+pub(crate) type ClosureCaller = Rc<dyn Fn(&mut Evaluator, Rc<Closure>, Vec<Object>) -> Object>;
+
+// The evaluator holds what is needed across the evaluation of a program
+// besides the bindings, like where puts writes and the builtins.
+pub struct Evaluator {
+    output: Rc<RefCell<dyn Write>>,
+    builtins: HashMap<String, Builtin>,
+    // The scopes created for function calls and if branches, for the
+    // garbage collection. Some are already dropped, they are removed from
+    // time to time.
+    scopes: Vec<Weak<RefCell<Environment>>>,
+    depth: usize,     // Number of function calls being evaluated.
+    max_depth: usize, // Call depth above which we stop.
+    // Where the last call evaluated is in the source, for the builtins
+    // reporting it.
+    call_position: Position,
+    #[cfg(feature = "rand")]
+    rng: Rng,
+    // Set by the VM when the builtins are called from the bytecode.
+    closure_caller: Option<ClosureCaller>,
+    // Number of statements and expressions evaluated so far.
+    steps: u64,
+    // Set from outside to stop the evaluation, like on Ctrl+C.
+    interrupt: Option<Arc<AtomicBool>>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::new()
+    }
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Evaluator::with_output(Rc::new(RefCell::new(io::stdout())))
+    }
+
+    // The output is shared so the embedder, or a test, can read what the
+    // program wrote.
+    pub fn with_output(output: Rc<RefCell<dyn Write>>) -> Self {
+        let builtins = builtins::all()
+            .map(|&(name, func)| (name.to_string(), Builtin::new(name, func)))
+            .collect();
+        Evaluator {
+            output,
+            builtins,
+            scopes: Vec::new(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_CALL_DEPTH,
+            call_position: Position::default(),
+            #[cfg(feature = "rand")]
+            rng: Rng::from_time(),
+            closure_caller: None,
+            steps: 0,
+            interrupt: None,
+        }
+    }
+
+    // Add a builtin written in Rust, available to the programs evaluated by
+    // this evaluator only. It replaces the builtin with the same name if
+    // there is one, standard ones included.
+    pub fn register_builtin(&mut self, name: &str, func: impl Fn(&[Object]) -> Object + 'static) {
+        let builtin = Builtin::new(name, move |_: &mut Evaluator, args: &[Object]| func(args));
+        self.builtins.insert(name.to_string(), builtin);
+    }
+
+    // Change how deep function calls can be nested before we return a
+    // "maximum recursion depth exceeded" error.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    // Once the flag is set, the evaluation stops with an "interrupted" error
+    // before the next statement, loop iteration or function call. It can be
+    // set from another thread or a signal handler. The evaluator never clears
+    // it, the caller does before evaluating again.
+    pub fn set_interrupt_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupt = Some(flag);
+    }
+
+    fn interrupted(&self) -> bool {
+        self.interrupt
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    // How many statements and expressions were evaluated by this evaluator,
+    // a measure of the work done that doesn't depend on the machine.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // Where the program writes, for the builtins.
+    pub fn output(&self) -> &Rc<RefCell<dyn Write>> {
+        &self.output
+    }
+
+    // Seed the numbers given by rand() and rand_int(), so a program gives the
+    // same results each time it is run. They are seeded with the time
+    // otherwise.
+    #[cfg(feature = "rand")]
+    pub fn seed_rand(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    #[cfg(feature = "rand")]
+    pub(crate) fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    // The builtin with the name, as found when a name isn't bound.
+    pub(crate) fn builtin(&self, name: &str) -> Option<&Builtin> {
+        self.builtins.get(name)
+    }
+
+    pub(crate) fn set_closure_caller(&mut self, caller: ClosureCaller) {
+        self.closure_caller = Some(caller);
+    }
+
+    // The position of the '(' of the call being evaluated, for the builtins.
+    pub fn call_position(&self) -> Position {
+        self.call_position
+    }
+
+    // The VM gives the position of the calls it runs.
+    pub(crate) fn set_call_position(&mut self, position: Position) {
+        self.call_position = position;
+    }
+
+    // Evaluates the statements of the program one after the other, with the
+    // names bound in env. The value of a program is the value of its last
+    // statement, of the first return statement, or the first error met.
+    pub fn eval(&mut self, program: &Program, env: &Env) -> Object {
+        let mut result = Object::Null;
+        for stmt in program.statements.iter() {
+            result = self.eval_statement(stmt, env);
+            match result {
+                Object::ReturnValue(value) => return *value,
+                Object::Error(_) => break,
+                _ => {}
+            }
+        }
+        result
+    }
+
+    fn eval_statement(&mut self, stmt: &Statement, env: &Env) -> Object {
+        self.steps += 1;
+        if self.interrupted() {
+            return Object::Error("interrupted".to_string());
+        }
+        match stmt {
+            Statement::Let(stmt) => self.eval_let(stmt, env),
+            Statement::Const(stmt) => {
+                let value = self.eval_expression(stmt.value(), env);
+                if value.is_error() {
+                    return value;
+                }
+                env.borrow_mut().set_const(stmt.name(), value);
+                Object::Null
+            }
+            Statement::Return(stmt) => match self.eval_expression(&stmt.return_value, env) {
+                value @ Object::Error(_) => value,
+                value => Object::ReturnValue(Box::new(value)),
+            },
+            Statement::Expression(stmt) => self.eval_expression(&stmt.expression, env),
+            Statement::ForIn(stmt) => self.eval_for_in(stmt, env),
+            _ => Object::Error(format!("unsupported statement: {}", stmt.kind())),
+        }
+    }
+
+    // A let without a value binds the name to null.
+    fn eval_let(&mut self, stmt: &LetStatement, env: &Env) -> Object {
+        let value = match stmt.value() {
+            Some(expr) => self.eval_expression(expr, env),
+            None => Object::Null,
+        };
+        if value.is_error() {
+            return value;
+        }
+        env.borrow_mut().set(stmt.name(), value);
+        Object::Null
+    }
+
+    fn eval_expression(&mut self, expr: &Expression, env: &Env) -> Object {
+        self.steps += 1;
+        match expr {
+            Expression::IntegerLiteral(lit) => Object::Integer(lit.value()),
+            Expression::FloatLiteral(lit) => Object::Float(lit.value()),
+            Expression::Boolean(lit) => Object::Boolean(lit.value()),
+            Expression::StringLiteral(lit) => Object::Str(lit.value().to_string()),
+            Expression::Identifier(ident) => self.eval_identifier(ident, env),
+            Expression::ArrayLiteral(lit) => match self.eval_expressions(&lit.elements, env) {
+                Ok(elements) => Object::Array(elements),
+                Err(err) => err,
+            },
+            Expression::HashLiteral(lit) => self.eval_hash_literal(lit, env),
+            Expression::Index(expr) => self.eval_index(expr, env),
+            Expression::Prefix(expr) => self.eval_prefix(expr, env),
+            Expression::Infix(expr) => self.eval_infix(expr, env),
+            Expression::If(expr) => self.eval_if(expr, env),
+            Expression::Ternary(expr) => {
+                let condition = self.eval_expression(&expr.condition, env);
+                if condition.is_error() {
+                    return condition;
+                }
+                if condition.is_truthy() {
+                    self.eval_expression(&expr.consequence, env)
+                } else {
+                    self.eval_expression(&expr.alternative, env)
+                }
+            }
+            Expression::FunctionLiteral(lit) => Object::Function(Rc::new(Function {
+                parameters: lit.parameters.clone(),
+                body: lit.body.clone(),
+                env: Rc::clone(env),
+            })),
+            Expression::Call(expr) => self.eval_call(expr, env),
+            Expression::Assign(expr) => self.eval_assign(expr, env),
+            _ => Object::Error(format!("unsupported expression: {}", expr.kind())),
+        }
+    }
+
+    // A new scope enclosed in outer, tracked for the garbage collection.
+    fn new_scope(&mut self, outer: &Env) -> Env {
+        // Forget the scopes already dropped before the list doubles, so it
+        // doesn't grow with the number of calls.
+        if self.scopes.len() == self.scopes.capacity() {
+            self.scopes.retain(|scope| scope.strong_count() > 0);
+        }
+        let scope = Rc::new(RefCell::new(Environment::new_enclosed(Rc::clone(outer))));
+        self.scopes.push(Rc::downgrade(&scope));
+        scope
+    }
+
+    // A function keeps the scope it is defined in, so a function bound in
+    // that scope, like a recursive one, makes a cycle of Rc that is never
+    // dropped. This frees the scopes created by the evaluator that are still
+    // alive but can't be reached from the roots anymore: the bindings of
+    // each of them are removed, breaking the cycles.
+    // Everything the caller keeps must be reachable from the roots, a
+    // function returned by an evaluation should be bound in one of them. It
+    // is meant to be called between evaluations, like after each REPL input.
+    // Returns the number of scopes freed.
+    pub fn collect_garbage(&mut self, roots: &[&Env]) -> usize {
+        let mut reachable = HashSet::new();
+        for root in roots {
+            mark_env(root, &mut reachable);
+        }
+
+        let mut freed = 0;
+        for scope in std::mem::take(&mut self.scopes) {
+            let Some(scope) = scope.upgrade() else {
+                continue;
+            };
+            if reachable.contains(&Rc::as_ptr(&scope)) {
+                self.scopes.push(Rc::downgrade(&scope));
+            } else {
+                let cleared = scope.borrow_mut().clear();
+                drop(cleared);
+                freed += 1;
+            }
+        }
+        freed
+    }
+
+    // The names bound in the environment shadow the builtins.
+    fn eval_identifier(&self, ident: &Identifier, env: &Env) -> Object {
+        if let Some(value) = env.borrow().get(ident.value()) {
+            return value;
+        }
+        match self.builtins.get(ident.value()) {
+            Some(builtin) => Object::Builtin(builtin.clone()),
+            None => Object::Error(format!("identifier not found: {}", ident.value())),
+        }
+    }
+
+    // An assignment changes the binding of the nearest scope that has the
+    // name, and gives the value assigned.
+    fn eval_assign(&mut self, expr: &AssignExpression, env: &Env) -> Object {
+        let Expression::Identifier(ident) = &*expr.target else {
+            return Object::Error(format!(
+                "unsupported assignment target: {}",
+                expr.target.kind()
+            ));
+        };
+        let value = self.eval_expression(&expr.value, env);
+        if value.is_error() {
+            return value;
+        }
+
+        match env.borrow_mut().assign(ident.value(), value.clone()) {
+            Ok(()) => value,
+            Err(AssignError::Undeclared) => Object::Error(format!(
+                "assignment to undeclared identifier: {}",
+                ident.value()
+            )),
+            Err(AssignError::Constant) => {
+                Object::Error(format!("assignment to constant: {}", ident.value()))
+            }
+        }
+    }
+
+    // The statements of a block are evaluated in env, the caller gives it the
+    // scope it needs. A return value is left wrapped so it also stops the
+    // evaluation of the enclosing blocks.
+    fn eval_block(&mut self, block: &BlockStatement, env: &Env) -> Object {
+        let mut result = Object::Null;
+        for stmt in block.statements.iter() {
+            result = self.eval_statement(stmt, env);
+            if matches!(result, Object::ReturnValue(_) | Object::Error(_)) {
+                break;
+            }
+        }
+        result
+    }
+
+    // The branch taken is evaluated in its own scope, the lets in it don't
+    // leak out. Without an else branch a false condition gives null.
+    fn eval_if(&mut self, expr: &IfExpression, env: &Env) -> Object {
+        let condition = self.eval_expression(&expr.condition, env);
+        if condition.is_error() {
+            return condition;
+        }
+
+        let branch = if condition.is_truthy() {
+            &expr.consequence
+        } else {
+            match &expr.alternative {
+                Some(alternative) => alternative,
+                None => return Object::Null,
+            }
+        };
+        let scope = self.new_scope(env);
+        self.eval_block(branch, &scope)
+    }
+
+    fn eval_call(&mut self, expr: &CallExpression, env: &Env) -> Object {
+        let function = self.eval_expression(&expr.function, env);
+        if function.is_error() {
+            return function;
+        }
+
+        match self.eval_expressions(&expr.arguments, env) {
+            Ok(args) => {
+                self.call_position = expr.token.position;
+                self.apply_function(function, args)
+            }
+            Err(err) => err,
+        }
+    }
+
+    // Evaluates the expressions from left to right, stopping at the first
+    // error.
+    fn eval_expressions(&mut self, exprs: &[Expression], env: &Env) -> Result<Vec<Object>, Object> {
+        let mut values = Vec::with_capacity(exprs.len());
+        for expr in exprs.iter() {
+            let value = self.eval_expression(expr, env);
+            if value.is_error() {
+                return Err(value);
+            }
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    // The keys and values are evaluated in the order they are written. A key
+    // written twice keeps the last value.
+    fn eval_hash_literal(&mut self, lit: &HashLiteral, env: &Env) -> Object {
+        let mut pairs = BTreeMap::new();
+        for (key, value) in lit.pairs.iter() {
+            let key = self.eval_expression(key, env);
+            if key.is_error() {
+                return key;
+            }
+            let Some(key) = HashKey::from_object(&key) else {
+                return Object::Error(format!("unusable as hash key: {}", key.type_name()));
+            };
+            let value = self.eval_expression(value, env);
+            if value.is_error() {
+                return value;
+            }
+            pairs.insert(key, value);
+        }
+        Object::Hash(pairs)
+    }
+
+    // The body is evaluated for each item in a new scope where the variable
+    // is bound to it: the elements of an array, the integers of a range, or
+    // the [key, value] pairs of a hash. A return in the body stops the loop.
+    fn eval_for_in(&mut self, stmt: &ForInStatement, env: &Env) -> Object {
+        let iterable = self.eval_expression(&stmt.iterable, env);
+        let items: Box<dyn Iterator<Item = Object>> = match iterable {
+            Object::Array(elements) => Box::new(elements.into_iter()),
+            Object::Range(start, end) => Box::new((start..end).map(Object::Integer)),
+            Object::Hash(pairs) => Box::new(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| Object::Array(vec![key.to_object(), value])),
+            ),
+            Object::Error(_) => return iterable,
+            _ => {
+                return Object::Error(format!("cannot iterate over {}", iterable.type_name()));
+            }
+        };
+
+        for item in items {
+            // An empty body has no statement to check the flag.
+            if self.interrupted() {
+                return Object::Error("interrupted".to_string());
+            }
+            let scope = self.new_scope(env);
+            scope.borrow_mut().set(stmt.variable.value(), item);
+            let result = self.eval_block(&stmt.body, &scope);
+            if matches!(result, Object::ReturnValue(_) | Object::Error(_)) {
+                return result;
+            }
+        }
+        Object::Null
+    }
+
+    // An index out of the bounds of the array gives null.
+    fn eval_index(&mut self, expr: &IndexExpression, env: &Env) -> Object {
+        let left = self.eval_expression(&expr.left, env);
+        if left.is_error() {
+            return left;
+        }
+        let index = self.eval_expression(&expr.index, env);
+        if index.is_error() {
+            return index;
+        }
+
+        apply_index(left, index)
+    }
+
+    // The body is evaluated in a new scope enclosed in the environment of the
+    // function, with the parameters bound to the arguments. So the locals of
+    // the call don't leak out of it, and neither does a return: it only ends
+    // the call. The builtins call it to run the functions they are given.
+    pub(crate) fn apply_function(&mut self, function: Object, args: Vec<Object>) -> Object {
+        let function = match function {
+            Object::Function(function) => function,
+            Object::Builtin(builtin) => return (builtin.func)(self, &args),
+            Object::Closure(closure) if self.closure_caller.is_some() => {
+                if self.depth >= self.max_depth {
+                    return Object::Error("maximum recursion depth exceeded".to_string());
+                }
+                let call = self.closure_caller.clone().unwrap();
+                self.depth += 1;
+                let result = call(self, closure, args);
+                self.depth -= 1;
+                return result;
+            }
+            _ => return Object::Error(format!("not a function: {}", function.type_name())),
+        };
+        if args.len() != function.parameters.len() {
+            return Object::Error(format!(
+                "wrong number of arguments: want={}, got={}",
+                function.parameters.len(),
+                args.len()
+            ));
+        }
+
+        if self.depth >= self.max_depth {
+            return Object::Error("maximum recursion depth exceeded".to_string());
+        }
+        if self.interrupted() {
+            return Object::Error("interrupted".to_string());
+        }
+
+        let scope = self.new_scope(&function.env);
+        for (param, arg) in function.parameters.iter().zip(args) {
+            scope.borrow_mut().set(param.value(), arg);
+        }
+        self.depth += 1;
+        let result = self.eval_block(&function.body, &scope);
+        self.depth -= 1;
+        match result {
+            Object::ReturnValue(value) => *value,
+            value => value,
+        }
+    }
+
+    fn eval_prefix(&mut self, expr: &PrefixExpression, env: &Env) -> Object {
+        match self.eval_expression(&expr.right, env) {
+            right @ Object::Error(_) => right,
+            right => apply_prefix(&expr.operator, right),
+        }
+    }
+
+    fn eval_infix(&mut self, expr: &InfixExpression, env: &Env) -> Object {
+        let left = self.eval_expression(&expr.left, env);
+        if left.is_error() {
+            return left;
+        }
+        let right = self.eval_expression(&expr.right, env);
+        if right.is_error() {
+            return right;
+        }
+
+        apply_infix(&expr.operator, left, right)
+    }
+}
+
+// The operators are applied by these functions, shared with the VM so both
+// give the same results and errors.
+
+// Indexes an array, out of bounds gives null, or a hash, a missing key gives
+// null.
+pub(crate) fn apply_index(left: Object, index: Object) -> Object {
+    match (left, index) {
+        (Object::Array(elements), Object::Integer(i)) => usize::try_from(i)
+            .ok()
+            .and_then(|i| elements.get(i).cloned())
+            .unwrap_or(Object::Null),
+        (Object::Hash(pairs), index) => match HashKey::from_object(&index) {
+            Some(key) => pairs.get(&key).cloned().unwrap_or(Object::Null),
+            None => Object::Error(format!("unusable as hash key: {}", index.type_name())),
+        },
+        (left, _) => Object::Error(format!(
+            "index operator not supported: {}",
+            left.type_name()
+        )),
+    }
+}
+
+pub(crate) fn apply_prefix(op: &str, right: Object) -> Object {
+    match (op, right) {
+        ("!", right) => Object::Boolean(!right.is_truthy()),
+        ("-", Object::Integer(value)) => match value.checked_neg() {
+            Some(value) => Object::Integer(value),
+            None => Object::Error(format!("integer overflow: -{}", value)),
+        },
+        ("-", Object::Float(value)) => Object::Float(-value),
+        (op, right) => Object::Error(format!("unknown operator: {}{}", op, right.type_name())),
+    }
+}
+
+pub(crate) fn apply_infix(op: &str, left: Object, right: Object) -> Object {
+    match (left, right) {
+        (Object::Integer(left), Object::Integer(right)) => eval_integer_infix(op, left, right),
+        // An integer with a float is promoted to a float.
+        (Object::Float(left), Object::Float(right)) => eval_float_infix(op, left, right),
+        (Object::Integer(left), Object::Float(right)) => eval_float_infix(op, left as f64, right),
+        (Object::Float(left), Object::Integer(right)) => eval_float_infix(op, left, right as f64),
+        (Object::Str(left), Object::Str(right)) if matches!(op, "+" | "==" | "!=" | "<" | ">") => {
+            eval_string_infix(op, left, right)
+        }
+        (Object::Boolean(left), Object::Boolean(right)) if op == "==" => {
+            Object::Boolean(left == right)
+        }
+        (Object::Boolean(left), Object::Boolean(right)) if op == "!=" => {
+            Object::Boolean(left != right)
+        }
+        (left, right) if left.type_name() != right.type_name() => Object::Error(format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            op,
+            right.type_name()
+        )),
+        (left, right) => Object::Error(format!(
+            "unknown operator: {} {} {}",
+            left.type_name(),
+            op,
+            right.type_name()
+        )),
+    }
+}
+
+// Adds env and what can be reached from it to the reachable scopes.
+fn mark_env(env: &Env, reachable: &mut HashSet<*const RefCell<Environment>>) {
+    if !reachable.insert(Rc::as_ptr(env)) {
+        return;
+    }
+    let env = env.borrow();
+    for value in env.values() {
+        mark_object(value, reachable);
+    }
+    if let Some(outer) = env.outer() {
+        mark_env(outer, reachable);
+    }
+}
+
+fn mark_object(object: &Object, reachable: &mut HashSet<*const RefCell<Environment>>) {
+    match object {
+        Object::Function(function) => mark_env(&function.env, reachable),
+        Object::Array(elements) => elements.iter().for_each(|e| mark_object(e, reachable)),
+        Object::Hash(pairs) => pairs.values().for_each(|v| mark_object(v, reachable)),
+        Object::ReturnValue(value) => mark_object(value, reachable),
+        // The closures of the VM only hold values made by the VM, without
+        // scopes, and their cells can refer to the closure itself.
+        _ => {}
+    }
+}
+
+// Overflows and divisions by zero are errors instead of panics.
+fn eval_integer_infix(op: &str, left: i64, right: i64) -> Object {
+    let value = match op {
+        "+" => left.checked_add(right),
+        "-" => left.checked_sub(right),
+        "*" => left.checked_mul(right),
+        "/" if right == 0 => return Object::Error("division by zero".to_string()),
+        "/" => left.checked_div(right),
+        // The result has the sign of the left operand, as in Rust.
+        "%" if right == 0 => return Object::Error("modulo by zero".to_string()),
+        "%" => left.checked_rem(right),
+        "**" if right < 0 => {
+            return Object::Error(format!("negative exponent: {} ** {}", left, right));
+        }
+        // An exponent too big for u32 overflows, unless left is 0, 1 or -1.
+        "**" => match u32::try_from(right) {
+            Ok(right) => left.checked_pow(right),
+            Err(_) if left == 0 || left == 1 => Some(left),
+            Err(_) if left == -1 => Some(if right % 2 == 0 { 1 } else { -1 }),
+            Err(_) => None,
+        },
+        "<" => return Object::Boolean(left < right),
+        ">" => return Object::Boolean(left > right),
+        "==" => return Object::Boolean(left == right),
+        "!=" => return Object::Boolean(left != right),
+        _ => {
+            return Object::Error(format!("unknown operator: INTEGER {} INTEGER", op));
+        }
+    };
+    match value {
+        Some(value) => Object::Integer(value),
+        None => Object::Error(format!("integer overflow: {} {} {}", left, op, right)),
+    }
+}
+
+// Floats follow IEEE 754: a division by zero gives an infinity and NaN is
+// equal to nothing, not even itself.
+fn eval_float_infix(op: &str, left: f64, right: f64) -> Object {
+    match op {
+        "+" => Object::Float(left + right),
+        "-" => Object::Float(left - right),
+        "*" => Object::Float(left * right),
+        "/" => Object::Float(left / right),
+        "%" => Object::Float(left % right),
+        "**" => Object::Float(left.powf(right)),
+        "<" => Object::Boolean(left < right),
+        ">" => Object::Boolean(left > right),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        _ => Object::Error(format!("unknown operator: FLOAT {} FLOAT", op)),
+    }
+}
+
+// Strings are concatenated with + and compared with ==, !=, < and >. They are
+// ordered lexicographically, by code point.
+fn eval_string_infix(op: &str, left: String, right: String) -> Object {
+    match op {
+        "+" => Object::Str(left + &right),
+        "<" => Object::Boolean(left < right),
+        ">" => Object::Boolean(left > right),
+        "==" => Object::Boolean(left == right),
+        _ => Object::Boolean(left != right),
+    }
+}
@@ -0,0 +1,296 @@
+// Conversions between JSON text and objects, for the json_parse and
+// json_stringify builtins. JSON objects are hashes with string keys, numbers
+// are integers when they have no fractional part nor exponent and fit in an
+// i64, floats otherwise.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::object::{HashKey, Object};
+
+// Arrays and objects nested deeper than this are refused, instead of
+// overflowing the stack of the recursive parser.
+const MAX_DEPTH: usize = 128;
+
+// Parses the JSON text into an object. The error tells what is wrong and
+// where.
+pub fn parse(input: &str) -> Result<Object, String> {
+    let mut parser = JsonParser { input, pos: 0 };
+    parser.skip_whitespace();
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.pos < input.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+// Writes the object as compact JSON. Integer and boolean hash keys become
+// strings. Functions, ranges and the floats JSON can't represent, NaN and
+// the infinities, give an error.
+pub fn stringify(object: &Object) -> Result<String, String> {
+    let mut out = String::new();
+    write_value(&mut out, object)?;
+    Ok(out)
+}
+
+struct JsonParser<'a> {
+    input: &'a str,
+    pos: usize, // Byte offset of the next char to read.
+}
+
+impl JsonParser<'_> {
+    fn value(&mut self, depth: usize) -> Result<Object, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+        match self.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => self.string().map(Object::Str),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) if self.eat_word("true") => Ok(Object::Boolean(true)),
+            Some(_) if self.eat_word("false") => Ok(Object::Boolean(false)),
+            Some(_) if self.eat_word("null") => Ok(Object::Null),
+            Some(c) => Err(self.error(&format!("unexpected character '{}'", c))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Object, String> {
+        self.pos += 1; // The '{'.
+        let mut pairs = BTreeMap::new();
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(Object::Hash(pairs));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(':') {
+                return Err(self.error("expected ':'"));
+            }
+            self.skip_whitespace();
+            // The last value wins when a key is repeated.
+            pairs.insert(HashKey::Str(key), self.value(depth + 1)?);
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(Object::Hash(pairs));
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected ',' or '}'"));
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Object, String> {
+        self.pos += 1; // The '['.
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(Object::Array(elements));
+        }
+        loop {
+            self.skip_whitespace();
+            elements.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(Object::Array(elements));
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1; // The opening '"'.
+        let mut value = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(value),
+                '\\' => value.push(self.escape()?),
+                c if c < ' ' => return Err(self.error("control character in string")),
+                c => value.push(c),
+            }
+        }
+    }
+
+    // The char of the escape sequence following a '\'.
+    fn escape(&mut self) -> Result<char, String> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error("unterminated string"))?;
+        self.pos += c.len_utf8();
+        Ok(match c {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let high = self.hex4()?;
+                // Chars outside the basic plane are written as a surrogate
+                // pair, two \u escapes in a row.
+                let code = if (0xd800..0xdc00).contains(&high) && self.eat_word("\\u") {
+                    let low = self.hex4()?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(self.error("invalid surrogate pair"));
+                    }
+                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))?
+            }
+            c => return Err(self.error(&format!("invalid escape '\\{}'", c))),
+        })
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let code = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Object, String> {
+        let start = self.pos;
+        self.eat('-');
+        if !self.eat('0') && self.digits() == 0 {
+            return Err(self.error("expected a digit"));
+        }
+        let mut integer = true;
+        if self.eat('.') {
+            integer = false;
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit"));
+            }
+        }
+        if self.eat('e') || self.eat('E') {
+            integer = false;
+            let _ = self.eat('+') || self.eat('-');
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit"));
+            }
+        }
+
+        let text = &self.input[start..self.pos];
+        if integer {
+            if let Ok(value) = text.parse() {
+                return Ok(Object::Integer(value));
+            }
+        }
+        // Too big for an i64, or not an integer.
+        text.parse()
+            .map(Object::Float)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    // Skips the ASCII digits and gives how many there were.
+    fn digits(&mut self) -> usize {
+        let count = self.input[self.pos..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count();
+        self.pos += count;
+        count
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.eat_word(c.encode_utf8(&mut [0; 4]))
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        if self.input[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    // The message with the line and column of the next char, both starting
+    // at 1 as for the tokens.
+    fn error(&self, message: &str) -> String {
+        let before = &self.input[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        format!("line {}, column {}: {}", line, column, message)
+    }
+}
+
+fn write_value(out: &mut String, object: &Object) -> Result<(), String> {
+    match object {
+        Object::Null => out.push_str("null"),
+        Object::Boolean(value) => write!(out, "{}", value).unwrap(),
+        Object::Integer(value) => write!(out, "{}", value).unwrap(),
+        Object::Float(value) if value.is_finite() => write!(out, "{:?}", value).unwrap(),
+        Object::Float(value) => return Err(format!("cannot convert {} to JSON", value)),
+        Object::Str(value) => write_string(out, value),
+        Object::Array(elements) => {
+            out.push('[');
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, element)?;
+            }
+            out.push(']');
+        }
+        Object::Hash(pairs) => {
+            out.push('{');
+            for (i, (key, value)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, &key.to_string());
+                out.push(':');
+                write_value(out, value)?;
+            }
+            out.push('}');
+        }
+        object => return Err(format!("cannot convert {} to JSON", object.type_name())),
+    }
+    Ok(())
+}
+
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
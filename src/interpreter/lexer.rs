@@ -1,19 +1,54 @@
-use super::token::{Token, TokenType};
+use super::token::{Position, Span, Token, TokenType, Trivia, TriviaKind};
+use std::error::Error;
+use std::fmt;
+
+// An error found while lexing. The lexer still returns an Illegal token at
+// the same place, so the parser can go on and report where it was expected.
+#[derive(PartialEq, Debug, Clone)]
+pub struct LexerError {
+    pub message: String,
+    pub position: Position,
+    pub span: Span,
+}
+
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.position.line, self.position.column, self.message
+        )
+    }
+}
+
+impl Error for LexerError {}
 
 pub struct Lexer<'a> {
     input: &'a str,
-    position: usize,      // Current position in input (points to current char).
-    read_position: usize, // Current reading position in input (after current char).
-    ch: char,             // Current char under examination.
+    position: usize,       // Current position in input (points to current char).
+    read_position: usize,  // Current reading position in input (after current char).
+    ch: char,              // Current char under examination.
+    line: usize,           // Line of the current char.
+    line_start: usize,     // Position in input where the current line starts.
+    error: Option<String>, // Message of the error found in the current token.
+    pub errors: Vec<LexerError>,
+    done: bool,   // EOF has been returned by the iterator.
+    trivia: bool, // Whitespace and comments are attached to tokens.
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(input: &'a str) -> Lexer {
+    pub fn new(input: &'a str) -> Lexer<'a> {
         let mut l = Lexer {
             input,
             position: 0,
             read_position: 0,
             ch: 0 as char,
+            line: 1,
+            line_start: 0,
+            error: None,
+            errors: Vec::new(),
+            done: false,
+            trivia: false,
         };
 
         // Initialize the lexer by reading the first character before
@@ -22,25 +57,136 @@ impl<'a> Lexer<'a> {
         l
     }
 
+    // Create a lexer that keeps whitespace and comments as trivia attached to
+    // the tokens instead of discarding them. This is what a formatter needs
+    // to give back the source as it was written.
+    pub fn with_trivia(input: &'a str) -> Lexer<'a> {
+        let mut l = Lexer::new(input);
+        l.trivia = true;
+        l
+    }
+
     pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+        let leading_trivia = match self.skip_whitespace(false) {
+            Ok(trivia) => trivia,
+            Err((start, position)) => {
+                // We reached the end of input inside a block comment. Report
+                // it using its opening as literal, the next call will return
+                // EOF.
+                let span = Span {
+                    start,
+                    end: start + 2,
+                };
+                self.push_error("unterminated block comment", position, span);
+                let mut tok = Token::new(TokenType::Illegal, "/*");
+                tok.position = position;
+                tok.span = span;
+                return tok;
+            }
+        };
+
+        let start = self.position.min(self.input.len());
+        let position = self.current_position();
+        let (token_type, literal) = self.read_token();
+        let span = Span {
+            start,
+            end: self.position.min(self.input.len()),
+        };
+
+        if token_type == TokenType::Illegal {
+            let message = match self.error.take() {
+                Some(msg) => msg,
+                None => format!("unexpected character '{}'", literal),
+            };
+            self.push_error(&message, position, span);
+        }
+
+        // Trailing trivia never fails as it stops before block comments.
+        let trailing_trivia = self.skip_whitespace(true).unwrap_or_default();
 
+        Token {
+            token_type,
+            literal,
+            position,
+            span,
+            leading_trivia,
+            trailing_trivia,
+        }
+    }
+
+    fn push_error(&mut self, message: &str, position: Position, span: Span) {
+        self.errors.push(LexerError {
+            message: String::from(message),
+            position,
+            span,
+        });
+    }
+
+    // Read the token starting at the current char and return its type and its
+    // literal. When we return the current char is the one following the
+    // token.
+    fn read_token(&mut self) -> (TokenType, String) {
         let token = self.ch;
         let mut literal = token.to_string();
         let token_type = match token {
             ';' => TokenType::Semicolon,
+            ':' => TokenType::Colon,
             '(' => TokenType::LParen,
             ')' => TokenType::RParen,
             ',' => TokenType::Comma,
-            '+' => TokenType::Plus,
-            '-' => TokenType::Minus,
+            '.' => TokenType::Dot,
             '/' => TokenType::Slash,
-            '*' => TokenType::Asterisk,
+            '%' => TokenType::Percent,
             '<' => TokenType::LT,
             '>' => TokenType::GT,
+            '?' => TokenType::Question,
             '{' => TokenType::LBrace,
             '}' => TokenType::RBrace,
+            '[' => TokenType::LBracket,
+            ']' => TokenType::RBracket,
             '\0' => TokenType::EOF,
+            '"' => {
+                // The literal of a string token is its decoded content, without
+                // the surrounding quotes. On a malformed string we get back the
+                // offending part of the input instead.
+                match self.read_string() {
+                    Ok(s) => {
+                        literal = s;
+                        TokenType::String
+                    }
+                    Err(s) => {
+                        literal = s;
+                        TokenType::Illegal
+                    }
+                }
+            }
+            '+' => {
+                if self.peek_char() == '+' {
+                    self.read_char();
+                    literal.push(self.ch);
+                    TokenType::PlusPlus
+                } else {
+                    TokenType::Plus
+                }
+            }
+            '-' => {
+                if self.peek_char() == '-' {
+                    self.read_char();
+                    literal.push(self.ch);
+                    TokenType::MinusMinus
+                } else {
+                    TokenType::Minus
+                }
+            }
+            '*' => {
+                if self.peek_char() == '*' {
+                    self.read_char();
+                    literal.push(self.ch);
+                    TokenType::Power
+                } else {
+                    TokenType::Asterisk
+                }
+            }
             '=' => {
                 // Here we don't know yet if it assign or equal. We need to
                 // peek next char to know. If it is an equal sign then we know
@@ -65,33 +211,48 @@ impl<'a> Lexer<'a> {
                     TokenType::Bang
                 }
             }
+            '&' => {
+                // A single & is not an operator in Monkey.
+                if self.peek_char() == '&' {
+                    self.read_char();
+                    literal.push(self.ch);
+                    TokenType::And
+                } else {
+                    TokenType::Illegal
+                }
+            }
+            '|' => {
+                // A single | encloses the parameters of a lambda: |x| x * 2.
+                if self.peek_char() == '|' {
+                    self.read_char();
+                    literal.push(self.ch);
+                    TokenType::Or
+                } else {
+                    TokenType::Pipe
+                }
+            }
             _ => {
-                if token.is_alphabetic() {
+                if is_letter(token) {
                     // read_identifier() returns a slice of the input string
                     // We return directly because we already did the self.read_char()
                     // so we don't want to do another one.
                     let ident = self.read_identifier();
-                    return Token {
-                        token_type: match ident {
-                            "fn" => TokenType::Function,
-                            "let" => TokenType::Let,
-                            "true" => TokenType::True,
-                            "false" => TokenType::False,
-                            "if" => TokenType::If,
-                            "else" => TokenType::Else,
-                            "return" => TokenType::Return,
-                            _ => TokenType::Ident,
-                        },
-                        literal: String::from(ident),
-                    };
+                    return (TokenType::lookup_ident(ident), String::from(ident));
                 } else if token.is_ascii_digit() {
-                    // read_number() returns a new String from slice of input
-                    // string. And as above, we return directly because we already
-                    // did the self.read_char().
-                    return Token {
-                        token_type: TokenType::Int,
-                        literal: String::from(self.read_number()),
-                    };
+                    // read_number() returns the kind of number it found with a
+                    // slice of input string. And as above, we return directly
+                    // because we already did the self.read_char().
+                    let (token_type, number) = self.read_number();
+                    return (token_type, String::from(number));
+                } else if !token.is_ascii() {
+                    // We are on the first byte of a multibyte char. Report the
+                    // whole char and skip it so we don't stop in the middle.
+                    let c = self.input[self.position..].chars().next().unwrap();
+                    for _ in 1..c.len_utf8() {
+                        self.read_char();
+                    }
+                    literal = c.to_string();
+                    TokenType::Illegal
                 } else {
                     TokenType::Illegal
                 }
@@ -99,16 +260,18 @@ impl<'a> Lexer<'a> {
         };
 
         self.read_char();
-        Token {
-            token_type,
-            literal,
-        }
+        (token_type, literal)
     }
 
     // Read the next character and advance our position in the input string.
     // position points to the current char, read_position points to the next
     // char.
     fn read_char(&mut self) {
+        if self.ch == '\n' {
+            self.line += 1;
+            self.line_start = self.read_position;
+        }
+
         if self.read_position >= self.input.len() {
             self.ch = 0 as char;
         } else {
@@ -119,37 +282,277 @@ impl<'a> Lexer<'a> {
         self.read_position += 1;
     }
 
+    // Return the line and column of the current char. The column counts chars
+    // and not bytes.
+    fn current_position(&self) -> Position {
+        let start = self.line_start.min(self.input.len());
+        let end = self.position.min(self.input.len());
+        Position {
+            line: self.line,
+            column: self.input[start..end].chars().count() + 1,
+        }
+    }
+
     // Return the next character without advancing our position in the input.
     fn peek_char(&mut self) -> char {
-        if self.read_position >= self.input.len() {
+        self.peek_char_nth(0)
+    }
+
+    // Return the character n positions after the next one without advancing
+    // our position in the input. peek_char_nth(0) is the same as peek_char().
+    fn peek_char_nth(&mut self, n: usize) -> char {
+        let pos = self.read_position + n;
+        if pos >= self.input.len() {
             0 as char
         } else {
-            self.input.as_bytes()[self.read_position] as char
+            self.input.as_bytes()[pos] as char
+        }
+    }
+
+    // Skip whitespace and comments. A line comment starts with // and goes
+    // until the end of the line, a block comment is enclosed in /* and */ and
+    // can be nested.
+    // We return the comments we skipped, and the whitespace too when the lexer
+    // keeps trivia. If `trailing` is set we only look for what follows a token
+    // on its line, that is we stop at the newline or at the start of a block
+    // comment.
+    // If we reached the end of the input in an unterminated block comment we
+    // return where this comment starts.
+    fn skip_whitespace(&mut self, trailing: bool) -> Result<Vec<Trivia>, (usize, Position)> {
+        let mut trivia = Vec::new();
+        let is_blank = |c: char| c.is_whitespace() && !(trailing && c == '\n');
+
+        loop {
+            let start = self.position;
+            let kind = if is_blank(self.ch) {
+                while is_blank(self.ch) {
+                    self.read_char();
+                }
+                TriviaKind::Whitespace
+            } else if self.ch == '/' && self.peek_char() == '/' {
+                self.skip_line_comment();
+                TriviaKind::LineComment
+            } else if !trailing && self.ch == '/' && self.peek_char() == '*' {
+                let position = self.current_position();
+                if !self.skip_block_comment() {
+                    return Err((start, position));
+                }
+                TriviaKind::BlockComment
+            } else {
+                return Ok(trivia);
+            };
+
+            if self.trivia || kind != TriviaKind::Whitespace {
+                let end = self.position.min(self.input.len());
+                trivia.push(Trivia {
+                    kind,
+                    text: String::from(&self.input[start..end]),
+                    span: Span { start, end },
+                });
+            }
+        }
+    }
+
+    // Skip everything until the end of the line. The newline itself is left
+    // as it is just whitespace.
+    fn skip_line_comment(&mut self) {
+        while self.ch != '\n' && self.ch != '\0' {
+            self.read_char();
         }
     }
 
-    fn skip_whitespace(&mut self) {
-        while self.ch.is_whitespace() {
+    // Skip a block comment, the current char being the '/' of its opening.
+    // We keep track of the depth so /* a /* b */ c */ is a single comment.
+    // Return false if the input ends before the comment is closed.
+    fn skip_block_comment(&mut self) -> bool {
+        let mut depth = 0;
+        loop {
+            match (self.ch, self.peek_char()) {
+                ('\0', _) => return false,
+                ('/', '*') => {
+                    depth += 1;
+                    self.read_char();
+                }
+                ('*', '/') => {
+                    depth -= 1;
+                    self.read_char();
+                    if depth == 0 {
+                        self.read_char();
+                        return true;
+                    }
+                }
+                _ => {}
+            }
             self.read_char();
         }
     }
 
     // Return a slice of the input string from the current position until
-    // the next non-alphabetic character.
+    // the next character that is not a letter.
     fn read_identifier(&mut self) -> &str {
         let pos = self.position;
-        while self.ch.is_alphabetic() {
+        while is_letter(self.ch) {
             self.read_char();
         }
         &self.input[pos..self.position]
     }
 
-    // Return a slice of the number in base 10 from the current position.
-    fn read_number(&mut self) -> &str {
+    // Read a string literal and decode its escape sequences. When we return
+    // the current char is the closing quote so next_token() can skip it as it
+    // does for any other single char token.
+    // On error we return the offending input: the unknown escape sequence, or
+    // the whole string if it is never closed. In the first case we still
+    // consume the string until its closing quote so lexing can go on.
+    fn read_string(&mut self) -> Result<String, String> {
+        let start = self.position;
+        let mut out = String::new();
+        let mut error: Option<String> = None;
+
+        self.read_char();
+        let mut pos = self.position;
+        loop {
+            match self.ch {
+                '"' => break,
+                '\0' => {
+                    self.error = Some(String::from("unterminated string"));
+                    return Err(String::from(&self.input[start..self.position]));
+                }
+                '\\' if error.is_none() => {
+                    let escape = self.position;
+                    out.push_str(&self.input[pos..escape]);
+                    self.read_char();
+                    match self.ch {
+                        'n' => out.push('\n'),
+                        't' => out.push('\t'),
+                        '"' => out.push('"'),
+                        '\\' => out.push('\\'),
+                        '\0' => continue,
+                        _ => {
+                            // Keep the backslash and the whole (maybe multibyte)
+                            // char that follows it.
+                            let sequence: String = self.input[escape..].chars().take(2).collect();
+                            self.error = Some(format!("unknown escape sequence '{}'", sequence));
+                            error = Some(sequence);
+                        }
+                    }
+                    self.read_char();
+                    pos = self.position;
+                }
+                '\\' => {
+                    // We already failed, just make sure that an escaped quote
+                    // doesn't end the string.
+                    self.read_char();
+                    if self.ch != '\0' {
+                        self.read_char();
+                    }
+                }
+                _ => self.read_char(),
+            }
+        }
+
+        match error {
+            Some(e) => Err(e),
+            None => {
+                out.push_str(&self.input[pos..self.position]);
+                Ok(out)
+            }
+        }
+    }
+
+    // Return a slice of the number in base 10 from the current position and
+    // its token type. It is a Float if the digits are followed by a fractional
+    // part and/or an exponent, an Int otherwise.
+    // The dot and the exponent are only consumed when followed by a digit so
+    // "3." is still lexed as an Int followed by whatever comes next.
+    // Integers can also be written in hexadecimal (0xFF), binary (0b1010) or
+    // octal (0o755). The prefix is kept in the literal so the parser knows
+    // which radix to use. A prefix without any digit is Illegal.
+    fn read_number(&mut self) -> (TokenType, &str) {
         let pos = self.position;
+        let mut token_type = TokenType::Int;
+
+        if self.ch == '0' {
+            let radix = match self.peek_char() {
+                'x' | 'X' => 16,
+                'b' | 'B' => 2,
+                'o' | 'O' => 8,
+                _ => 10,
+            };
+
+            if radix != 10 {
+                self.read_char();
+                self.read_char();
+                let digits = self.position;
+                while self.ch.is_digit(radix) {
+                    self.read_char();
+                }
+                if self.position == digits {
+                    self.error = Some(format!(
+                        "missing digits after '{}'",
+                        &self.input[pos..self.position]
+                    ));
+                    token_type = TokenType::Illegal;
+                }
+                return (token_type, &self.input[pos..self.position]);
+            }
+        }
+
+        self.read_digits();
+
+        if self.ch == '.' && self.peek_char().is_ascii_digit() {
+            token_type = TokenType::Float;
+            self.read_char();
+            self.read_digits();
+        }
+
+        if self.ch == 'e' || self.ch == 'E' {
+            let sign = self.peek_char() == '+' || self.peek_char() == '-';
+            let digit = if sign {
+                self.peek_char_nth(1)
+            } else {
+                self.peek_char()
+            };
+
+            if digit.is_ascii_digit() {
+                token_type = TokenType::Float;
+                self.read_char();
+                if sign {
+                    self.read_char();
+                }
+                self.read_digits();
+            }
+        }
+
+        (token_type, &self.input[pos..self.position])
+    }
+
+    fn read_digits(&mut self) {
         while self.ch.is_ascii_digit() {
             self.read_char();
         }
-        &self.input[pos..self.position]
     }
 }
+
+// Iterating over the lexer yields all the tokens of the input, the last one
+// being EOF.
+impl Iterator for Lexer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        if self.done {
+            return None;
+        }
+
+        let tok = self.next_token();
+        if tok.token_type == TokenType::EOF {
+            self.done = true;
+        }
+        Some(tok)
+    }
+}
+
+// The characters identifiers are made of. As in the book the underscore is a
+// letter, so names like has_key are one identifier.
+pub(crate) fn is_letter(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_'
+}
@@ -1,8 +1,23 @@
 // looking for src/interpreter/ast.rs
+// looking for src/interpreter/builtins.rs
+// looking for src/interpreter/environment.rs
+// looking for src/interpreter/evaluator.rs
+// looking for src/interpreter/json.rs
 // looking for src/interpreter/lexer.rs
+// looking for src/interpreter/object.rs
 // looking for src/interpreter/parser.rs
+// looking for src/interpreter/random.rs
 // looking for src/interpreter/token.rs
+// looking for src/interpreter/token_stream.rs
 pub mod ast;
+pub mod builtins;
+pub mod environment;
+pub mod evaluator;
+pub mod json;
 pub mod lexer;
+pub mod object;
 pub mod parser;
+#[cfg(feature = "rand")]
+pub mod random;
 pub mod token;
+pub mod token_stream;
@@ -0,0 +1,379 @@
+// Objects are the values produced when evaluating a program.
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::rc::Rc;
+
+use super::ast::{BlockStatement, Identifier};
+use super::environment::Env;
+use super::evaluator::Evaluator;
+use super::token::Position;
+use crate::compiler::code::Instructions;
+use crate::compiler::symbol_table::Symbol;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Str(String),
+    Array(Vec<Object>),
+    // The pairs are kept sorted by key so a hash is always printed and
+    // iterated in the same order.
+    Hash(BTreeMap<HashKey, Object>),
+    // The integers from the start up to the end, excluded. They are produced
+    // one by one when iterated, not stored.
+    Range(i64, i64),
+    Null,
+    // Shared, evaluating the function literal again creates another one.
+    Function(Rc<Function>),
+    Builtin(Builtin),
+    // A function compiled to bytecode, only found in the constants. The VM
+    // runs it wrapped in a closure.
+    CompiledFunction(Rc<CompiledFunction>),
+    // The functions of the VM.
+    Closure(Rc<Closure>),
+    // The value of a return statement. It stops the evaluation of the blocks
+    // it is in and is unwrapped at the end of the function call, or of the
+    // program.
+    ReturnValue(Box<Object>),
+    // Errors are values too, evaluation stops at the first one and returns
+    // it. The message doesn't have the "ERROR: " prefix, it is added when
+    // printed.
+    Error(String),
+}
+
+impl Object {
+    // The name of the type of the object, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::Str(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Range(..) => "RANGE",
+            Object::Null => "NULL",
+            Object::Function(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            // The same type as the functions of the evaluator, the backends
+            // agree on what type() gives.
+            Object::Closure(_) => "FUNCTION",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Error(_) => "ERROR",
+        }
+    }
+
+    // Null and false are falsy, every other object is truthy, 0 and empty
+    // strings included.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Null | Object::Boolean(false))
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+
+    // Whether the object can be called, by the program or by a builtin.
+    pub fn is_callable(&self) -> bool {
+        matches!(
+            self,
+            Object::Function(_) | Object::Closure(_) | Object::Builtin(_)
+        )
+    }
+
+    // A debugging representation of the object. Unlike Display, strings are
+    // quoted, and arrays and hashes holding other non empty arrays or hashes
+    // are written one element per line, indented.
+    pub fn inspect(&self) -> String {
+        let mut out = String::new();
+        self.write_inspect(&mut out, 0);
+        out
+    }
+
+    fn write_inspect(&self, out: &mut String, indent: usize) {
+        match self {
+            Object::Str(value) => write_quoted(out, value),
+            Object::Array(elements) => {
+                let nested = elements.iter().any(Object::is_nested);
+                write_items(out, ('[', ']'), elements.len(), nested, indent, |out, i| {
+                    elements[i].write_inspect(out, indent + 1)
+                });
+            }
+            Object::Hash(pairs) => {
+                let nested = pairs.values().any(Object::is_nested);
+                let pairs: Vec<_> = pairs.iter().collect();
+                write_items(out, ('{', '}'), pairs.len(), nested, indent, |out, i| {
+                    let (key, value) = pairs[i];
+                    key.to_object().write_inspect(out, indent + 1);
+                    out.push_str(": ");
+                    value.write_inspect(out, indent + 1);
+                });
+            }
+            Object::ReturnValue(value) => value.write_inspect(out, indent),
+            object => {
+                let _ = write!(out, "{}", object);
+            }
+        }
+    }
+
+    // Whether the object is an array or a hash that isn't empty.
+    fn is_nested(&self) -> bool {
+        match self {
+            Object::Array(elements) => !elements.is_empty(),
+            Object::Hash(pairs) => !pairs.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+// Writes count items between the delimiters, on one line or, when broken,
+// one per line indented one level deeper than indent.
+fn write_items(
+    out: &mut String,
+    (open, close): (char, char),
+    count: usize,
+    broken: bool,
+    indent: usize,
+    mut item: impl FnMut(&mut String, usize),
+) {
+    out.push(open);
+    for i in 0..count {
+        if broken {
+            out.push('\n');
+            out.push_str(&"  ".repeat(indent + 1));
+        } else if i > 0 {
+            out.push(' ');
+        }
+        item(out, i);
+        if broken || i + 1 < count {
+            out.push(',');
+        }
+    }
+    if broken && count > 0 {
+        out.push('\n');
+        out.push_str(&"  ".repeat(indent));
+    }
+    out.push(close);
+}
+
+// Writes the string between double quotes, escaped the way it is written in
+// the source.
+fn write_quoted(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// The objects are printed as the REPL shows them.
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            // Debug keeps the fractional part of 1.0, so it doesn't look like
+            // an integer.
+            Object::Float(value) => write!(f, "{:?}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::Str(value) => f.write_str(value),
+            Object::Array(elements) => {
+                f.write_char('[')?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                f.write_char(']')
+            }
+            Object::Hash(pairs) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                f.write_char('}')
+            }
+            Object::Range(start, end) => write!(f, "range({}, {})", start, end),
+            Object::Null => f.write_str("null"),
+            Object::Function(func) => write!(f, "{}", func),
+            Object::Builtin(builtin) => write!(f, "builtin function {}", builtin.name),
+            Object::CompiledFunction(function) => write!(f, "{}", function),
+            Object::Closure(closure) => write!(f, "{}", closure.function),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::Error(message) => write!(f, "ERROR: {}", message),
+        }
+    }
+}
+
+// Only integers, booleans and strings can be used as keys of a hash.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    Str(String),
+}
+
+impl HashKey {
+    // The key for the object, None if it can't be one.
+    pub fn from_object(object: &Object) -> Option<HashKey> {
+        match object {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::Str(value) => Some(HashKey::Str(value.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn to_object(&self) -> Object {
+        match self {
+            HashKey::Integer(value) => Object::Integer(*value),
+            HashKey::Boolean(value) => Object::Boolean(*value),
+            HashKey::Str(value) => Object::Str(value.clone()),
+        }
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_object())
+    }
+}
+
+// A function is a closure: it keeps the environment it was defined in, its
+// body is evaluated in a scope enclosed in it.
+#[derive(Clone)]
+pub struct Function {
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+    pub env: Env,
+}
+
+// Two functions are equal when they are the same code defined in the same
+// environment. The environments are not compared, they may hold the function
+// itself.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.parameters == other.parameters
+            && self.body == other.body
+            && Rc::ptr_eq(&self.env, &other.env)
+    }
+}
+
+// The environment is left out for the same reason.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("fn(")?;
+        for (i, param) in self.parameters.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", param)?;
+        }
+        write!(f, ") {{ {} }}", self.body)
+    }
+}
+
+// A function compiled to bytecode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    // The parameters are the first locals. Their names are kept for printing
+    // the function.
+    pub num_locals: usize,
+    pub parameters: Vec<String>,
+    // The variables of the enclosing function the closure captures, as they
+    // resolve there.
+    pub free: Vec<Symbol>,
+    // Where the calls of the function are in the source, by offset of their
+    // instruction.
+    pub call_positions: Vec<(usize, Position)>,
+}
+
+impl CompiledFunction {
+    // The position of the call instruction at the offset, or 0:0 if the
+    // compiler didn't give one.
+    pub fn call_position(&self, offset: usize) -> Position {
+        match self
+            .call_positions
+            .binary_search_by_key(&offset, |&(call, _)| call)
+        {
+            Ok(i) => self.call_positions[i].1,
+            Err(_) => Position::default(),
+        }
+    }
+}
+
+// Printed like the functions of the evaluator, the body is left out as it
+// was compiled.
+impl fmt::Display for CompiledFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fn({}) {{ ... }}", self.parameters.join(", "))
+    }
+}
+
+// A compiled function with the variables of the enclosing functions it uses.
+// They are captured by reference like with the evaluator, each one is a cell
+// shared with the frame that defined it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Rc<RefCell<Object>>>,
+}
+
+// A function written in Rust. It gets the evaluator, for the ones writing to
+// its output, and the evaluated arguments. It returns an error object when
+// they are not the ones expected.
+pub type BuiltinFn = fn(&mut Evaluator, &[Object]) -> Object;
+
+// The standard builtins are plain functions, the ones registered by an
+// embedder can be closures.
+pub type NativeFn = dyn Fn(&mut Evaluator, &[Object]) -> Object;
+
+#[derive(Clone)]
+pub struct Builtin {
+    pub name: String,
+    pub func: Rc<NativeFn>,
+}
+
+impl Builtin {
+    pub fn new(name: &str, func: impl Fn(&mut Evaluator, &[Object]) -> Object + 'static) -> Self {
+        Builtin {
+            name: name.to_string(),
+            func: Rc::new(func),
+        }
+    }
+}
+
+// There is one builtin per name in an evaluator, so they are compared on it.
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Builtin").field("name", &self.name).finish()
+    }
+}
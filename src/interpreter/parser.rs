@@ -3,118 +3,334 @@
 // in the process.
 // We are constructing a recursive descent parser, which is a type of top-down
 // parsing.
-use super::ast::{self};
-use super::lexer::Lexer;
-use super::token::{Token, TokenType};
+use super::ast;
+use super::lexer::{Lexer, LexerError};
+use super::token::{Position, Span, Token, TokenType, Trivia, TriviaKind};
+use super::token_stream::TokenStream;
 
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+// The errors reported by the parser. They all know where in the input they
+// were found.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ParserError {
+    // The next token is not the one required by the grammar.
+    UnexpectedToken {
+        expected: TokenType,
+        got: TokenType,
+        position: Position,
+    },
+    // The token can't start an expression.
+    NoPrefixParseFn {
+        token: TokenType,
+        position: Position,
+    },
+    // The integer doesn't fit in an i64.
+    InvalidIntegerLiteral {
+        literal: String,
+        position: Position,
+    },
+    InvalidFloatLiteral {
+        literal: String,
+        position: Position,
+    },
+    // The input ended before the closing brace of the block opened at
+    // `position`.
+    UnclosedBlock {
+        position: Position,
+    },
+    // The path of an import is empty or has an empty segment, like "a//b".
+    InvalidImportPath {
+        path: String,
+        position: Position,
+    },
+    // Only identifiers, index expressions and properties can be assigned.
+    InvalidAssignmentTarget {
+        target: String,
+        position: Position,
+    },
+    // Expressions or blocks are nested deeper than the parser allows.
+    NestingTooDeep {
+        max_depth: usize,
+        position: Position,
+    },
+    // The lexer found an Illegal token.
+    Lexer(LexerError),
+}
+
+impl ParserError {
+    pub fn position(&self) -> Position {
+        match self {
+            ParserError::UnexpectedToken { position, .. }
+            | ParserError::NoPrefixParseFn { position, .. }
+            | ParserError::InvalidIntegerLiteral { position, .. }
+            | ParserError::InvalidFloatLiteral { position, .. }
+            | ParserError::UnclosedBlock { position }
+            | ParserError::InvalidImportPath { position, .. }
+            | ParserError::InvalidAssignmentTarget { position, .. }
+            | ParserError::NestingTooDeep { position, .. } => *position,
+            ParserError::Lexer(err) => err.position,
+        }
+    }
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Lexer errors already give their position.
+        if let ParserError::Lexer(err) = self {
+            return write!(f, "{}", err);
+        }
+
+        let position = self.position();
+        write!(f, "line {}, column {}: ", position.line, position.column)?;
+
+        match self {
+            ParserError::UnexpectedToken { expected, got, .. } => {
+                write!(
+                    f,
+                    "Expected next token to be {}, got {} instead",
+                    expected, got
+                )
+            }
+            ParserError::NoPrefixParseFn { token, .. } => {
+                write!(f, "No prefix parse function found for {}", token)
+            }
+            ParserError::InvalidIntegerLiteral { literal, .. } => {
+                write!(f, "Could not parse {} as integer", literal)
+            }
+            ParserError::InvalidFloatLiteral { literal, .. } => {
+                write!(f, "Could not parse {} as float", literal)
+            }
+            ParserError::UnclosedBlock { .. } => write!(
+                f,
+                "Expected {} to close the block, got {} instead",
+                TokenType::RBrace,
+                TokenType::EOF
+            ),
+            ParserError::InvalidImportPath { path, .. } => {
+                write!(f, "Invalid import path \"{}\"", path)
+            }
+            ParserError::InvalidAssignmentTarget { target, .. } => {
+                write!(f, "Cannot assign to {}", target)
+            }
+            ParserError::NestingTooDeep { max_depth, .. } => {
+                write!(f, "Nesting is too deep, the limit is {} levels", max_depth)
+            }
+            ParserError::Lexer(_) => unreachable!(),
+        }
+    }
+}
+
+impl Error for ParserError {}
+
+// Warnings are about code that parses but is most likely not what was meant.
+// They don't prevent the program from being used.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ParserWarning {
+    // An assignment used as a condition, like `if (x = 5)`, is usually a
+    // typo for `==`.
+    AssignmentInCondition { position: Position },
+    // Statements after a return can't be reached, they are removed by
+    // ast::eliminate_dead_code().
+    UnreachableCode { count: usize, position: Position },
+    // The condition of an if is always true or false so one of its branches
+    // is never run and has been removed.
+    DeadBranch { condition: bool, position: Position },
+}
+
+impl ParserWarning {
+    pub fn position(&self) -> Position {
+        match self {
+            ParserWarning::AssignmentInCondition { position }
+            | ParserWarning::UnreachableCode { position, .. }
+            | ParserWarning::DeadBranch { position, .. } => *position,
+        }
+    }
+}
+
+impl fmt::Display for ParserWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let position = self.position();
+        write!(f, "line {}, column {}: ", position.line, position.column)?;
+
+        match self {
+            ParserWarning::AssignmentInCondition { .. } => write!(
+                f,
+                "Assignment used as a condition, did you mean {}?",
+                TokenType::Equal
+            ),
+            ParserWarning::UnreachableCode { count, .. } => write!(
+                f,
+                "Removed {} unreachable statement{} after {}",
+                count,
+                if *count == 1 { "" } else { "s" },
+                TokenType::Return
+            ),
+            ParserWarning::DeadBranch { condition, .. } => write!(
+                f,
+                "Condition is always {}, removed the {} branch",
+                condition,
+                if *condition { "else" } else { "then" }
+            ),
+        }
+    }
+}
+
+// The comments among the trivia of a token, to keep them in the AST.
+fn comments(trivia: &[Trivia]) -> Vec<Trivia> {
+    trivia
+        .iter()
+        .filter(|t| t.kind != TriviaKind::Whitespace)
+        .cloned()
+        .collect()
+}
+
+// Parsing is recursive so each nested expression or block uses some stack. We
+// limit the nesting to not overflow it on inputs like ((((((...)))))).
+pub const DEFAULT_MAX_DEPTH: usize = 128;
 
 // Pratt parser idea is to associate parsing functions with token types instead
 // of grammar rules. This is called precedence climbing.
 
-type PrefixParseFn = fn(&mut Parser) -> Option<Box<dyn ast::Expression>>;
-type InfixParseFn = fn(&mut Parser, Box<dyn ast::Expression>) -> Option<Box<dyn ast::Expression>>;
+type PrefixParseFn<I> = fn(&mut Parser<I>) -> Option<ast::Expression>;
+// Infix parsing functions are called with the left operand already parsed
+// and the operator as current token. The ones registered with
+// Parser::register_infix() can be closures, they are shared so the parser
+// can call them while it is borrowed.
+pub type InfixParseFn<I> = Rc<dyn Fn(&mut Parser<I>, ast::Expression) -> Option<ast::Expression>>;
+type BuiltinInfixParseFn<I> = fn(&mut Parser<I>, ast::Expression) -> Option<ast::Expression>;
+
+// Take out of the token source the errors explaining the Illegal tokens it
+// produced so far.
+type SourceErrorsFn<I> = fn(&mut I) -> Vec<LexerError>;
 
 // Defining precedence
 #[allow(dead_code)]
-#[derive(PartialOrd, PartialEq)]
-enum Precedence {
+#[derive(PartialOrd, PartialEq, Debug, Clone, Copy)]
+pub enum Precedence {
     Lowest = 1,
+    Assign,      // =
+    Ternary,     // ? :
+    LogicalOr,   // ||
+    LogicalAnd,  // &&
     Equals,      // ==
     LessGreater, // > or <
     Sum,         // +
-    Product,     // *
+    Product,     // * / %
     Prefix,      // -X or !X
+    Power,       // **, above prefix operators so -2 ** 2 is -(2 ** 2)
+    Postfix,     // X++ or X--
     Call,        // myFunction(X)
+    Index,       // array[index]
+    Member,      // object.property or object.method()
 }
 
 #[allow(dead_code)]
-pub struct Parser<'l> {
-    lexer: Lexer<'l>,
+pub struct Parser<I: Iterator<Item = Token>> {
+    tokens: TokenStream<I>,
+    source_errors: Option<SourceErrorsFn<I>>,
     cur_token: Token,
-    peek_token: Token,
-    pub errors: Vec<String>,
-    prefix_parse_fns: HashMap<TokenType, PrefixParseFn>,
-    infix_parse_fns: HashMap<TokenType, InfixParseFn>,
+    pub errors: Vec<ParserError>,
+    pub warnings: Vec<ParserWarning>,
+    depth: usize,     // Current nesting depth.
+    max_depth: usize, // Nesting depth above which we stop parsing.
+    too_deep: bool,   // The max depth was reached, we gave up parsing.
+    // Operators registered with register_infix(). They take over the built-in
+    // ones for the same token.
+    operators: HashMap<TokenType, (Precedence, InfixParseFn<I>)>,
 }
 
 // TODO: As we have the same lifetime as lexer maybe we can use a reference to
 // Token instead of creating a new one and so creating new string. But maybe it is
 // completely ok.
-#[allow(dead_code)]
-impl<'l> Parser<'l> {
+impl<'l> Parser<Lexer<'l>> {
     pub fn new(lexer: Lexer<'l>) -> Self {
+        Parser::with_source(lexer, Some(|lexer| lexer.errors.drain(..).collect()))
+    }
+}
+
+#[allow(dead_code)]
+impl<I: Iterator<Item = Token>> Parser<I> {
+    // Parse tokens coming from anything else than the lexer: hand-built
+    // sequences or another front end. As nothing explains the Illegal tokens
+    // we report them ourselves.
+    pub fn from_tokens(tokens: I) -> Self {
+        Parser::with_source(tokens, None)
+    }
+
+    fn with_source(tokens: I, source_errors: Option<SourceErrorsFn<I>>) -> Self {
         let mut p = Parser {
-            lexer,
-            cur_token: Token {
-                token_type: TokenType::Illegal,
-                literal: String::from("Dummy"),
-            },
-            peek_token: Token {
-                token_type: TokenType::Illegal,
-                literal: String::from("Dummy"),
-            },
+            tokens: TokenStream::new(tokens),
+            source_errors,
+            cur_token: Token::new(TokenType::Illegal, "Dummy"),
             errors: Vec::new(),
-            prefix_parse_fns: HashMap::new(),
-            infix_parse_fns: HashMap::new(),
+            warnings: Vec::new(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            too_deep: false,
+            operators: HashMap::new(),
         };
 
-        // Register prefix parsing functions.
-        p.register_prefix(TokenType::Ident, |parser| Parser::parse_identifier(parser));
-        p.register_prefix(TokenType::Int, |parser| {
-            Parser::parse_integer_literal(parser)
-        });
-        p.register_prefix(TokenType::Bang, |parser| {
-            Parser::parse_prefix_expression(parser)
-        });
-        p.register_prefix(TokenType::Minus, |parser| {
-            Parser::parse_prefix_expression(parser)
-        });
-
-        // Register infix parsing functions.
-        p.register_infix(TokenType::Plus, |parser, left| {
-            Parser::parse_infix_expression(parser, left)
-        });
-        p.register_infix(TokenType::Minus, |parser, left| {
-            Parser::parse_infix_expression(parser, left)
-        });
-        p.register_infix(TokenType::Slash, |parser, left| {
-            Parser::parse_infix_expression(parser, left)
-        });
-        p.register_infix(TokenType::Asterisk, |parser, left| {
-            Parser::parse_infix_expression(parser, left)
-        });
-        p.register_infix(TokenType::Equal, |parser, left| {
-            Parser::parse_infix_expression(parser, left)
-        });
-        p.register_infix(TokenType::NotEqual, |parser, left| {
-            Parser::parse_infix_expression(parser, left)
-        });
-        p.register_infix(TokenType::LT, |parser, left| {
-            Parser::parse_infix_expression(parser, left)
-        });
-        p.register_infix(TokenType::GT, |parser, left| {
-            Parser::parse_infix_expression(parser, left)
-        });
-
-        // Read two tokens, so cur_token and peek_token will be both set.
-        p.next_token();
+        // Read the first token, so cur_token will be set. The next one is
+        // available through the token stream.
         p.next_token();
         p
     }
 
+    // Change how deep expressions and blocks can be nested before we report
+    // a NestingTooDeep error.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    // Make `token_type` an infix operator binding at `precedence` and parsed
+    // by `func`. Parser::parse_infix_expression can be used for a plain
+    // binary operator. It replaces the built-in operator if there is one.
+    pub fn register_infix(
+        &mut self,
+        token_type: TokenType,
+        precedence: Precedence,
+        func: impl Fn(&mut Parser<I>, ast::Expression) -> Option<ast::Expression> + 'static,
+    ) {
+        self.operators
+            .insert(token_type, (precedence, Rc::new(func)));
+    }
+
+    // The token being parsed. For infix parsing functions it is the operator.
+    pub fn cur_token(&self) -> &Token {
+        &self.cur_token
+    }
+
+    // Parse the whole input and return the program, or all the errors found
+    // if there is any. Use parse_program() to get the program and look at the
+    // errors separately.
+    pub fn parse(mut self) -> Result<ast::Program, Vec<ParserError>> {
+        let program = self.parse_program();
+
+        if self.errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(self.errors)
+        }
+    }
+
     // This is the entry point for parsing a program.
-    // We keep parsing statements until we reach the end of the input.
+    // We keep parsing statements until we reach the end of the input. Errors
+    // are collected in self.errors.
     pub fn parse_program(&mut self) -> ast::Program {
         let mut program = ast::Program::default();
 
-        while self.cur_token.token_type != TokenType::EOF {
-            let stmt_opt = self.parse_statement();
+        while self.cur_token.token_type != TokenType::EOF && !self.too_deep {
+            let stmt_opt = self.parse_statement_or_synchronize();
             if let Some(stmt) = stmt_opt {
                 program.push(stmt);
             }
             self.next_token();
         }
+        program.comments = comments(&self.cur_token.leading_trivia);
         program
     }
 
@@ -122,15 +338,42 @@ impl<'l> Parser<'l> {
     // PARSING STATEMENTS
     // ========================================================================
 
+    // Parse a statement and, if it fails, skip what remains of it so a single
+    // mistake produces a single error instead of a cascade of them. The error
+    // can be one of the lexer, already reported.
+    fn parse_statement_or_synchronize(&mut self) -> Option<ast::Statement> {
+        let stmt = self.parse_statement();
+        if stmt.is_none() && !self.too_deep {
+            self.synchronize();
+        }
+
+        stmt
+    }
+
     // This is the entry point for parsing a statement.
     // In the current implementation we only support let statements. So if the token
     // matches let we parse a let statement, otherwise we return None.
-    fn parse_statement(&mut self) -> Option<Box<dyn ast::Statement>> {
-        match self.cur_token.token_type {
+    fn parse_statement(&mut self) -> Option<ast::Statement> {
+        let start = self.cur_token.span.start;
+        let mut stmt_comments = comments(&self.cur_token.leading_trivia);
+
+        let mut stmt = match self.cur_token.token_type {
             TokenType::Let => self.parse_let_statement(),
+            TokenType::Const => self.parse_const_statement(),
+            TokenType::Import => self.parse_import_statement(),
             TokenType::Return => self.parse_return_statement(),
+            TokenType::For => self.parse_for_in_statement(),
             _ => self.parse_expression_statement(),
-        }
+        }?;
+
+        // The statement ends on its last token.
+        stmt.set_span(Span {
+            start,
+            end: self.cur_token.span.end,
+        });
+        stmt_comments.extend(comments(&self.cur_token.trailing_trivia));
+        stmt.set_comments(stmt_comments);
+        Some(stmt)
     }
 
     // This is the entry point for parsing a let statement.
@@ -139,59 +382,204 @@ impl<'l> Parser<'l> {
     // - let token
     // - identifier token
     // - assign token
-    // - expression (TODO: parse expression, currently we skip it)
-    // - semicolon token
-    fn parse_let_statement(&mut self) -> Option<Box<dyn ast::Statement>> {
-        let mut stmt_builder = ast::LetStatementBuilder::new(&self.cur_token);
+    // - expression
+    // - semicolon token, that is optional as for expression statements
+    // The binding can also be declared alone, `let <identifier>;`, and
+    // assigned later.
+    fn parse_let_statement(&mut self) -> Option<ast::Statement> {
+        let stmt_builder = ast::LetStatementBuilder::new(&self.cur_token);
 
         if !self.expect_peek(&TokenType::Ident) {
             return None;
         }
 
-        stmt_builder.name(ast::Identifier::new(&self.cur_token));
+        let stmt_builder = stmt_builder.name(ast::Identifier::new(&self.cur_token));
+
+        // Without initializer the semicolon is required.
+        if self.peek_token_is(&TokenType::Semicolon) {
+            self.next_token();
+            return Some(ast::Statement::Let(stmt_builder.build()));
+        }
 
         if !self.expect_peek(&TokenType::Assign) {
             return None;
         }
 
-        // TODO: We're skipping the expressions until we encounter a semicolon.
-        // To be able to build it we pass a dummy expression.
-        let dummy_expr = ast::Identifier::new(&self.cur_token);
-        stmt_builder.value(Some(Box::new(dummy_expr)));
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+        let stmt_builder = stmt_builder.value(Some(value));
 
-        while !self.cur_token_is(&TokenType::Semicolon) {
+        if self.peek_token_is(&TokenType::Semicolon) {
             self.next_token();
         }
 
         let let_stmt = stmt_builder.build();
-        Some(Box::new(let_stmt))
+        Some(ast::Statement::Let(let_stmt))
+    }
+
+    // Const statement is of the form: const <identifier> = <expression>;
+    // It is parsed like a let statement but kept as its own node so that the
+    // binding can later be enforced as immutable.
+    fn parse_const_statement(&mut self) -> Option<ast::Statement> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(&TokenType::Ident) {
+            return None;
+        }
+
+        let name = ast::Identifier::new(&self.cur_token);
+
+        if !self.expect_peek(&TokenType::Assign) {
+            return None;
+        }
+
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        if self.peek_token_is(&TokenType::Semicolon) {
+            self.next_token();
+        }
+
+        Some(ast::Statement::Const(ast::ConstStatement::new(
+            &token, name, value,
+        )))
+    }
+
+    // Import statement is of the form: import "<path>";
+    // The path is made of segments separated by '/', none of them can be
+    // empty. Finding and loading the module is left to the module loader.
+    fn parse_import_statement(&mut self) -> Option<ast::Statement> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(&TokenType::String) {
+            return None;
+        }
+
+        let path = self.cur_token.literal();
+        if path.split('/').any(|segment| segment.is_empty()) {
+            self.errors.push(ParserError::InvalidImportPath {
+                path,
+                position: self.cur_token.position,
+            });
+            return None;
+        }
+
+        if self.peek_token_is(&TokenType::Semicolon) {
+            self.next_token();
+        }
+
+        Some(ast::Statement::Import(ast::ImportStatement::new(
+            &token, &path,
+        )))
     }
 
     // This is the entry point for parsing a return statement.
     // Return statement is of the form: return <expression>;
-    fn parse_return_statement(&mut self) -> Option<Box<dyn ast::Statement>> {
-        let mut stmt_builder = ast::ReturnStatementBuilder::new(&self.cur_token);
+    // As for let statements the semicolon is optional.
+    fn parse_return_statement(&mut self) -> Option<ast::Statement> {
+        let stmt_builder = ast::ReturnStatementBuilder::new(&self.cur_token);
 
         self.next_token();
 
-        // TODO: We're skipping the expressions until we encounter a semicolon.
-        // To be able to build it we pass a dummy expression.
-        let dummy_expr = ast::Identifier::new(&self.cur_token);
-        stmt_builder.return_value(Some(Box::new(dummy_expr)));
+        let return_value = self.parse_expression(Precedence::Lowest)?;
+        let stmt_builder = stmt_builder.return_value(return_value);
 
-        while !self.cur_token_is(&TokenType::Semicolon) {
+        if self.peek_token_is(&TokenType::Semicolon) {
             self.next_token();
         }
 
         let ret_stmt = stmt_builder.build();
-        Some(Box::new(ret_stmt))
+        Some(ast::Statement::Return(ret_stmt))
+    }
+
+    // For-in statement is of the form:
+    //   for (<identifier> in <expression>) { <statements> }
+    // It can be followed by an optional semicolon.
+    fn parse_for_in_statement(&mut self) -> Option<ast::Statement> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(&TokenType::LParen) || !self.expect_peek(&TokenType::Ident) {
+            return None;
+        }
+
+        let variable = ast::Identifier::new(&self.cur_token);
+
+        if !self.expect_peek(&TokenType::In) {
+            return None;
+        }
+
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect_peek(&TokenType::RParen) || !self.expect_peek(&TokenType::LBrace) {
+            return None;
+        }
+
+        let body = self.parse_block_statement()?;
+
+        if self.peek_token_is(&TokenType::Semicolon) {
+            self.next_token();
+        }
+
+        let stmt = ast::ForInStatement::new(&token, variable, iterable, body);
+        Some(ast::Statement::ForIn(stmt))
+    }
+
+    // Parse statements until the closing brace. The current token is the
+    // opening brace and, on success, the closing one when we return.
+    fn parse_block_statement(&mut self) -> Option<ast::BlockStatement> {
+        self.nested(|parser| parser.parse_block_statement_content())
+    }
+
+    fn parse_block_statement_content(&mut self) -> Option<ast::BlockStatement> {
+        let token = self.cur_token.clone();
+        let mut statements = Vec::new();
+
+        let mut failed = false;
+
+        self.next_token();
+
+        while !self.cur_token_is(&TokenType::RBrace) {
+            if self.cur_token_is(&TokenType::EOF) {
+                // If a statement failed we may have skipped the closing brace
+                // while synchronizing, so don't report it twice.
+                if !failed {
+                    self.errors.push(ParserError::UnclosedBlock {
+                        position: token.position,
+                    });
+                }
+                return None;
+            }
+
+            match self.parse_statement_or_synchronize() {
+                Some(stmt) => statements.push(stmt),
+                None if self.too_deep => return None,
+                None => {
+                    failed = true;
+                    // The statement stopped on the closing brace, it is ours.
+                    if self.cur_token_is(&TokenType::RBrace) {
+                        continue;
+                    }
+                }
+            }
+            self.next_token();
+        }
+
+        let mut block = ast::BlockStatement::new(&token, statements);
+        block.comments = comments(&self.cur_token.leading_trivia);
+        Some(block)
     }
 
     // This is the entry point for parsing an expression statement.
-    fn parse_expression_statement(&mut self) -> Option<Box<dyn ast::Statement>> {
-        let mut stmt_builder = ast::ExpressionStatementBuilder::new(&self.cur_token);
+    fn parse_expression_statement(&mut self) -> Option<ast::Statement> {
+        let stmt_builder = ast::ExpressionStatementBuilder::new(&self.cur_token);
 
-        stmt_builder.expression(self.parse_expression(Precedence::Lowest));
+        // If the expression can't be parsed an error has been recorded, so just
+        // drop the statement.
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        let stmt_builder = stmt_builder.expression(expression);
 
         // Semi colon is optional. If we have it we skip it but if we don't have
         // it it is fine.
@@ -200,98 +588,577 @@ impl<'l> Parser<'l> {
         }
 
         let expr_stmt = stmt_builder.build();
-        Some(Box::new(expr_stmt))
+        Some(ast::Statement::Expression(expr_stmt))
     }
 
     // ========================================================================
     // PARSING EXPRESSIONS
     // ========================================================================
-    fn parse_expression(&mut self, precedence: Precedence) -> Option<Box<dyn ast::Expression>> {
-        let prefix_opt = self.prefix_parse_fns.get(&self.cur_token.token_type);
+    // Parse an expression starting at the current token and made of operators
+    // binding tighter than `precedence`.
+    pub fn parse_expression(&mut self, precedence: Precedence) -> Option<ast::Expression> {
+        self.nested(|parser| parser.parse_expression_at(precedence))
+    }
+
+    fn parse_expression_at(&mut self, precedence: Precedence) -> Option<ast::Expression> {
+        let prefix_opt = Self::prefix_parse_fn(&self.cur_token.token_type);
 
         // Check if we have a parsing function associated with the current token. If we
         // do we call it, otherwise we return None.
         if let Some(prefix) = prefix_opt {
-            let mut left_expr = prefix(self);
-
-            while !self.peek_token_is(&TokenType::Semicolon) && precedence < self.peek_precedence()
-            {
-                let ipf = self
-                    .infix_parse_fns
-                    .get(&self.peek_token.token_type)
-                    .cloned();
-                match ipf {
-                    Some(infix) => {
-                        self.next_token();
-                        left_expr = infix(self, left_expr.unwrap());
-                    }
-                    None => return left_expr,
-                }
-            }
+            // If the prefix failed there is nothing to apply an infix to.
+            let left_expr = prefix(self)?;
 
-            left_expr
+            // Each operator applied in the loop nests the expression built so
+            // far one level deeper, a long chain like 1+1+...+1 is as deep as
+            // many parentheses.
+            let depth = self.depth;
+            let expr = self.parse_infix_chain(left_expr, precedence);
+            self.depth = depth;
+            expr
         } else {
-            let msg = format!(
-                "No prefix parse function found for {:?}",
-                self.cur_token.token_type
-            );
-            self.errors.push(msg);
+            // The lexer already reported Illegal tokens.
+            if !self.cur_token_is(&TokenType::Illegal) || self.source_errors.is_none() {
+                self.errors.push(ParserError::NoPrefixParseFn {
+                    token: self.cur_token.token_type.clone(),
+                    position: self.cur_token.position,
+                });
+            }
             None
         }
     }
 
-    fn parse_identifier(&mut self) -> Option<Box<dyn ast::Expression>> {
-        Some(Box::new(ast::Identifier::new(&self.cur_token)))
+    fn parse_infix_chain(
+        &mut self,
+        mut left_expr: ast::Expression,
+        precedence: Precedence,
+    ) -> Option<ast::Expression> {
+        while !self.peek_token_is(&TokenType::Semicolon) && precedence < self.peek_precedence() {
+            if !self.is_infix(&self.tokens.peek().token_type) {
+                return Some(left_expr);
+            }
+
+            if !self.enter_level() {
+                return None;
+            }
+
+            self.next_token();
+            // A failed infix has already recorded why. Stop here, the
+            // operators that follow have no left operand.
+            left_expr = self.parse_infix(left_expr)?;
+        }
+
+        Some(left_expr)
+    }
+
+    fn parse_identifier(&mut self) -> Option<ast::Expression> {
+        Some(ast::Expression::Identifier(ast::Identifier::new(
+            &self.cur_token,
+        )))
     }
 
-    fn parse_integer_literal(&mut self) -> Option<Box<dyn ast::Expression>> {
-        if let Ok(value) = self.cur_token.literal.parse::<i64>() {
+    // The literal can be prefixed by 0x, 0b or 0o, in which case it is parsed
+    // in base 16, 2 or 8 respectively.
+    fn parse_integer_literal(&mut self) -> Option<ast::Expression> {
+        let literal = self.cur_token.literal.as_str();
+        let (digits, radix) = match literal.get(..2) {
+            Some("0x") | Some("0X") => (&literal[2..], 16),
+            Some("0b") | Some("0B") => (&literal[2..], 2),
+            Some("0o") | Some("0O") => (&literal[2..], 8),
+            _ => (literal, 10),
+        };
+
+        if let Ok(value) = i64::from_str_radix(digits, radix) {
             let lit = ast::IntegerLiteral::new(&self.cur_token, value);
-            Some(Box::new(lit))
+            Some(ast::Expression::IntegerLiteral(lit))
         } else {
-            let msg = format!(
-                "Could not parse {} as integer",
-                self.cur_token.literal.as_str()
-            );
-            self.errors.push(msg);
+            self.errors.push(ParserError::InvalidIntegerLiteral {
+                literal: self.cur_token.literal.clone(),
+                position: self.cur_token.position,
+            });
             None
         }
     }
 
-    fn parse_prefix_expression(&mut self) -> Option<Box<dyn ast::Expression>> {
-        let mut expr_builder = ast::PrefixExpressionBuilder::new(&self.cur_token);
-        expr_builder.operator(self.cur_token.literal.clone());
+    fn parse_float_literal(&mut self) -> Option<ast::Expression> {
+        if let Ok(value) = self.cur_token.literal.parse::<f64>() {
+            let lit = ast::FloatLiteral::new(&self.cur_token, value);
+            Some(ast::Expression::FloatLiteral(lit))
+        } else {
+            self.errors.push(ParserError::InvalidFloatLiteral {
+                literal: self.cur_token.literal.clone(),
+                position: self.cur_token.position,
+            });
+            None
+        }
+    }
+
+    fn parse_boolean(&mut self) -> Option<ast::Expression> {
+        let value = self.cur_token_is(&TokenType::True);
+        let lit = ast::Boolean::new(&self.cur_token, value);
+        Some(ast::Expression::Boolean(lit))
+    }
+
+    fn parse_string_literal(&mut self) -> Option<ast::Expression> {
+        let lit = ast::StringLiteral::new(&self.cur_token, &self.cur_token.literal);
+        Some(ast::Expression::StringLiteral(lit))
+    }
+
+    fn parse_prefix_expression(&mut self) -> Option<ast::Expression> {
+        let expr_builder = ast::PrefixExpressionBuilder::new(&self.cur_token)
+            .operator(self.cur_token.literal.clone());
 
         self.next_token();
 
-        expr_builder.right(self.parse_expression(Precedence::Prefix));
+        let right = self.parse_expression(Precedence::Prefix)?;
+        let expr_builder = expr_builder.right(right);
 
-        Some(Box::new(expr_builder.build()))
+        Some(ast::Expression::Prefix(expr_builder.build()))
     }
 
-    fn parse_infix_expression(
-        &mut self,
-        left: Box<dyn ast::Expression>,
-    ) -> Option<Box<dyn ast::Expression>> {
-        let mut expr_builder = ast::InfixExpressionBuilder::new(&self.cur_token);
-        expr_builder.operator(self.cur_token.literal.clone());
-        expr_builder.left(Some(left));
+    // The lexer reads -- as one token, before an operand it is two minus
+    // signs: --x is -(-x). Each one is a level of nesting, like when they are
+    // written apart.
+    fn parse_minus_minus(&mut self) -> Option<ast::Expression> {
+        let mut outer = self.cur_token.clone();
+        outer.token_type = TokenType::Minus;
+        outer.literal = "-".to_string();
+        outer.span.end = outer.span.start + 1;
+        outer.trailing_trivia.clear();
+        let mut inner = outer.clone();
+        inner.position.column += 1;
+        inner.span = Span {
+            start: outer.span.end,
+            end: outer.span.end + 1,
+        };
+        inner.leading_trivia = Vec::new();
+        inner.trailing_trivia = self.cur_token.trailing_trivia.clone();
+
+        self.next_token();
+        let right = self.nested(|parser| parser.parse_expression(Precedence::Prefix))?;
+        let right = ast::PrefixExpressionBuilder::new(&inner)
+            .operator(inner.literal.clone())
+            .right(right)
+            .build();
+        let expr = ast::PrefixExpressionBuilder::new(&outer)
+            .operator(outer.literal.clone())
+            .right(ast::Expression::Prefix(right))
+            .build();
+        Some(ast::Expression::Prefix(expr))
+    }
+
+    // Parentheses only change the way the AST is built: the expression inside
+    // is parsed from the lowest precedence, so it is grouped whatever the
+    // operators around it.
+    fn parse_grouped_expression(&mut self) -> Option<ast::Expression> {
+        self.next_token();
+
+        let expr = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect_peek(&TokenType::RParen) {
+            return None;
+        }
+
+        Some(expr)
+    }
+
+    pub fn parse_infix_expression(&mut self, left: ast::Expression) -> Option<ast::Expression> {
+        let expr_builder = ast::InfixExpressionBuilder::new(&self.cur_token)
+            .operator(self.cur_token.literal.clone())
+            .left(left);
+
+        let precedence = match self.cur_token.token_type {
+            // Right associative, 2 ** 3 ** 2 is 2 ** (3 ** 2): the right
+            // operand takes the following ** with it.
+            TokenType::Power => Precedence::Prefix,
+            _ => self.cur_precedence(),
+        };
+        self.next_token();
+        let right = self.parse_expression(precedence)?;
+        let expr_builder = expr_builder.right(right);
+
+        Some(ast::Expression::Infix(expr_builder.build()))
+    }
+
+    fn parse_array_literal(&mut self) -> Option<ast::Expression> {
+        let token = self.cur_token.clone();
+        let elements = self.parse_expression_list(&TokenType::RBracket)?;
+
+        Some(ast::Expression::ArrayLiteral(ast::ArrayLiteral::new(
+            &token, elements,
+        )))
+    }
+
+    fn parse_hash_literal(&mut self) -> Option<ast::Expression> {
+        let token = self.cur_token.clone();
+        let mut pairs = Vec::new();
+
+        while !self.peek_token_is(&TokenType::RBrace) {
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
+            if !self.expect_peek(&TokenType::Colon) {
+                return None;
+            }
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+            pairs.push((key, value));
+
+            if !self.peek_token_is(&TokenType::RBrace) && !self.expect_peek(&TokenType::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+
+        Some(ast::Expression::HashLiteral(ast::HashLiteral::new(
+            &token, pairs,
+        )))
+    }
+
+    // The operand has already been parsed and the current token is the
+    // operator. Nothing follows a postfix operator so there is nothing more
+    // to parse.
+    fn parse_postfix_expression(&mut self, left: ast::Expression) -> Option<ast::Expression> {
+        let expr = ast::PostfixExpression::new(&self.cur_token, left);
+        Some(ast::Expression::Postfix(expr))
+    }
+
+    // The object has already been parsed and the current token is '.'.
+    // A name followed by '(' is a method call, otherwise it is a property
+    // access.
+    fn parse_member_expression(&mut self, object: ast::Expression) -> Option<ast::Expression> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(&TokenType::Ident) {
+            return None;
+        }
+
+        let name = ast::Identifier::new(&self.cur_token);
+
+        if !self.peek_token_is(&TokenType::LParen) {
+            let expr = ast::PropertyAccess::new(&token, object, name);
+            return Some(ast::Expression::PropertyAccess(expr));
+        }
+
+        self.next_token();
+        let arguments = self.parse_expression_list(&TokenType::RParen)?;
+
+        let expr = ast::MethodCallExpression::new(&token, object, name, arguments);
+        Some(ast::Expression::MethodCall(expr))
+    }
+
+    // The target has already been parsed and the current token is '='. The
+    // value is parsed from the lowest precedence so assignments chain from
+    // the right: a = b = c is a = (b = c).
+    fn parse_assign_expression(&mut self, target: ast::Expression) -> Option<ast::Expression> {
+        let token = self.cur_token.clone();
+
+        if !matches!(
+            target,
+            ast::Expression::Identifier(_)
+                | ast::Expression::Index(_)
+                | ast::Expression::PropertyAccess(_)
+        ) {
+            self.errors.push(ParserError::InvalidAssignmentTarget {
+                target: target.to_string(),
+                position: token.position,
+            });
+            return None;
+        }
+
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        let expr = ast::AssignExpression::new(&token, target, value);
+        Some(ast::Expression::Assign(expr))
+    }
+
+    // The condition has already been parsed and the current token is '?'.
+    // The alternative is parsed from the lowest precedence, so another
+    // ternary in it takes it all: a ? b : c ? d : e is a ? b : (c ? d : e).
+    fn parse_ternary_expression(&mut self, condition: ast::Expression) -> Option<ast::Expression> {
+        let token = self.cur_token.clone();
+
+        self.next_token();
+        let consequence = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect_peek(&TokenType::Colon) {
+            return None;
+        }
+
+        self.next_token();
+        let alternative = self.parse_expression(Precedence::Lowest)?;
+
+        let expr = ast::TernaryExpression::new(&token, condition, consequence, alternative);
+        Some(ast::Expression::Ternary(expr))
+    }
+
+    // If expression is of the form:
+    //   if (<condition>) <consequence> else <alternative>
+    // where the else part is optional.
+    fn parse_if_expression(&mut self) -> Option<ast::Expression> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(&TokenType::LParen) {
+            return None;
+        }
+
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.check_condition(&condition);
+
+        if !self.expect_peek(&TokenType::RParen) || !self.expect_peek(&TokenType::LBrace) {
+            return None;
+        }
+
+        let consequence = self.parse_block_statement()?;
+
+        let mut alternative = None;
+        if self.peek_token_is(&TokenType::Else) {
+            self.next_token();
+
+            if !self.expect_peek(&TokenType::LBrace) {
+                return None;
+            }
+
+            alternative = Some(self.parse_block_statement()?);
+        }
+
+        let expr = ast::IfExpression::new(&token, condition, consequence, alternative);
+        Some(ast::Expression::If(expr))
+    }
+
+    // Warn about conditions that are valid but most likely typos.
+    fn check_condition(&mut self, condition: &ast::Expression) {
+        if let ast::Expression::Assign(assign) = condition {
+            self.warnings.push(ParserWarning::AssignmentInCondition {
+                position: assign.token.position,
+            });
+        }
+    }
+
+    // Function literal is of the form: fn(<parameters>) <block statement>
+    fn parse_function_literal(&mut self) -> Option<ast::Expression> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(&TokenType::LParen) {
+            return None;
+        }
+
+        let parameters = self.parse_parameters(&TokenType::RParen)?;
+
+        if !self.expect_peek(&TokenType::LBrace) {
+            return None;
+        }
+
+        let body = self.parse_block_statement()?;
+
+        let expr = ast::FunctionLiteral::new(&token, parameters, body);
+        Some(ast::Expression::FunctionLiteral(expr))
+    }
+
+    // Lambda is the short form of a function literal: |<parameters>| <body>
+    // The body is a block or a single expression, so `|x| x * 2` is the same
+    // function as `fn(x) { x * 2 }`. Without parameters the two pipes are
+    // read as `||`.
+    fn parse_lambda(&mut self) -> Option<ast::Expression> {
+        let token = self.cur_token.clone();
+
+        let parameters = if self.cur_token_is(&TokenType::Or) {
+            Vec::new()
+        } else {
+            self.parse_parameters(&TokenType::Pipe)?
+        };
+
+        self.next_token();
+        let body = if self.cur_token_is(&TokenType::LBrace) {
+            self.parse_block_statement()?
+        } else {
+            let body_token = self.cur_token.clone();
+            let stmt_builder = ast::ExpressionStatementBuilder::new(&body_token)
+                .expression(self.parse_expression(Precedence::Lowest)?);
+
+            let statements = vec![ast::Statement::Expression(stmt_builder.build())];
+            ast::BlockStatement::new(&body_token, statements)
+        };
+
+        let expr = ast::FunctionLiteral::new(&token, parameters, body);
+        Some(ast::Expression::FunctionLiteral(expr))
+    }
+
+    // Macro literal is of the form: macro(<parameters>) <block statement>
+    // The body works on quoted code: `quote(...)` and `unquote(...)` are not
+    // special for the parser, they are plain call expressions that the macro
+    // expansion recognizes by the name of the called function.
+    fn parse_macro_literal(&mut self) -> Option<ast::Expression> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(&TokenType::LParen) {
+            return None;
+        }
+
+        let parameters = self.parse_parameters(&TokenType::RParen)?;
+
+        if !self.expect_peek(&TokenType::LBrace) {
+            return None;
+        }
+
+        let body = self.parse_block_statement()?;
+
+        let expr = ast::MacroLiteral::new(&token, parameters, body);
+        Some(ast::Expression::MacroLiteral(expr))
+    }
+
+    // Parse a comma separated list of identifiers. The current token is the
+    // one opening the list and, on success, `end` when we return.
+    fn parse_parameters(&mut self, end: &TokenType) -> Option<Vec<ast::Identifier>> {
+        let mut parameters = Vec::new();
+
+        if self.peek_token_is(end) {
+            self.next_token();
+            return Some(parameters);
+        }
+
+        if !self.expect_peek(&TokenType::Ident) {
+            return None;
+        }
+        parameters.push(ast::Identifier::new(&self.cur_token));
+
+        while self.peek_token_is(&TokenType::Comma) {
+            self.next_token();
+            if !self.expect_peek(&TokenType::Ident) {
+                return None;
+            }
+            parameters.push(ast::Identifier::new(&self.cur_token));
+        }
+
+        if !self.expect_peek(end) {
+            return None;
+        }
+
+        Some(parameters)
+    }
+
+    // The function has already been parsed and the current token is '('.
+    fn parse_call_expression(&mut self, function: ast::Expression) -> Option<ast::Expression> {
+        let expr_builder = ast::CallExpressionBuilder::new(&self.cur_token)
+            .function(function)
+            .arguments(self.parse_expression_list(&TokenType::RParen)?);
+
+        Some(ast::Expression::Call(expr_builder.build()))
+    }
+
+    // The indexed expression has already been parsed and the current token
+    // is '['.
+    fn parse_index_expression(&mut self, left: ast::Expression) -> Option<ast::Expression> {
+        let token = self.cur_token.clone();
+
+        self.next_token();
+        let index = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect_peek(&TokenType::RBracket) {
+            return None;
+        }
+
+        let expr = ast::IndexExpression::new(&token, left, index);
+        Some(ast::Expression::Index(expr))
+    }
+
+    // Parse a comma separated list of expressions until the `end` token, as
+    // found in call arguments and array literals. The current token is the
+    // one opening the list and, on success, the `end` token when we return.
+    fn parse_expression_list(&mut self, end: &TokenType) -> Option<Vec<ast::Expression>> {
+        let mut list = Vec::new();
+
+        if self.peek_token_is(end) {
+            self.next_token();
+            return Some(list);
+        }
 
-        let precedence: Precedence = self.cur_precedence();
         self.next_token();
-        expr_builder.right(self.parse_expression(precedence));
+        list.push(self.parse_expression(Precedence::Lowest)?);
 
-        Some(Box::new(expr_builder.build()))
+        while self.peek_token_is(&TokenType::Comma) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Precedence::Lowest)?);
+        }
+
+        if !self.expect_peek(end) {
+            return None;
+        }
+
+        Some(list)
     }
 
     // ========================================================================
     // HELPERS FUNCTIONS
     // ========================================================================
 
-    // Advance the lexer by one token and update the current and peek tokens.
-    fn next_token(&mut self) {
-        self.cur_token = self.peek_token.clone();
-        self.peek_token = self.lexer.next_token();
+    // Advance the token stream by one token and update the current token.
+    // Errors found by the lexer are reported as parser errors, they explain
+    // better than us why an Illegal token was found. The parsing functions of
+    // registered operators call it to move past the operator.
+    pub fn next_token(&mut self) {
+        self.cur_token = self.tokens.next_token();
+        if let Some(source_errors) = self.source_errors {
+            for err in source_errors(self.tokens.inner_mut()) {
+                self.errors.push(ParserError::Lexer(err));
+            }
+        }
+    }
+
+    // Run `parse` one nesting level deeper. If that is above the limit we
+    // report it and give up parsing the rest of the input: we can't tell
+    // where the statement would have ended anyway.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        if !self.enter_level() {
+            return None;
+        }
+
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // Go one nesting level deeper, or report that we are above the limit and
+    // return false. The caller restores the depth when it leaves the level.
+    fn enter_level(&mut self) -> bool {
+        if self.depth >= self.max_depth {
+            self.errors.push(ParserError::NestingTooDeep {
+                max_depth: self.max_depth,
+                position: self.cur_token.position,
+            });
+            self.too_deep = true;
+            return false;
+        }
+
+        self.depth += 1;
+        true
+    }
+
+    // Skip the tokens of a statement that failed to parse. We stop on its
+    // semicolon, or before the next token if it starts a new statement or
+    // closes a block. Like after any statement the current token is then the
+    // last one of it, so callers just have to move to the next token.
+    fn synchronize(&mut self) {
+        loop {
+            match self.cur_token.token_type {
+                TokenType::Semicolon | TokenType::RBrace | TokenType::EOF => return,
+                _ => {}
+            }
+
+            match self.tokens.peek().token_type {
+                TokenType::Let
+                | TokenType::Const
+                | TokenType::Import
+                | TokenType::Return
+                | TokenType::For
+                | TokenType::RBrace => return,
+                _ => {}
+            }
+
+            self.next_token();
+        }
     }
 
     // Check if the current token is of the expected type.
@@ -301,7 +1168,7 @@ impl<'l> Parser<'l> {
 
     // Check if the next token is of the expected type.
     fn peek_token_is(&self, token_type: &TokenType) -> bool {
-        self.peek_token.token_type == *token_type
+        self.tokens.peek().token_type == *token_type
     }
 
     // If the next token is the expected one then we advance to next token
@@ -317,37 +1184,105 @@ impl<'l> Parser<'l> {
     }
 
     fn peek_error(&mut self, token_type: &TokenType) {
-        let msg = format!(
-            "Expected next token to be {:?}, got {:?} instead",
-            *token_type, self.peek_token.token_type
-        );
-        self.errors.push(msg);
+        let got = self.tokens.peek();
+        self.errors.push(ParserError::UnexpectedToken {
+            expected: token_type.clone(),
+            got: got.token_type.clone(),
+            position: got.position,
+        });
+    }
+
+    // The token set is closed so the parsing functions associated with a
+    // token type are found with a match, the same way as its precedence.
+    fn prefix_parse_fn(token_type: &TokenType) -> Option<PrefixParseFn<I>> {
+        let func: PrefixParseFn<I> = match token_type {
+            TokenType::Ident => Self::parse_identifier,
+            TokenType::Int => Self::parse_integer_literal,
+            TokenType::Float => Self::parse_float_literal,
+            TokenType::True | TokenType::False => Self::parse_boolean,
+            TokenType::String => Self::parse_string_literal,
+            TokenType::Bang | TokenType::Minus => Self::parse_prefix_expression,
+            TokenType::MinusMinus => Self::parse_minus_minus,
+            TokenType::LParen => Self::parse_grouped_expression,
+            TokenType::LBracket => Self::parse_array_literal,
+            TokenType::LBrace => Self::parse_hash_literal,
+            TokenType::If => Self::parse_if_expression,
+            TokenType::Function => Self::parse_function_literal,
+            TokenType::Pipe | TokenType::Or => Self::parse_lambda,
+            TokenType::Macro => Self::parse_macro_literal,
+            _ => return None,
+        };
+        Some(func)
     }
 
-    fn register_prefix(&mut self, token_type: TokenType, func: PrefixParseFn) {
-        self.prefix_parse_fns.insert(token_type, func);
+    fn is_infix(&self, token_type: &TokenType) -> bool {
+        self.operators.contains_key(token_type) || Self::infix_parse_fn(token_type).is_some()
     }
 
-    fn register_infix(&mut self, token_type: TokenType, func: InfixParseFn) {
-        self.infix_parse_fns.insert(token_type, func);
+    // Parse the operator of the current token with its registered function,
+    // or the built-in one.
+    fn parse_infix(&mut self, left: ast::Expression) -> Option<ast::Expression> {
+        if let Some((_, func)) = self.operators.get(&self.cur_token.token_type) {
+            let func = Rc::clone(func);
+            return func(self, left);
+        }
+        let func = Self::infix_parse_fn(&self.cur_token.token_type)?;
+        func(self, left)
     }
 
-    fn precedences(token_type: &TokenType) -> Precedence {
+    fn infix_parse_fn(token_type: &TokenType) -> Option<BuiltinInfixParseFn<I>> {
+        let func: BuiltinInfixParseFn<I> = match token_type {
+            TokenType::Plus
+            | TokenType::Minus
+            | TokenType::Slash
+            | TokenType::Asterisk
+            | TokenType::Percent
+            | TokenType::Power
+            | TokenType::Equal
+            | TokenType::NotEqual
+            | TokenType::LT
+            | TokenType::GT
+            | TokenType::And
+            | TokenType::Or => Self::parse_infix_expression,
+            TokenType::LParen => Self::parse_call_expression,
+            TokenType::LBracket => Self::parse_index_expression,
+            TokenType::PlusPlus | TokenType::MinusMinus => Self::parse_postfix_expression,
+            TokenType::Dot => Self::parse_member_expression,
+            TokenType::Question => Self::parse_ternary_expression,
+            TokenType::Assign => Self::parse_assign_expression,
+            _ => return None,
+        };
+        Some(func)
+    }
+
+    fn precedences(&self, token_type: &TokenType) -> Precedence {
+        if let Some((precedence, _)) = self.operators.get(token_type) {
+            return *precedence;
+        }
+
         match token_type {
+            TokenType::Assign => Precedence::Assign,
+            TokenType::Question => Precedence::Ternary,
+            TokenType::Or => Precedence::LogicalOr,
+            TokenType::And => Precedence::LogicalAnd,
             TokenType::Equal | TokenType::NotEqual => Precedence::Equals,
             TokenType::LT | TokenType::GT => Precedence::LessGreater,
             TokenType::Plus | TokenType::Minus => Precedence::Sum,
-            TokenType::Slash | TokenType::Asterisk => Precedence::Product,
+            TokenType::Slash | TokenType::Asterisk | TokenType::Percent => Precedence::Product,
+            TokenType::Power => Precedence::Power,
+            TokenType::PlusPlus | TokenType::MinusMinus => Precedence::Postfix,
             TokenType::LParen => Precedence::Call,
+            TokenType::LBracket => Precedence::Index,
+            TokenType::Dot => Precedence::Member,
             _ => Precedence::Lowest,
         }
     }
 
     fn peek_precedence(&self) -> Precedence {
-        Parser::precedences(&self.peek_token.token_type)
+        self.precedences(&self.tokens.peek().token_type)
     }
 
     fn cur_precedence(&self) -> Precedence {
-        Parser::precedences(&self.cur_token.token_type)
+        self.precedences(&self.cur_token.token_type)
     }
 }
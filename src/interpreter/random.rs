@@ -0,0 +1,46 @@
+// A small pseudo random number generator for the rand builtins, so we don't
+// need a dependency. It is SplitMix64, fast and good enough for scripts but
+// not for cryptography.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    // The same seed always gives the same numbers.
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    // Seeded with the current time.
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Rng::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // A float in [0, 1), from the 53 high bits so every value is as likely.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // An integer in [low, high), high must be greater than low. Multiplying
+    // instead of taking the remainder avoids favouring the small values.
+    pub fn next_in(&mut self, low: i64, high: i64) -> i64 {
+        let span = (high as i128 - low as i128) as u128;
+        let offset = (self.next_u64() as u128 * span) >> 64;
+        (low as i128 + offset as i128) as i64
+    }
+}
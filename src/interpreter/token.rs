@@ -1,5 +1,8 @@
 // Token definitions for the Monkey language.
+use std::fmt;
+
 #[derive(Eq, Hash, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenType {
     // Special tokens
     Illegal,
@@ -8,6 +11,8 @@ pub enum TokenType {
     // Identifiers + literals
     Ident,
     Int,
+    Float,
+    String,
 
     // One character operators
     Assign,
@@ -16,20 +21,32 @@ pub enum TokenType {
     Bang,
     Asterisk,
     Slash,
+    Percent,
     LT,
     GT,
+    Question,
 
     // Two characters operators
-    Equal,    // ==
-    NotEqual, // !=
+    Equal,      // ==
+    NotEqual,   // !=
+    And,        // &&
+    Or,         // ||
+    PlusPlus,   // ++
+    MinusMinus, // --
+    Power,      // **
 
     // Delimiters
     Comma,
+    Dot,
+    Pipe,
     Semicolon,
+    Colon,
     LParen,
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
 
     // Keywords
     Function,
@@ -39,15 +56,255 @@ pub enum TokenType {
     If,
     Else,
     Return,
+    For,
+    In,
+    Const,
+    Macro,
+    Import,
+}
+
+// The keywords of the language with their token type. It is the only place
+// where they are listed, tools needing them should use it or lookup_ident().
+pub const KEYWORDS: [(&str, TokenType); 12] = [
+    ("fn", TokenType::Function),
+    ("let", TokenType::Let),
+    ("true", TokenType::True),
+    ("false", TokenType::False),
+    ("if", TokenType::If),
+    ("else", TokenType::Else),
+    ("return", TokenType::Return),
+    ("for", TokenType::For),
+    ("in", TokenType::In),
+    ("const", TokenType::Const),
+    ("macro", TokenType::Macro),
+    ("import", TokenType::Import),
+];
+
+impl TokenType {
+    // Return the token type of a word read in the source: the keyword it is,
+    // or Ident.
+    pub fn lookup_ident(ident: &str) -> TokenType {
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == ident)
+            .map_or(TokenType::Ident, |(_, token_type)| token_type.clone())
+    }
+
+    // Return the text of the tokens that are always written the same way,
+    // None for identifiers, literals and special tokens.
+    pub fn symbol(&self) -> Option<&'static str> {
+        let symbol = match self {
+            TokenType::Illegal
+            | TokenType::EOF
+            | TokenType::Ident
+            | TokenType::Int
+            | TokenType::Float
+            | TokenType::String => return None,
+            TokenType::Assign => "=",
+            TokenType::Plus => "+",
+            TokenType::Minus => "-",
+            TokenType::Bang => "!",
+            TokenType::Asterisk => "*",
+            TokenType::Slash => "/",
+            TokenType::Percent => "%",
+            TokenType::LT => "<",
+            TokenType::GT => ">",
+            TokenType::Question => "?",
+            TokenType::Equal => "==",
+            TokenType::NotEqual => "!=",
+            TokenType::And => "&&",
+            TokenType::Or => "||",
+            TokenType::PlusPlus => "++",
+            TokenType::MinusMinus => "--",
+            TokenType::Power => "**",
+            TokenType::Comma => ",",
+            TokenType::Dot => ".",
+            TokenType::Pipe => "|",
+            TokenType::Semicolon => ";",
+            TokenType::Colon => ":",
+            TokenType::LParen => "(",
+            TokenType::RParen => ")",
+            TokenType::LBrace => "{",
+            TokenType::RBrace => "}",
+            TokenType::LBracket => "[",
+            TokenType::RBracket => "]",
+            TokenType::Function => "fn",
+            TokenType::Let => "let",
+            TokenType::True => "true",
+            TokenType::False => "false",
+            TokenType::If => "if",
+            TokenType::Else => "else",
+            TokenType::Return => "return",
+            TokenType::For => "for",
+            TokenType::In => "in",
+            TokenType::Const => "const",
+            TokenType::Macro => "macro",
+            TokenType::Import => "import",
+        };
+        Some(symbol)
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Function
+                | TokenType::Let
+                | TokenType::True
+                | TokenType::False
+                | TokenType::If
+                | TokenType::Else
+                | TokenType::Return
+                | TokenType::For
+                | TokenType::In
+                | TokenType::Const
+                | TokenType::Macro
+                | TokenType::Import
+        )
+    }
+
+    pub fn is_operator(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Assign
+                | TokenType::Plus
+                | TokenType::Minus
+                | TokenType::Bang
+                | TokenType::Asterisk
+                | TokenType::Slash
+                | TokenType::Percent
+                | TokenType::LT
+                | TokenType::GT
+                | TokenType::Question
+                | TokenType::Equal
+                | TokenType::NotEqual
+                | TokenType::And
+                | TokenType::Or
+                | TokenType::PlusPlus
+                | TokenType::MinusMinus
+                | TokenType::Power
+        )
+    }
+
+    pub fn is_delimiter(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Comma
+                | TokenType::Dot
+                | TokenType::Pipe
+                | TokenType::Semicolon
+                | TokenType::Colon
+                | TokenType::LParen
+                | TokenType::RParen
+                | TokenType::LBrace
+                | TokenType::RBrace
+                | TokenType::LBracket
+                | TokenType::RBracket
+        )
+    }
+
+    // true and false are both keywords and literals.
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Int
+                | TokenType::Float
+                | TokenType::String
+                | TokenType::True
+                | TokenType::False
+        )
+    }
+}
+
+// Describe the token type for diagnostics, e.g. "identifier", "`+`" or
+// "keyword `let`".
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenType::Illegal => write!(f, "illegal token"),
+            TokenType::EOF => write!(f, "end of input"),
+            TokenType::Ident => write!(f, "identifier"),
+            TokenType::Int => write!(f, "integer"),
+            TokenType::Float => write!(f, "float"),
+            TokenType::String => write!(f, "string"),
+            t if t.is_keyword() => write!(f, "keyword `{}`", t.symbol().unwrap()),
+            t => write!(f, "`{}`", t.symbol().unwrap()),
+        }
+    }
+}
+
+// Position of a token in the source, lines and columns start at 1. A
+// position of 0:0 means that the token wasn't produced by the lexer.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+// Byte offsets of a token in the source, end being excluded. So the text of
+// the token is &input[span.start..span.end].
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+// Trivia is the part of the source that doesn't produce any token: whitespace
+// and comments. Comments are always kept by the lexer while whitespace is only
+// kept by a lexer created with Lexer::with_trivia().
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
 }
 
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String, // The text as found in the source, // or /* */ included.
+    pub span: Span,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     pub token_type: TokenType,
     pub literal: String,
+    pub position: Position,
+    pub span: Span,
+    // The trivia found before the token, and after it until the end of its
+    // line.
+    pub leading_trivia: Vec<Trivia>,
+    pub trailing_trivia: Vec<Trivia>,
 }
 
 impl Token {
+    // Create a token that is not located in any source, it is useful when
+    // building tokens by hand.
+    pub fn new(token_type: TokenType, literal: &str) -> Self {
+        Token {
+            token_type,
+            literal: String::from(literal),
+            position: Position::default(),
+            span: Span::default(),
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new(),
+        }
+    }
+
+    // Return the text of the comments found between the previous token (or
+    // the end of its line) and this one, e.g. a doc comment above a let.
+    pub fn leading_comments(&self) -> impl Iterator<Item = &str> {
+        self.leading_trivia
+            .iter()
+            .filter(|t| t.kind != TriviaKind::Whitespace)
+            .map(|t| t.text.as_str())
+    }
+
     pub fn literal(&self) -> String {
         self.literal.clone()
     }
@@ -0,0 +1,72 @@
+// A TokenStream wraps any iterator of tokens, like the Lexer, and allows to
+// look ahead as many tokens as needed without consuming them.
+use super::token::{Token, TokenType};
+use std::collections::VecDeque;
+
+pub struct TokenStream<I: Iterator<Item = Token>> {
+    tokens: I,
+    // Tokens already read from the iterator but not consumed yet. It always
+    // holds at least one token so peek() doesn't need to read.
+    buffer: VecDeque<Token>,
+    // Once the iterator is exhausted we keep returning its last EOF token.
+    eof: Token,
+}
+
+impl<I: Iterator<Item = Token>> TokenStream<I> {
+    pub fn new(tokens: I) -> Self {
+        let mut stream = TokenStream {
+            tokens,
+            buffer: VecDeque::new(),
+            eof: Token::new(TokenType::EOF, "\0"),
+        };
+        stream.fill(0);
+        stream
+    }
+
+    // Consume and return the next token. After the end of the input it keeps
+    // returning EOF.
+    pub fn next_token(&mut self) -> Token {
+        let tok = self.buffer.pop_front().unwrap();
+        self.fill(0);
+        tok
+    }
+
+    // Return the next token without consuming it.
+    pub fn peek(&self) -> &Token {
+        &self.buffer[0]
+    }
+
+    // Return the token n positions after the next one without consuming
+    // anything. peek_nth(0) is the same as peek().
+    pub fn peek_nth(&mut self, n: usize) -> &Token {
+        self.fill(n);
+        &self.buffer[n]
+    }
+
+    // Give access to the underlying iterator, to get the lexer errors for
+    // example. Keep in mind that it may have been read ahead of what has been
+    // consumed.
+    pub fn inner(&self) -> &I {
+        &self.tokens
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.tokens
+    }
+
+    // Make sure that the buffer holds at least n + 1 tokens.
+    fn fill(&mut self, n: usize) {
+        while self.buffer.len() <= n {
+            let tok = match self.tokens.next() {
+                Some(tok) => {
+                    if tok.token_type == TokenType::EOF {
+                        self.eof = tok.clone();
+                    }
+                    tok
+                }
+                None => self.eof.clone(),
+            };
+            self.buffer.push_back(tok);
+        }
+    }
+}
@@ -1,4 +1,8 @@
 // lib.rs is our crate root.
-// looking into interpreter/mod.rs and repl/mod.rs
+// looking into compiler/mod.rs, engine/mod.rs, interpreter/mod.rs, repl/mod.rs
+// and vm/mod.rs
+pub mod compiler;
+pub mod engine;
 pub mod interpreter;
 pub mod repl;
+pub mod vm;
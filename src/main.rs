@@ -1,11 +1,140 @@
 extern crate redhowler;
 
+use redhowler::engine::Engine;
+use redhowler::interpreter::ast::{self, Program};
+use redhowler::interpreter::object::Object;
+use redhowler::interpreter::{
+    lexer::Lexer,
+    parser::{Parser, ParserWarning},
+};
 use redhowler::repl;
+use std::io::{IsTerminal, Read};
 
 fn main() {
+    // With --tokens-json we only lex the standard input and dump the tokens.
+    if has_flag("--tokens-json") {
+        dump_tokens_json();
+        return;
+    }
+
+    // With --ast or --ast-json we parse the standard input and dump the
+    // program. --fold-constants and --eliminate-dead-code run these passes on
+    // it first.
+    if has_flag("--ast-json") {
+        dump_ast_json();
+        return;
+    }
+    if has_flag("--ast") {
+        print!("{}", parse_stdin().pretty(4));
+        return;
+    }
+
+    // With --engine eval or --engine vm we run the standard input with this
+    // backend and print its value, unless it is null.
+    if let Some(name) = flag_value("--engine") {
+        let engine: Engine = name.parse().unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+        run_stdin(engine);
+        return;
+    }
+
     println!("Welcome to Monkey Islang!!!");
     println!("This is the REPL for Monkey programming language.");
     println!("Feel free to type some code or 'q;'");
 
-    repl::start();
+    // The colors are left out with --no-color, when the NO_COLOR variable is
+    // set, or when the output is not a terminal.
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let color = !has_flag("--no-color") && !no_color && std::io::stdout().is_terminal();
+    repl::start_interactive(color);
+}
+
+fn has_flag(flag: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == flag)
+}
+
+// The argument following the flag, if the flag is given.
+fn flag_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    args.find(|arg| arg == flag)?;
+    Some(args.next().unwrap_or_else(|| {
+        eprintln!("{} requires a value", flag);
+        std::process::exit(1);
+    }))
+}
+
+fn run_stdin(engine: Engine) {
+    let program = parse_stdin();
+    match engine.run_program(&program) {
+        Ok(Object::Null) => {}
+        Ok(value) => println!("{}", value),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Parse the standard input and run the passes asked on the command line. The
+// warnings and errors are printed on the standard error and we exit if there
+// are errors.
+fn parse_stdin() -> Program {
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .expect("Failed to read input");
+
+    let mut parser = Parser::new(Lexer::new(&input));
+    let mut program = parser.parse_program();
+    print_warnings(&parser.warnings);
+    if !parser.errors.is_empty() {
+        parser.errors.iter().for_each(|e| eprintln!("{}", e));
+        std::process::exit(1);
+    }
+
+    if has_flag("--fold-constants") {
+        program = ast::fold_constants(program);
+    }
+    if has_flag("--eliminate-dead-code") {
+        print_warnings(&ast::eliminate_dead_code(&mut program));
+    }
+
+    program
+}
+
+fn print_warnings(warnings: &[ParserWarning]) {
+    warnings.iter().for_each(|w| eprintln!("warning: {}", w));
+}
+
+#[cfg(feature = "serde")]
+fn dump_tokens_json() {
+    use redhowler::interpreter::token::Token;
+
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .expect("Failed to read input");
+
+    let tokens: Vec<Token> = Lexer::new(&input).collect();
+    println!("{}", serde_json::to_string_pretty(&tokens).unwrap());
+}
+
+#[cfg(not(feature = "serde"))]
+fn dump_tokens_json() {
+    eprintln!("--tokens-json requires redhowler to be built with the serde feature");
+    std::process::exit(1);
+}
+
+#[cfg(feature = "serde")]
+fn dump_ast_json() {
+    let program = parse_stdin();
+    println!("{}", serde_json::to_string_pretty(&program).unwrap());
+}
+
+#[cfg(not(feature = "serde"))]
+fn dump_ast_json() {
+    eprintln!("--ast-json requires redhowler to be built with the serde feature");
+    std::process::exit(1);
 }
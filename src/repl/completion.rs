@@ -0,0 +1,32 @@
+// Completion of the word before the cursor, when Tab is pressed in the REPL.
+use crate::interpreter::builtins;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::lexer;
+use crate::interpreter::token::KEYWORDS;
+
+// Gives where the word ending at pos starts in the line, and the keywords,
+// builtins and names bound in env it can be completed to, sorted. There is
+// nothing to complete when pos is not right after an identifier.
+pub fn complete(line: &str, pos: usize, env: &Environment) -> (usize, Vec<String>) {
+    let start = line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| lexer::is_letter(*c))
+        .last()
+        .map_or(pos, |(i, _)| i);
+    let word = &line[start..pos];
+    if word.is_empty() {
+        return (pos, Vec::new());
+    }
+
+    let mut candidates: Vec<String> = KEYWORDS
+        .iter()
+        .map(|(keyword, _)| keyword.to_string())
+        .chain(builtins::all().map(|(name, _)| name.to_string()))
+        .chain(env.names())
+        .filter(|candidate| candidate.starts_with(word))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
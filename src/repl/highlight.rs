@@ -0,0 +1,55 @@
+// ANSI colors for the REPL: the input as it is typed and the values echoed.
+// The text is split in tokens by the lexer, so what is colored as a keyword
+// is always what the parser takes for one.
+use crate::interpreter::lexer::Lexer;
+use crate::interpreter::token::TokenType;
+
+const RESET: &str = "\x1b[0m";
+const KEYWORD: &str = "\x1b[35m"; // Magenta.
+const NUMBER: &str = "\x1b[33m"; // Yellow.
+const STRING: &str = "\x1b[32m"; // Green.
+const ERROR: &str = "\x1b[31m"; // Red.
+
+// The source with its keywords, numbers, strings and illegal tokens colored.
+// The rest, whitespace and comments included, is kept as it is.
+pub fn highlight(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut end = 0;
+    for tok in Lexer::new(source) {
+        if tok.span.start < end {
+            continue;
+        }
+        out.push_str(&source[end..tok.span.start]);
+        let text = &source[tok.span.start..tok.span.end];
+        match color(&tok.token_type) {
+            Some(color) => paint(&mut out, color, text),
+            None => out.push_str(text),
+        }
+        end = tok.span.end;
+    }
+    out.push_str(&source[end..]);
+    out
+}
+
+// The text colored as an error.
+pub fn error(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + ERROR.len() + RESET.len());
+    paint(&mut out, ERROR, text);
+    out
+}
+
+fn color(token_type: &TokenType) -> Option<&'static str> {
+    match token_type {
+        t if t.is_keyword() => Some(KEYWORD),
+        TokenType::Int | TokenType::Float => Some(NUMBER),
+        TokenType::String => Some(STRING),
+        TokenType::Illegal => Some(ERROR),
+        _ => None,
+    }
+}
+
+fn paint(out: &mut String, color: &str, text: &str) {
+    out.push_str(color);
+    out.push_str(text);
+    out.push_str(RESET);
+}
@@ -0,0 +1,70 @@
+// The lines typed in the REPL, kept in a file from a session to the next.
+// The file has one line per entry, the oldest first.
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// The entries kept, the older ones are dropped.
+pub const MAX_ENTRIES: usize = 1000;
+
+// Where the history is kept: $XDG_STATE_HOME/redhowler/history when the
+// variable is set, ~/.redhowler_history otherwise. None without a home.
+pub fn path() -> Option<PathBuf> {
+    path_in(std::env::var_os("XDG_STATE_HOME"), std::env::var_os("HOME"))
+}
+
+// Same as path() with the values of the variables given. As the XDG
+// specification says, a relative XDG_STATE_HOME is ignored.
+pub fn path_in(state_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    match state_home.map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir.join("redhowler").join("history")),
+        _ => Some(PathBuf::from(home.filter(|home| !home.is_empty())?).join(".redhowler_history")),
+    }
+}
+
+// The last entries of the file. A missing file is an empty history.
+pub fn load(path: &Path) -> io::Result<Vec<String>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut entries: Vec<String> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(String::from)
+        .collect();
+    truncate(&mut entries);
+    Ok(entries)
+}
+
+// Writes the last entries to the file, creating its directory if needed.
+pub fn save(path: &Path, entries: &[String]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let start = entries.len().saturating_sub(MAX_ENTRIES);
+    let mut text = String::new();
+    for entry in &entries[start..] {
+        text.push_str(entry);
+        text.push('\n');
+    }
+    fs::write(path, text)
+}
+
+// Adds the line to the entries unless it is blank or the same as the last
+// one, and drops the oldest entries beyond MAX_ENTRIES.
+pub fn add(entries: &mut Vec<String>, line: &str) {
+    if line.trim().is_empty() || entries.last().is_some_and(|last| last == line) {
+        return;
+    }
+    entries.push(line.to_string());
+    truncate(entries);
+}
+
+fn truncate(entries: &mut Vec<String>) {
+    if entries.len() > MAX_ENTRIES {
+        entries.drain(..entries.len() - MAX_ENTRIES);
+    }
+}
@@ -1,32 +1,454 @@
-use crate::interpreter::{lexer, token};
-use std::io::{self, Write};
+// looking for src/repl/completion.rs
+// looking for src/repl/highlight.rs
+// looking for src/repl/history.rs
+pub mod completion;
+pub mod highlight;
+pub mod history;
 
-pub fn start() {
+use crate::interpreter::ast::Program;
+use crate::interpreter::environment::{Env, Environment};
+use crate::interpreter::evaluator::Evaluator;
+use crate::interpreter::lexer::Lexer;
+use crate::interpreter::object::Object;
+use crate::interpreter::parser::{Parser, ParserError};
+use crate::interpreter::token::TokenType;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Config, Context, Editor, Helper};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+const PROMPT: &str = ">> ";
+
+// Runs a REPL session reading the lines from input and writing the prompts,
+// the results and the output of puts to output, without colors. It stops at
+// the end of input or at "q;". Each input is evaluated in the same
+// environment, so the names bound by an input can be used by the next ones.
+pub fn start(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    // puts writes to a buffer copied to output after each evaluation, as the
+    // evaluator needs an output it can keep.
+    let puts_output = Rc::new(RefCell::new(Vec::new()));
+    let mut session = Session::new(Evaluator::with_output(puts_output.clone()), false);
+    session.puts_output = Some(puts_output);
+
+    let mut lines = input.lines();
     loop {
-        let mut input = String::new();
-        io::stdout().write_all(b">> ").unwrap();
-        io::stdout().flush().unwrap();
-        io::stdin()
-            .read_line(&mut input)
-            .expect("Failed to read line");
+        output.write_all(PROMPT.as_bytes())?;
+        output.flush()?;
+        let Some(line) = lines.next() else {
+            // End of input.
+            return writeln!(output);
+        };
+        if !session.run(&line?, &mut output)? {
+            return Ok(());
+        }
+    }
+}
+
+// The REPL of the terminal. The lines are read with a line editor, Tab
+// completes the names. With color the input and the values echoed are
+// highlighted. The history is loaded from its file at startup and saved
+// back when the session ends. Ctrl+C stops the evaluation in progress.
+pub fn start_interactive(color: bool) {
+    let mut session = Session::new(Evaluator::new(), color);
+    let config = Config::builder()
+        .max_history_size(history::MAX_ENTRIES)
+        .expect("Invalid history size")
+        .build();
+    let mut editor: Editor<ReplHelper, DefaultHistory> =
+        Editor::with_config(config).expect("Failed to create the line editor");
+    editor.set_helper(Some(ReplHelper {
+        env: Rc::clone(&session.env),
+        color,
+    }));
 
+    // The line editor handles SIGINT too, ours is installed after it so it
+    // replaces it. While a line is read the terminal is in raw mode, Ctrl+C
+    // is a key for the line editor and not a signal, so the handler is only
+    // called during evaluations.
+    let interrupted = Arc::clone(&session.interrupted);
+    if let Err(err) = ctrlc::set_handler(move || interrupted.store(true, Ordering::Relaxed)) {
+        eprintln!("could not handle Ctrl+C: {}", err);
+    }
+
+    let history_path = history::path();
+    if let Some(path) = &history_path {
+        match history::load(path) {
+            Ok(entries) => session.history = entries,
+            Err(err) => eprintln!(
+                "could not load the history from {}: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+    for entry in session.history.iter() {
+        let _ = editor.add_history_entry(entry.as_str());
+    }
+
+    loop {
+        let input = match editor.readline(PROMPT) {
+            Ok(input) => input,
+            // Ctrl+C drops the line being typed.
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => {
+                println!();
+                break;
+            }
+            Err(err) => panic!("Failed to read line: {}", err),
+        };
+        if !input.trim().is_empty() {
+            let _ = editor.add_history_entry(input.as_str());
+        }
+
+        let go_on = session
+            .run(&input, &mut io::stdout())
+            .expect("Failed to write output");
+        if !go_on {
+            break;
+        }
+    }
+
+    if let Some(path) = &history_path {
+        if let Err(err) = history::save(path, &session.history) {
+            eprintln!("could not save the history to {}: {}", path.display(), err);
+        }
+    }
+}
+
+// What the REPL shows for an input: its tokens, the program parsed, or its
+// value. It is switched with the :mode command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Tokens,
+    Ast,
+    Eval,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Tokens => write!(f, "tokens"),
+            Mode::Ast => write!(f, "ast"),
+            Mode::Eval => write!(f, "eval"),
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tokens" => Ok(Mode::Tokens),
+            "ast" => Ok(Mode::Ast),
+            "eval" => Ok(Mode::Eval),
+            _ => Err(format!(
+                "unknown mode: {} (expected tokens, ast or eval)",
+                s
+            )),
+        }
+    }
+}
+
+// What is kept from an input to the next.
+struct Session {
+    env: Env,
+    evaluator: Evaluator,
+    // Where puts writes when it is not the standard output. It is copied to
+    // the output of the session after each evaluation.
+    puts_output: Option<Rc<RefCell<Vec<u8>>>>,
+    // The lines entered, the oldest first. Commands are in it too.
+    history: Vec<String>,
+    // Set to stop the evaluation in progress.
+    interrupted: Arc<AtomicBool>,
+    mode: Mode,
+    color: bool,
+}
+
+impl Session {
+    fn new(mut evaluator: Evaluator, color: bool) -> Self {
+        let interrupted = Arc::new(AtomicBool::new(false));
+        evaluator.set_interrupt_flag(Arc::clone(&interrupted));
+        Session {
+            env: Rc::new(RefCell::new(Environment::new())),
+            evaluator,
+            interrupted,
+            puts_output: None,
+            history: Vec::new(),
+            mode: Mode::Eval,
+            color,
+        }
+    }
+
+    // Runs a line of input, a command when it starts with ':'. Returns false
+    // when the input ends the session.
+    fn run(&mut self, input: &str, out: &mut dyn Write) -> io::Result<bool> {
+        history::add(&mut self.history, input);
         if input.trim() == "q;" {
-            io::stdout()
-                .write_all(
-                    b"May your trip be as enjoyable as finding \
+            out.write_all(
+                b"May your trip be as enjoyable as finding \
                 extra bananas at the bottom of the bag!",
-                )
-                .unwrap();
-            break;
+            )?;
+            return Ok(false);
+        }
+        if let Some(command) = input.trim().strip_prefix(':') {
+            self.run_command(command, out)?;
+            return Ok(true);
+        }
+        if self.mode == Mode::Tokens {
+            self.print_tokens(input, out)?;
+            return Ok(true);
+        }
+
+        let Some(program) = self.parse(input, out)? else {
+            return Ok(true);
+        };
+        if self.mode == Mode::Ast {
+            writeln!(out, "{}", self.show_source(&program.to_string()))?;
+            return Ok(true);
         }
 
-        let mut l = lexer::Lexer::new(&input);
-        loop {
-            let tok = l.next_token();
-            if tok.token_type == token::TokenType::EOF {
+        let value = self.eval(&program);
+        self.print_value(value, out)?;
+        Ok(true)
+    }
+
+    // An interruption that came between two evaluations is dropped.
+    fn eval(&mut self, program: &Program) -> Object {
+        self.interrupted.store(false, Ordering::Relaxed);
+        self.evaluator.eval(program, &self.env)
+    }
+
+    // The program parsed from the input, after printing the warnings. The
+    // errors are printed when there are some, and there is no program.
+    fn parse(&self, input: &str, out: &mut dyn Write) -> io::Result<Option<Program>> {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        if !parser.errors.is_empty() {
+            self.print_parser_errors(input, &parser.errors, out)?;
+            return Ok(None);
+        }
+        for warning in parser.warnings.iter() {
+            writeln!(out, "warning: {}", warning)?;
+        }
+        Ok(Some(program))
+    }
+
+    // Prints what the evaluation wrote, then the value.
+    fn print_value(&mut self, value: Object, out: &mut dyn Write) -> io::Result<()> {
+        if let Some(puts_output) = &self.puts_output {
+            out.write_all(&std::mem::take(&mut *puts_output.borrow_mut()))?;
+        }
+        // Statements like let have no value to show.
+        if value != Object::Null {
+            writeln!(out, "{}", self.show(&value))?;
+        }
+        // The value is dropped, what is still needed is bound in env.
+        drop(value);
+        self.evaluator.collect_garbage(&[&self.env]);
+        Ok(())
+    }
+
+    // Evaluates the code, whatever the mode, and prints how long it took
+    // and how many steps the evaluator made.
+    fn time(&mut self, code: &str, out: &mut dyn Write) -> io::Result<()> {
+        if code.is_empty() {
+            return self.print_error("usage: :time <code>", out);
+        }
+        let Some(program) = self.parse(code, out)? else {
+            return Ok(());
+        };
+
+        let steps = self.evaluator.steps();
+        let start = Instant::now();
+        let value = self.eval(&program);
+        let elapsed = start.elapsed();
+        let steps = self.evaluator.steps() - steps;
+
+        self.print_value(value, out)?;
+        writeln!(out, "time: {:?}, {} steps", elapsed, steps)
+    }
+
+    // :mode shows the current mode, :mode <name> switches to another one.
+    // :history lists the entries of the history, :history <count> only the
+    // last ones. :time <code> evaluates the code and tells how long it took.
+    fn run_command(&mut self, command: &str, out: &mut dyn Write) -> io::Result<()> {
+        if let ("time", code) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""))
+        {
+            return self.time(code.trim(), out);
+        }
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words.as_slice() {
+            ["history"] => self.print_history(self.history.len(), out),
+            ["history", count] => match count.parse() {
+                Ok(count) => self.print_history(count, out),
+                Err(_) => self.print_error("usage: :history [count]", out),
+            },
+            ["mode"] => writeln!(out, "{}", self.mode),
+            ["mode", name] => match name.parse() {
+                Ok(mode) => {
+                    self.mode = mode;
+                    Ok(())
+                }
+                Err(err) => self.print_error(&err, out),
+            },
+            _ => self.print_error(&format!("unknown command: :{}", command), out),
+        }
+    }
+
+    // The last count entries, with their number in the history.
+    fn print_history(&self, count: usize, out: &mut dyn Write) -> io::Result<()> {
+        let start = self.history.len().saturating_sub(count);
+        for (i, entry) in self.history.iter().enumerate().skip(start) {
+            writeln!(out, "{:>5}  {}", i + 1, entry)?;
+        }
+        Ok(())
+    }
+
+    // One token per line, with where it starts, then the lexer errors.
+    fn print_tokens(&self, input: &str, out: &mut dyn Write) -> io::Result<()> {
+        let mut lexer = Lexer::new(input);
+        for tok in lexer.by_ref() {
+            if tok.token_type == TokenType::EOF {
                 break;
             }
-            println!("{:?}", tok);
+            writeln!(
+                out,
+                "{}:{} {:?} {:?}",
+                tok.position.line, tok.position.column, tok.token_type, tok.literal
+            )?;
+        }
+        for err in lexer.errors.iter() {
+            self.print_error(&format!("Error: {}", err), out)?;
+        }
+        Ok(())
+    }
+
+    fn print_parser_errors(
+        &self,
+        input: &str,
+        errors: &[ParserError],
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let mut text = String::from("Woops! We ran into some monkey business here!\n");
+        for err in errors.iter() {
+            text.push_str(&format_parser_error(input, err));
+        }
+        if self.color {
+            text = highlight::error(&text);
+        }
+        write!(out, "{}", text)
+    }
+
+    fn print_error(&self, message: &str, out: &mut dyn Write) -> io::Result<()> {
+        if self.color {
+            writeln!(out, "{}", highlight::error(message))
+        } else {
+            writeln!(out, "{}", message)
         }
     }
+
+    // The value as the REPL echoes it.
+    fn show(&self, value: &Object) -> String {
+        let text = value.inspect();
+        match value {
+            _ if !self.color => text,
+            Object::Error(_) => highlight::error(&text),
+            _ => highlight::highlight(&text),
+        }
+    }
+
+    fn show_source(&self, source: &str) -> String {
+        if self.color {
+            highlight::highlight(source)
+        } else {
+            source.to_string()
+        }
+    }
+}
+
+// Shows the line of the input where the error is, with the token found there
+// underlined, and the message underneath:
+//
+//     let = 5;
+//         ^
+//     line 1, column 5: Expected next token to be identifier, got `=` instead
+pub fn format_parser_error(input: &str, err: &ParserError) -> String {
+    let position = err.position();
+    let Some(line) = input.lines().nth(position.line.wrapping_sub(1)) else {
+        return format!("{}\n", err);
+    };
+
+    // The token is lexed again to know how long it is. It may go on past the
+    // end of the line, like a string, or be empty, like the end of input.
+    let before = position.column.saturating_sub(1);
+    let width = Lexer::new(input)
+        .find(|tok| tok.position == position)
+        .map_or(1, |tok| input[tok.span.start..tok.span.end].chars().count())
+        .min(line.chars().count().saturating_sub(before))
+        .max(1);
+
+    // Tabs are kept so the caret lines up with the same columns.
+    let indent: String = line
+        .chars()
+        .take(before)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!("{}\n{}^{}\n{}\n", line, indent, "~".repeat(width - 1), err)
+}
+
+// What the line editor needs from the REPL.
+struct ReplHelper {
+    env: Env,
+    color: bool,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(completion::complete(line, pos, &self.env.borrow()))
+    }
 }
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if self.color {
+            Cow::Owned(highlight::highlight(line))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    // Any char typed can change the tokens, like a quote.
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        self.color
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
@@ -0,0 +1,373 @@
+// The VM runs the bytecode made by the compiler, on a stack of values with a
+// frame for each function call.
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::compiler::code::{self, Instructions, Opcode};
+use crate::compiler::symbol_table::{Symbol, SymbolScope};
+use crate::compiler::Bytecode;
+use crate::interpreter::builtins;
+use crate::interpreter::evaluator::{self, Evaluator};
+use crate::interpreter::object::{Closure, CompiledFunction, HashKey, Object};
+
+// Calls nested deeper than this stop with an error, as a function recursing
+// forever would make the stack grow until the memory runs out. The frames
+// are not on the Rust stack, so it is higher than the limit of the
+// evaluator.
+pub const MAX_FRAMES: usize = 1024;
+
+// A function call being run.
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize, // The position of the next instruction to run.
+    // Where the locals of the call start on the stack. The closure called is
+    // just below them.
+    base_pointer: usize,
+    // The cells of the locals captured by a closure, by index. Such a local
+    // is read and written through its cell from then on.
+    cells: Vec<Option<Rc<RefCell<Object>>>>,
+}
+
+pub struct Vm {
+    machine: Machine,
+    // The builtins are called with it, for its output and its settings.
+    evaluator: Evaluator,
+}
+
+impl Vm {
+    pub fn new(bytecode: Bytecode) -> Self {
+        Vm::with_evaluator(bytecode, Evaluator::new())
+    }
+
+    // The builtins of the evaluator replace the standard ones with the same
+    // name, they write to its output.
+    pub fn with_evaluator(bytecode: Bytecode, evaluator: Evaluator) -> Self {
+        let builtins = builtins::all()
+            .map(|(name, _)| Object::Builtin(evaluator.builtin(name).unwrap().clone()))
+            .collect();
+        let mut machine = Machine {
+            constants: bytecode.constants.into(),
+            globals: Rc::default(),
+            builtins: Rc::new(builtins),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            last_popped: Object::Null,
+        };
+        // The program is run as the body of a function without parameters.
+        let main = Rc::new(Closure {
+            function: Rc::new(CompiledFunction {
+                instructions: bytecode.instructions,
+                num_locals: 0,
+                parameters: Vec::new(),
+                free: Vec::new(),
+                call_positions: bytecode.call_positions,
+            }),
+            free: Vec::new(),
+        });
+        machine.stack.push(Object::Closure(Rc::clone(&main)));
+        machine.frames.push(Frame {
+            closure: main,
+            ip: 0,
+            base_pointer: 1,
+            cells: Vec::new(),
+        });
+        Vm { machine, evaluator }
+    }
+
+    // Runs the program. Its value is the one of the last expression
+    // statement, of a return statement, or the first error met.
+    pub fn run(&mut self) -> Object {
+        let machine = &self.machine;
+        let (constants, globals, builtins) = (
+            Rc::clone(&machine.constants),
+            Rc::clone(&machine.globals),
+            Rc::clone(&machine.builtins),
+        );
+        // The builtins given a closure, like map, run it on a machine of
+        // their own sharing the globals.
+        self.evaluator
+            .set_closure_caller(Rc::new(move |eval, closure, args| {
+                let mut machine = Machine {
+                    constants: Rc::clone(&constants),
+                    globals: Rc::clone(&globals),
+                    builtins: Rc::clone(&builtins),
+                    stack: Vec::new(),
+                    frames: Vec::new(),
+                    last_popped: Object::Null,
+                };
+                let argc = args.len();
+                machine.stack.push(Object::Closure(closure));
+                machine.stack.extend(args);
+                match machine.call(argc).and_then(|()| machine.run(eval)) {
+                    Ok(value) => value,
+                    Err(message) => Object::Error(message),
+                }
+            }));
+
+        match self.machine.run(&mut self.evaluator) {
+            Ok(value) => value,
+            Err(message) => Object::Error(message),
+        }
+    }
+}
+
+struct Machine {
+    constants: Rc<[Object]>,
+    globals: Rc<RefCell<Vec<Object>>>,
+    builtins: Rc<Vec<Object>>,
+    stack: Vec<Object>,
+    frames: Vec<Frame>,
+    // The value of the last expression statement.
+    last_popped: Object,
+}
+
+impl Machine {
+    // Runs the instructions until the first frame is done, and gives the
+    // value it returned. The first frame of the program isn't a function
+    // call, its value is the last one popped.
+    fn run(&mut self, eval: &mut Evaluator) -> Result<Object, String> {
+        loop {
+            let frame = self.frames.last_mut().expect("no frame to run");
+            let instructions: &Instructions = &frame.closure.function.instructions;
+            let Some(&byte) = instructions.0.get(frame.ip) else {
+                // Only the program ends without a return.
+                self.frames.pop();
+                return Ok(std::mem::replace(&mut self.last_popped, Object::Null));
+            };
+            let op = Opcode::try_from(byte).map_err(|byte| format!("unknown opcode {}", byte))?;
+            let ip = frame.ip;
+            let mut operands = [0; 2];
+            let mut offset = ip + 1;
+            for (operand, &width) in operands.iter_mut().zip(op.operand_widths()) {
+                *operand = code::read_operand(&instructions.0[offset..], width);
+                offset += width;
+            }
+            frame.ip = offset;
+
+            match op {
+                Opcode::Constant => self.push(self.constants[operands[0]].clone()),
+                Opcode::Pop => self.last_popped = self.pop(),
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Mod
+                | Opcode::Pow
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::GreaterThan
+                | Opcode::LessThan => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = evaluator::apply_infix(infix_operator(op), left, right);
+                    self.push_result(result)?;
+                }
+                Opcode::Minus | Opcode::Bang => {
+                    let right = self.pop();
+                    let op = if op == Opcode::Minus { "-" } else { "!" };
+                    self.push_result(evaluator::apply_prefix(op, right))?;
+                }
+                Opcode::True => self.push(Object::Boolean(true)),
+                Opcode::False => self.push(Object::Boolean(false)),
+                Opcode::Null => self.push(Object::Null),
+                Opcode::GetGlobal => {
+                    let value = self.globals.borrow().get(operands[0]).cloned();
+                    self.push(value.unwrap_or(Object::Null));
+                }
+                Opcode::SetGlobal => {
+                    let value = self.pop();
+                    let mut globals = self.globals.borrow_mut();
+                    if globals.len() <= operands[0] {
+                        globals.resize(operands[0] + 1, Object::Null);
+                    }
+                    globals[operands[0]] = value;
+                }
+                Opcode::GetLocal => {
+                    let frame = self.frame();
+                    let value = match frame.cells.get(operands[0]) {
+                        Some(Some(cell)) => cell.borrow().clone(),
+                        _ => self.stack[frame.base_pointer + operands[0]].clone(),
+                    };
+                    self.push(value);
+                }
+                Opcode::SetLocal => {
+                    let value = self.pop();
+                    let frame = self.frames.last().expect("no frame");
+                    match frame.cells.get(operands[0]) {
+                        Some(Some(cell)) => *cell.borrow_mut() = value,
+                        _ => self.stack[frame.base_pointer + operands[0]] = value,
+                    }
+                }
+                Opcode::GetBuiltin => self.push(self.builtins[operands[0]].clone()),
+                Opcode::GetFree => {
+                    let value = self.frame().closure.free[operands[0]].borrow().clone();
+                    self.push(value);
+                }
+                Opcode::CurrentClosure => {
+                    let closure = Rc::clone(&self.frame().closure);
+                    self.push(Object::Closure(closure));
+                }
+                Opcode::Closure => {
+                    let Object::CompiledFunction(function) = &self.constants[operands[0]] else {
+                        return Err(format!("not a function: constant {}", operands[0]));
+                    };
+                    let function = Rc::clone(function);
+                    let free = function
+                        .free
+                        .iter()
+                        .map(|symbol| self.capture(symbol))
+                        .collect();
+                    self.push(Object::Closure(Rc::new(Closure { function, free })));
+                }
+                Opcode::Array => {
+                    let elements = self.stack.split_off(self.stack.len() - operands[0]);
+                    self.push(Object::Array(elements));
+                }
+                Opcode::Hash => {
+                    let values = self.stack.split_off(self.stack.len() - operands[0]);
+                    let mut pairs = BTreeMap::new();
+                    let mut values = values.into_iter();
+                    while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                        let Some(key) = HashKey::from_object(&key) else {
+                            return Err(format!("unusable as hash key: {}", key.type_name()));
+                        };
+                        pairs.insert(key, value);
+                    }
+                    self.push(Object::Hash(pairs));
+                }
+                Opcode::Index => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    self.push_result(evaluator::apply_index(left, index))?;
+                }
+                Opcode::JumpNotTruthy => {
+                    if !self.pop().is_truthy() {
+                        self.frame_mut().ip = operands[0];
+                    }
+                }
+                Opcode::Jump => self.frame_mut().ip = operands[0],
+                Opcode::Call => {
+                    let argc = operands[0];
+                    if let Object::Builtin(builtin) = &self.stack[self.stack.len() - 1 - argc] {
+                        let func = Rc::clone(&builtin.func);
+                        eval.set_call_position(self.frame().closure.function.call_position(ip));
+                        let args = self.stack.split_off(self.stack.len() - argc);
+                        self.pop();
+                        self.push_result(func(eval, &args))?;
+                    } else {
+                        self.call(argc)?;
+                    }
+                }
+                Opcode::ReturnValue | Opcode::Return => {
+                    let value = match op {
+                        Opcode::ReturnValue => self.pop(),
+                        _ => Object::Null,
+                    };
+                    let frame = self.frames.pop().expect("no frame to return from");
+                    self.stack.truncate(frame.base_pointer - 1);
+                    if self.frames.is_empty() {
+                        return Ok(value);
+                    }
+                    self.push(value);
+                }
+            }
+        }
+    }
+
+    // Starts running the closure below the argc arguments on the stack. The
+    // locals other than the parameters start as null.
+    fn call(&mut self, argc: usize) -> Result<(), String> {
+        let callee = &self.stack[self.stack.len() - 1 - argc];
+        let Object::Closure(closure) = callee else {
+            return Err(format!("not a function: {}", callee.type_name()));
+        };
+        let closure = Rc::clone(closure);
+        let function = &closure.function;
+        if argc != function.parameters.len() {
+            return Err(format!(
+                "wrong number of arguments: want={}, got={}",
+                function.parameters.len(),
+                argc
+            ));
+        }
+        if self.frames.len() >= MAX_FRAMES {
+            return Err("maximum recursion depth exceeded".to_string());
+        }
+
+        let base_pointer = self.stack.len() - argc;
+        self.stack
+            .resize(base_pointer + function.num_locals, Object::Null);
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base_pointer,
+            cells: Vec::new(),
+        });
+        Ok(())
+    }
+
+    // The cell of a variable of the frame, for a closure to share it. A local
+    // gets one the first time it is captured.
+    fn capture(&mut self, symbol: &Symbol) -> Rc<RefCell<Object>> {
+        let frame = self.frames.last_mut().expect("no frame");
+        match symbol.scope {
+            SymbolScope::Free => Rc::clone(&frame.closure.free[symbol.index]),
+            SymbolScope::Function => {
+                Rc::new(RefCell::new(Object::Closure(Rc::clone(&frame.closure))))
+            }
+            _ => {
+                if frame.cells.len() <= symbol.index {
+                    frame.cells.resize(symbol.index + 1, None);
+                }
+                let value = &self.stack[frame.base_pointer + symbol.index];
+                let cell = frame.cells[symbol.index]
+                    .get_or_insert_with(|| Rc::new(RefCell::new(value.clone())));
+                Rc::clone(cell)
+            }
+        }
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("no frame")
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("no frame")
+    }
+
+    fn push(&mut self, value: Object) {
+        self.stack.push(value);
+    }
+
+    // Errors stop the program.
+    fn push_result(&mut self, value: Object) -> Result<(), String> {
+        match value {
+            Object::Error(message) => Err(message),
+            value => {
+                self.push(value);
+                Ok(())
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Object {
+        self.stack.pop().expect("stack underflow")
+    }
+}
+
+// The operator applied by the opcode, as written in the source.
+fn infix_operator(op: Opcode) -> &'static str {
+    match op {
+        Opcode::Add => "+",
+        Opcode::Sub => "-",
+        Opcode::Mul => "*",
+        Opcode::Div => "/",
+        Opcode::Mod => "%",
+        Opcode::Pow => "**",
+        Opcode::Equal => "==",
+        Opcode::NotEqual => "!=",
+        Opcode::GreaterThan => ">",
+        _ => "<",
+    }
+}
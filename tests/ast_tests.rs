@@ -1,32 +1,459 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use redhowler::interpreter::ast;
-use redhowler::interpreter::ast::{Identifier, LetStatementBuilder, Program};
-use redhowler::interpreter::token::{Token, TokenType};
+use redhowler::interpreter::ast::{
+    Expression, Identifier, InfixExpression, InfixExpressionBuilder, IntegerLiteral,
+    LetStatementBuilder, PrefixExpressionBuilder, Program, Statement, Transform, Visitor,
+    VisitorMut,
+};
+use redhowler::interpreter::environment::Environment;
+use redhowler::interpreter::evaluator::Evaluator;
+use redhowler::interpreter::lexer::Lexer;
+use redhowler::interpreter::parser::{Parser, ParserWarning};
+use redhowler::interpreter::token::{Position, Token, TokenType};
 
 #[test]
 fn test_let_statement() {
     let mut p = Program::default();
 
     // Build LetStatement
-    let mut builder = LetStatementBuilder::new(&Token {
-        token_type: TokenType::Let,
-        literal: "let".to_string(),
-    });
+    let builder = LetStatementBuilder::new(&Token::new(TokenType::Let, "let"));
 
     // Add name
-    builder.name(Identifier::new(&Token {
-        token_type: TokenType::Ident,
-        literal: "myVar".to_string(),
-    }));
+    let builder = builder.name(Identifier::new(&Token::new(TokenType::Ident, "myVar")));
 
     // Add value
-    let id_token = Token {
-        token_type: TokenType::Ident,
-        literal: "anotherVar".to_string(),
-    };
+    let id_token = Token::new(TokenType::Ident, "anotherVar");
     let id = ast::Identifier::new(&id_token);
-    builder.value(Some(Box::new(id)));
+    let builder = builder.value(Some(Expression::Identifier(id)));
     let stmt = builder.build();
-    p.push(Box::new(stmt));
+    p.push(Statement::Let(stmt));
+
+    assert_eq!(p.to_string(), "let myVar = anotherVar;");
+}
+
+fn parse(input: &str) -> Program {
+    let mut parser = Parser::new(Lexer::new(input));
+    let program = parser.parse_program();
+    assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+    program
+}
+
+#[derive(Default)]
+struct IdentifierCollector {
+    names: Vec<String>,
+}
+
+impl Visitor for IdentifierCollector {
+    fn visit_identifier(&mut self, ident: &Identifier) {
+        self.names.push(ident.value().to_string());
+    }
+}
+
+#[test]
+fn test_visitor_walks_nested_nodes() {
+    let program = parse("let a = b + c * d; if (e) { f(g[h]); } else { return i; }");
+
+    let mut collector = IdentifierCollector::default();
+    collector.visit_program(&program);
+
+    assert_eq!(
+        collector.names,
+        vec!["b", "c", "d", "e", "f", "g", "h", "i"]
+    );
+}
+
+struct PlusToMinus;
+
+impl VisitorMut for PlusToMinus {
+    fn visit_infix_mut(&mut self, expr: &mut InfixExpression) {
+        if expr.operator == "+" {
+            expr.operator = "-".to_string();
+        }
+        ast::walk_infix_mut(self, expr);
+    }
+}
+
+#[test]
+fn test_visitor_mut_rewrites_nodes() {
+    let mut program = parse("let x = fn(a) { a + 1 + 2 };");
+
+    PlusToMinus.visit_program_mut(&mut program);
+
+    assert_eq!(program.to_string(), "let x = fn(a) ((a - 1) - 2);");
+}
+
+#[test]
+fn test_parsed_program_equals_built_one() {
+    let program = parse("let x  =  1 + y;");
+
+    let one = IntegerLiteral::new(&Token::new(TokenType::Int, "1"), 1);
+    let y = Identifier::new(&Token::new(TokenType::Ident, "y"));
+    let infix = InfixExpressionBuilder::new(&Token::new(TokenType::Plus, "+"))
+        .left(Expression::IntegerLiteral(one))
+        .operator("+".to_string())
+        .right(Expression::Identifier(y))
+        .build();
+
+    let stmt = LetStatementBuilder::new(&Token::new(TokenType::Let, "let"))
+        .name(Identifier::new(&Token::new(TokenType::Ident, "x")))
+        .value(Some(Expression::Infix(infix)))
+        .build();
+
+    let mut expected = Program::new();
+    expected.push(Statement::Let(stmt));
+
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn test_minus_before_negative_literal() {
+    let minus = Token::new(TokenType::Minus, "-");
+    let negative = IntegerLiteral::new(&Token::new(TokenType::Int, "-5"), -5);
+    let prefix = PrefixExpressionBuilder::new(&minus)
+        .operator("-".to_string())
+        .right(Expression::IntegerLiteral(negative))
+        .build();
+    let stmt = LetStatementBuilder::new(&Token::new(TokenType::Let, "let"))
+        .name(Identifier::new(&Token::new(TokenType::Ident, "x")))
+        .value(Some(Expression::Prefix(prefix)))
+        .build();
+    let mut program = Program::new();
+    program.push(Statement::Let(stmt));
+
+    // Written together the minus signs would be read back as --.
+    assert_eq!(program.to_string(), "let x = (- -5);");
+    assert_eq!(program.pretty(2), "let x = (- -5);\n");
+}
+
+#[test]
+fn test_clone_and_compare_subtrees() {
+    let program = parse("f(a * 2); f(a * 3);");
+
+    let copy = program.clone();
+    assert_eq!(copy, program);
+
+    let Statement::Expression(first) = &program.statements[0] else {
+        panic!("expected an expression statement");
+    };
+    let Statement::Expression(second) = &program.statements[1] else {
+        panic!("expected an expression statement");
+    };
+    assert_ne!(first, second);
+    assert_eq!(first.clone(), *first);
+}
+
+#[test]
+fn test_pretty_printer() {
+    let program = parse(
+        "let max = fn(a, b) { if (a > b) { return a; } else { b } }; for (x in [1, 2]) { puts(x); } let f = fn() {};",
+    );
+
+    let expected = "\
+let max = fn(a, b) {
+  if (a > b) {
+    return a;
+  } else {
+    b;
+  }
+};
+for (x in [1, 2]) {
+  puts(x);
+}
+let f = fn() {};
+";
+    assert_eq!(program.pretty(2), expected);
+
+    // The output is source again, it parses back to the same program.
+    assert_eq!(parse(&program.pretty(4)), program);
+
+    // Properties and method calls are grouped like the other operators.
+    let program = parse("a.b.c(1).d;");
+    assert_eq!(program.pretty(2), "(((a.b).c(1)).d);\n");
+    assert_eq!(parse(&program.pretty(2)), program);
+}
+
+// Rewrites -(-x) into x, and records the expressions in the order they are
+// handed over.
+#[derive(Default)]
+struct DoubleNegation {
+    seen: Vec<String>,
+}
+
+impl Transform for DoubleNegation {
+    fn rewrite_expression(&mut self, expr: Expression) -> Expression {
+        self.seen.push(expr.to_string());
+        match expr {
+            Expression::Prefix(outer) if outer.operator == "-" => match *outer.right {
+                Expression::Prefix(inner) if inner.operator == "-" => *inner.right,
+                right => Expression::Prefix(ast::PrefixExpression {
+                    right: Box::new(right),
+                    ..outer
+                }),
+            },
+            expr => expr,
+        }
+    }
+}
+
+#[test]
+fn test_transform_rewrites_bottom_up() {
+    let program = parse("let x = - - - - a; if (b) { f(- - c) }");
+
+    let mut pass = DoubleNegation::default();
+    let program = ast::transform_program(&mut pass, program);
+
+    assert_eq!(program.to_string(), "let x = a;ifb f(c)");
+    assert_eq!(
+        pass.seen,
+        vec![
+            "a", "(-a)", "(-(-a))", "(-a)", "(-(-a))", "b", "f", "c", "(-c)", "(-(-c))", "f(c)",
+            "ifb f(c)"
+        ]
+    );
+}
+
+#[test]
+fn test_fold_constants() {
+    let tests = [
+        ("3 + 4 * 5;", "23"),
+        ("!true;", "false"),
+        ("-(2 - 9);", "7"),
+        ("1 < 2 == true;", "true"),
+        ("true && !false || false;", "((true && true) || false)"),
+        ("x + 2 * 3;", "(x + 6)"),
+        ("2 ** 3 % 5;", "3"),
+        ("if (1 > 2) { 10 / 5 } else { y };", "iffalse 2else y"),
+        // Left for the runtime to report.
+        ("1 / 0;", "(1 / 0)"),
+        ("1 % 0;", "(1 % 0)"),
+        ("2 ** -1;", "(2 ** -1)"),
+        (
+            "-(-9223372036854775807 - 1);",
+            "(-(-9223372036854775807 - 1))",
+        ),
+        ("9223372036854775807 + 1;", "(9223372036854775807 + 1)"),
+        // Not an operation on values of the same type.
+        ("1 == true;", "(1 == true)"),
+    ];
+
+    for (input, expected) in tests {
+        let program = ast::fold_constants(parse(input));
+        assert_eq!(program.to_string(), expected, "folding {}", input);
+    }
+
+    // What is folded can still be read back.
+    let program = ast::fold_constants(parse("-(0 - 9223372036854775807 - 1);"));
+    assert_eq!(program.pretty(2), "(-(-9223372036854775807 - 1));\n");
+    assert!(Parser::new(Lexer::new(&program.pretty(2))).parse().is_ok());
+}
+
+#[test]
+fn test_fold_constants_keeps_results() {
+    let inputs = [
+        "3 + 4 * 5",
+        "!true == false",
+        "-(2 - 9) % 4",
+        "2 ** 10 / 3",
+        "1 < 2 != 3 > 4",
+        "true && false",
+        "false || true",
+        "1 / 0",
+        "9223372036854775807 + 1",
+        "1 == true",
+    ];
+
+    for input in inputs {
+        let evaluate = |program: &Program| {
+            let env = Rc::new(RefCell::new(Environment::new()));
+            Evaluator::new().eval(program, &env).to_string()
+        };
+        let expected = evaluate(&parse(input));
+        let folded = evaluate(&ast::fold_constants(parse(input)));
+        assert_eq!(folded, expected, "folding {}", input);
+    }
+}
+
+#[test]
+fn test_eliminate_dead_code() {
+    let mut program = ast::fold_constants(parse(
+        "let f = fn() { return 1; x; y };\nif (1 > 2) { a } else { b };\nif (true) { c } else { d };",
+    ));
+
+    let warnings = ast::eliminate_dead_code(&mut program);
+
+    assert_eq!(
+        program.to_string(),
+        "let f = fn() return 1;;iftrue biftrue c"
+    );
+    assert_eq!(
+        warnings,
+        [
+            ParserWarning::UnreachableCode {
+                count: 2,
+                position: Position {
+                    line: 1,
+                    column: 26
+                },
+            },
+            ParserWarning::DeadBranch {
+                condition: false,
+                position: Position { line: 2, column: 1 },
+            },
+            ParserWarning::DeadBranch {
+                condition: true,
+                position: Position { line: 3, column: 1 },
+            },
+        ]
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        "line 1, column 26: Removed 2 unreachable statements after keyword `return`"
+    );
+
+    // Nothing is left to remove.
+    assert!(ast::eliminate_dead_code(&mut program).is_empty());
+
+    // Without else nothing is left of an if always false, only its null value
+    // is kept when the block gives it.
+    let mut program = ast::fold_constants(parse(
+        "if (1 > 2) { a }; let x = if (false) { b }; let f = fn() { c; if (false) { d } }; e;",
+    ));
+    let warnings = ast::eliminate_dead_code(&mut program);
+    assert_eq!(
+        program.to_string(),
+        "let x = iffalse ;let f = fn() ciffalse ;e"
+    );
+    assert_eq!(warnings.len(), 3);
+}
+
+#[test]
+fn test_builder_fields_in_any_order() {
+    // build() only exists once all the required fields are set, whatever the
+    // order they are set in. Calling it earlier doesn't compile.
+    let token = Token::new(TokenType::Asterisk, "*");
+    let two =
+        || Expression::IntegerLiteral(IntegerLiteral::new(&Token::new(TokenType::Int, "2"), 2));
+
+    let infix = InfixExpressionBuilder::new(&token)
+        .right(two())
+        .left(two())
+        .operator("*".to_string())
+        .build();
+    assert_eq!(infix.to_string(), "(2 * 2)");
+
+    // The optional value of a let can be given before its name.
+    let stmt = LetStatementBuilder::new(&Token::new(TokenType::Let, "let"))
+        .value(Some(Expression::Infix(infix)))
+        .name(Identifier::new(&Token::new(TokenType::Ident, "four")))
+        .build();
+    assert_eq!(stmt.to_string(), "let four = (2 * 2);");
+}
+
+#[test]
+fn test_program_stats() {
+    let program = parse("let x = 1 + 2 * y; if (x > 1) { return f(x); }");
+
+    let stats = program.stats();
+
+    assert_eq!(stats.statements, 3);
+    // Expression > If > Return > Call > Identifier, deeper than
+    // Let > Infix > Infix > IntegerLiteral.
+    assert_eq!(stats.max_depth, 5);
+    let counts: Vec<(&str, usize)> = stats.node_counts.into_iter().collect();
+    assert_eq!(
+        counts,
+        [
+            ("Call", 1),
+            ("Expression", 1),
+            ("Identifier", 4),
+            ("If", 1),
+            ("Infix", 3),
+            ("IntegerLiteral", 3),
+            ("Let", 1),
+            ("Return", 1),
+        ]
+    );
+
+    assert_eq!(Program::new().stats(), Default::default());
+}
+
+#[test]
+fn test_program_iteration_helpers() {
+    let program =
+        parse("let a = f(b, 1); for (x in xs) { let y = -x; } let g = fn(p) { let z; p };");
+
+    let exprs: Vec<String> = program.expressions().map(|e| e.to_string()).collect();
+    assert_eq!(
+        exprs,
+        [
+            "f(b, 1)",
+            "f",
+            "b",
+            "1",
+            "xs",
+            "(-x)",
+            "x",
+            "fn(p) let z;p",
+            "p"
+        ]
+    );
+
+    let names: Vec<&str> = program.find_identifiers().map(|i| i.value()).collect();
+    assert_eq!(names, ["f", "b", "xs", "x", "p"]);
+
+    let lets: Vec<String> = program.lets().map(|l| l.to_string()).collect();
+    assert_eq!(
+        lets,
+        [
+            "let a = f(b, 1);",
+            "let y = (-x);",
+            "let g = fn(p) let z;p;",
+            "let z;"
+        ]
+    );
+
+    assert_eq!(program.statements().count(), 6);
+}
+
+#[test]
+fn test_pretty_printer_keeps_comments() {
+    let program = parse("// doc\nlet f = fn() {\n  x; // ret\n  // todo\n};\n/* end */");
+
+    let expected = "\
+// doc
+let f = fn() {
+  x; // ret
+  // todo
+};
+/* end */
+";
+    assert_eq!(program.pretty(2), expected);
+}
+
+#[test]
+fn test_print_lossless() {
+    let source =
+        "  // header\nlet   x=1 ;\n\n/* f */ let f = fn(a) {\n\ta   +x };   \nf( 2 )  // call\n";
+    let mut program = parse(source);
+
+    assert_eq!(program.print_lossless(source), source);
+
+    // Replace the first statement and remove the second one, the rest is
+    // left as written.
+    let stmt = LetStatementBuilder::new(&Token::new(TokenType::Let, "let"))
+        .name(Identifier::new(&Token::new(TokenType::Ident, "x")))
+        .value(Some(Expression::IntegerLiteral(IntegerLiteral::new(
+            &Token::new(TokenType::Int, "2"),
+            2,
+        ))))
+        .build();
+    program.statements[0] = Statement::Let(stmt);
+    program.statements.remove(1);
 
-    assert_eq!(p.string(), "let myVar = anotherVar;");
+    assert_eq!(
+        program.print_lossless(source),
+        "  // header\nlet x = 2;\nf( 2 )  // call\n"
+    );
 }
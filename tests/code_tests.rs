@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests {
+
+    use redhowler::compiler::code::{self, Instructions, Opcode};
+
+    #[test]
+    fn test_make() {
+        let tests = [
+            (
+                Opcode::Constant,
+                vec![65534],
+                vec![Opcode::Constant as u8, 255, 254],
+            ),
+            (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+        ];
+
+        for (op, operands, expected) in tests {
+            assert_eq!(code::make(op, &operands), expected, "making {:?}", op);
+        }
+    }
+
+    #[test]
+    fn test_read_operands() {
+        let tests = [
+            (Opcode::Constant, vec![65535], 2),
+            (Opcode::JumpNotTruthy, vec![12], 2),
+            (Opcode::Pop, vec![], 0),
+        ];
+
+        for (op, operands, bytes_read) in tests {
+            let instruction = code::make(op, &operands);
+
+            let (read, n) = code::read_operands(op, &instruction[1..]);
+            assert_eq!(n, bytes_read);
+            assert_eq!(read, operands);
+        }
+    }
+
+    #[test]
+    fn test_opcode_from_byte() {
+        for byte in 0..=255u8 {
+            if let Ok(op) = Opcode::try_from(byte) {
+                assert_eq!(op as u8, byte);
+            }
+        }
+        assert_eq!(Opcode::try_from(Opcode::Bang as u8), Ok(Opcode::Bang));
+        assert_eq!(Opcode::try_from(200), Err(200));
+    }
+
+    #[test]
+    fn test_instructions_string() {
+        let instructions = Instructions::from(vec![
+            code::make(Opcode::Add, &[]),
+            code::make(Opcode::Constant, &[2]),
+            code::make(Opcode::Constant, &[65535]),
+            code::make(Opcode::Pop, &[]),
+        ]);
+
+        let expected = "\
+0000 OpAdd
+0001 OpConstant 2
+0004 OpConstant 65535
+0007 OpPop
+";
+        assert_eq!(instructions.to_string(), expected);
+
+        let broken = Instructions(vec![200, Opcode::Constant as u8, 1]);
+        assert_eq!(
+            broken.to_string(),
+            "0000 ERROR: unknown opcode 200\n0001 ERROR: truncated OpConstant\n"
+        );
+    }
+}
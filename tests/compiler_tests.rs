@@ -0,0 +1,221 @@
+#[cfg(test)]
+mod tests {
+
+    use redhowler::compiler::symbol_table::SymbolScope;
+    use redhowler::compiler::{Bytecode, Compiler};
+    use redhowler::interpreter::lexer::Lexer;
+    use redhowler::interpreter::object::Object;
+    use redhowler::interpreter::parser::Parser;
+    use redhowler::interpreter::token::Position;
+
+    fn compile(input: &str) -> Result<Bytecode, String> {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+        let mut compiler = Compiler::new();
+        compiler.compile(&program)?;
+        Ok(compiler.bytecode())
+    }
+
+    // The constants as they are printed, with the instructions of the
+    // compiled functions.
+    fn constants(bytecode: &Bytecode) -> Vec<String> {
+        bytecode
+            .constants
+            .iter()
+            .map(|constant| match constant {
+                Object::CompiledFunction(function) => function.instructions.to_string(),
+                constant => constant.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_expressions() {
+        let tests = [
+            (
+                "1 + 2; -3",
+                "0000 OpConstant 0\n0003 OpConstant 1\n0006 OpAdd\n0007 OpPop\n0008 OpConstant 2\n0011 OpMinus\n0012 OpPop\n",
+                vec!["1", "2", "3"],
+            ),
+            (
+                "1 < 2.5 == !true",
+                "0000 OpConstant 0\n0003 OpConstant 1\n0006 OpLessThan\n0007 OpTrue\n0008 OpBang\n0009 OpEqual\n0010 OpPop\n",
+                vec!["1", "2.5"],
+            ),
+            (
+                r#"[1, "a"][0]; {"k": 2}"#,
+                "0000 OpConstant 0\n0003 OpConstant 1\n0006 OpArray 2\n0009 OpConstant 2\n0012 OpIndex\n0013 OpPop\n0014 OpConstant 3\n0017 OpConstant 4\n0020 OpHash 2\n0023 OpPop\n",
+                vec!["1", "a", "0", "k", "2"],
+            ),
+            (
+                "if (true) { 10 }; 3333",
+                "0000 OpTrue\n0001 OpJumpNotTruthy 10\n0004 OpConstant 0\n0007 OpJump 11\n0010 OpNull\n0011 OpPop\n0012 OpConstant 1\n0015 OpPop\n",
+                vec!["10", "3333"],
+            ),
+            (
+                "if (true) { 10 } else { 20 }; 3333",
+                "0000 OpTrue\n0001 OpJumpNotTruthy 10\n0004 OpConstant 0\n0007 OpJump 13\n0010 OpConstant 1\n0013 OpPop\n0014 OpConstant 2\n0017 OpPop\n",
+                vec!["10", "20", "3333"],
+            ),
+            (
+                "1; if (false) { }",
+                "0000 OpConstant 0\n0003 OpPop\n0004 OpFalse\n0005 OpJumpNotTruthy 12\n0008 OpNull\n0009 OpJump 13\n0012 OpNull\n0013 OpPop\n",
+                vec!["1"],
+            ),
+        ];
+
+        for (input, instructions, expected_constants) in tests {
+            let bytecode = compile(input).unwrap();
+            assert_eq!(
+                bytecode.instructions.to_string(),
+                instructions,
+                "compiling {}",
+                input
+            );
+            assert_eq!(
+                constants(&bytecode),
+                expected_constants,
+                "compiling {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_bindings() {
+        let bytecode = compile("let a = 1; let b; const c = a; len(c)").unwrap();
+        assert_eq!(
+            bytecode.instructions.to_string(),
+            "\
+0000 OpConstant 0
+0003 OpSetGlobal 0
+0006 OpNull
+0007 OpSetGlobal 1
+0010 OpGetGlobal 0
+0013 OpSetGlobal 2
+0016 OpGetBuiltin 0
+0018 OpGetGlobal 2
+0021 OpCall 1
+0023 OpPop
+"
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let bytecode = compile("fn(a) { let b = a; b }; fn() { return 1; }; fn() {}").unwrap();
+
+        assert_eq!(
+            bytecode.instructions.to_string(),
+            "0000 OpClosure 0 0\n0004 OpPop\n0005 OpClosure 2 0\n0009 OpPop\n0010 OpClosure 3 0\n0014 OpPop\n"
+        );
+        assert_eq!(
+            constants(&bytecode),
+            [
+                "0000 OpGetLocal 0\n0002 OpSetLocal 1\n0004 OpGetLocal 1\n0006 OpReturnValue\n",
+                "1",
+                "0000 OpConstant 1\n0003 OpReturnValue\n",
+                "0000 OpReturn\n",
+            ]
+        );
+        let Object::CompiledFunction(function) = &bytecode.constants[0] else {
+            panic!("expected a compiled function");
+        };
+        assert_eq!((function.parameters.len(), function.num_locals), (1, 2));
+        assert_eq!(bytecode.constants[0].to_string(), "fn(a) { ... }");
+    }
+
+    #[test]
+    fn test_call_positions() {
+        let bytecode = compile("len([]);\nlet f = fn() { puts(len(\"\")) };").unwrap();
+
+        let positions = |calls: &[(usize, Position)]| {
+            calls
+                .iter()
+                .map(|(offset, position)| (*offset, position.line, position.column))
+                .collect::<Vec<_>>()
+        };
+        // The offsets are the ones of the call instructions.
+        assert_eq!(positions(&bytecode.call_positions), [(5, 1, 4)]);
+        let Object::CompiledFunction(function) = &bytecode.constants[1] else {
+            panic!("expected a compiled function");
+        };
+        assert_eq!(
+            positions(&function.call_positions),
+            [(7, 2, 24), (9, 2, 20)]
+        );
+        assert_eq!(function.call_position(9), function.call_positions[1].1);
+        assert_eq!(function.call_position(8), Position::default());
+    }
+
+    #[test]
+    fn test_closures() {
+        let bytecode = compile("fn(a) { fn(b) { fn(c) { a + b + c } } }").unwrap();
+
+        assert_eq!(
+            constants(&bytecode),
+            [
+                "0000 OpGetFree 0\n0002 OpGetFree 1\n0004 OpAdd\n0005 OpGetLocal 0\n0007 OpAdd\n0008 OpReturnValue\n",
+                "0000 OpClosure 0 2\n0004 OpReturnValue\n",
+                "0000 OpClosure 1 1\n0004 OpReturnValue\n",
+            ]
+        );
+        // The closures capture the free variables listed by the functions.
+        let free = |index: usize| match &bytecode.constants[index] {
+            Object::CompiledFunction(function) => function
+                .free
+                .iter()
+                .map(|symbol| (symbol.name.as_str(), symbol.scope, symbol.index))
+                .collect::<Vec<_>>(),
+            constant => panic!("expected a compiled function, got {}", constant),
+        };
+        assert_eq!(
+            free(0),
+            [("a", SymbolScope::Free, 0), ("b", SymbolScope::Local, 0)]
+        );
+        assert_eq!(free(1), [("a", SymbolScope::Local, 0)]);
+        assert_eq!(
+            bytecode.instructions.to_string(),
+            "0000 OpClosure 2 0\n0004 OpPop\n"
+        );
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        let bytecode =
+            compile("let wrapper = fn() { let count = fn(x) { count(x - 1) }; count(1) };")
+                .unwrap();
+
+        assert_eq!(
+            constants(&bytecode),
+            [
+                "1",
+                "0000 OpCurrentClosure\n0001 OpGetLocal 0\n0003 OpConstant 0\n0006 OpSub\n0007 OpCall 1\n0009 OpReturnValue\n",
+                "1",
+                "0000 OpClosure 1 0\n0004 OpSetLocal 0\n0006 OpGetLocal 0\n0008 OpConstant 2\n0011 OpCall 1\n0013 OpReturnValue\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let tests = [
+            ("x", "identifier not found: x"),
+            ("let f = fn() { y }", "identifier not found: y"),
+            ("true && false", "unsupported operator: &&"),
+            ("for (x in [1]) { x }", "unsupported statement: ForIn"),
+            ("let a = 1; a = 2", "unsupported expression: Assign"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                compile(input),
+                Err(expected.to_string()),
+                "compiling {}",
+                input
+            );
+        }
+    }
+}
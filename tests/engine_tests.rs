@@ -0,0 +1,131 @@
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use redhowler::engine::{Engine, Error};
+    use redhowler::interpreter::evaluator::Evaluator;
+    use redhowler::interpreter::lexer::Lexer;
+    use redhowler::interpreter::parser::Parser;
+
+    const ENGINES: [Engine; 2] = [Engine::Eval, Engine::Vm];
+
+    #[test]
+    fn test_engine_from_str() {
+        assert_eq!("eval".parse(), Ok(Engine::Eval));
+        assert_eq!("vm".parse(), Ok(Engine::Vm));
+        assert_eq!(
+            "jit".parse::<Engine>(),
+            Err("unknown engine: jit (expected eval or vm)".to_string())
+        );
+        assert_eq!(Engine::default(), Engine::Eval);
+        assert_eq!(Engine::Vm.to_string(), "vm");
+    }
+
+    #[test]
+    fn test_run() {
+        let tests = [
+            ("1 + 2", "3"),
+            (
+                "let f = fn(n) { if (n < 2) { n } else { f(n - 1) + f(n - 2) } }; f(10)",
+                "55",
+            ),
+            ("map([1, 2], fn(x) { x * 2 })", "[2, 4]"),
+            ("let x;", "null"),
+        ];
+
+        for engine in ENGINES {
+            for (input, expected) in tests {
+                let value = engine.run(input).unwrap();
+                assert_eq!(
+                    value.to_string(),
+                    expected,
+                    "running {} with {}",
+                    input,
+                    engine
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        for engine in ENGINES {
+            assert_eq!(
+                engine.run("1 + true"),
+                Err(Error::Runtime(
+                    "type mismatch: INTEGER + BOOLEAN".to_string()
+                )),
+                "with {}",
+                engine
+            );
+            // The builtins know where they were called.
+            assert_eq!(
+                engine.run(r#"assert(false, "m")"#),
+                Err(Error::Runtime(
+                    "line 1, column 7: assertion failed: m".to_string()
+                )),
+                "with {}",
+                engine
+            );
+            assert_eq!(
+                engine.run("let f = fn() { assert(false, \"m\") };\nf()"),
+                Err(Error::Runtime(
+                    "line 1, column 22: assertion failed: m".to_string()
+                )),
+                "with {}",
+                engine
+            );
+            let Err(Error::Parse(errors)) = engine.run("let = 1;") else {
+                panic!("expected a parse error with {}", engine);
+            };
+            assert!(!errors.is_empty());
+        }
+
+        // Only the VM needs the program to compile.
+        assert!(Engine::Eval.run("let a = 1; a = 2; a").is_ok());
+        assert_eq!(
+            Engine::Vm.run("let a = 1; a = 2; a"),
+            Err(Error::Compile("unsupported expression: Assign".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_branch_scope() {
+        // The lets of a branch are not seen after it, and don't change the
+        // bindings they hide.
+        let input = "let f = fn() { let y = 1; if (true) { let y = 2; }; y }; f()";
+        for engine in ENGINES {
+            assert_eq!(
+                engine.run(input).map(|value| value.to_string()),
+                Ok("1".to_string()),
+                "with {}",
+                engine
+            );
+            let err = engine.run("if (true) { let x = 1; }; x").unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "identifier not found: x",
+                "with {}",
+                engine
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_with_evaluator_output() {
+        for engine in ENGINES {
+            let output = Rc::new(RefCell::new(Vec::new()));
+            let mut parser = Parser::new(Lexer::new(r#"puts("hi"); 2"#));
+            let program = parser.parse_program();
+
+            let value = engine
+                .run_with(&program, Evaluator::with_output(output.clone()))
+                .unwrap();
+
+            assert_eq!(value.to_string(), "2");
+            assert_eq!(output.borrow().as_slice(), b"hi\n", "with {}", engine);
+        }
+    }
+}
@@ -0,0 +1,1229 @@
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use redhowler::interpreter::environment::{Env, Environment};
+    use redhowler::interpreter::evaluator::{self, Evaluator};
+    use redhowler::interpreter::lexer::Lexer;
+    use redhowler::interpreter::object::{HashKey, Object};
+    use redhowler::interpreter::parser::Parser;
+
+    fn new_env() -> Env {
+        Rc::new(RefCell::new(Environment::new()))
+    }
+
+    fn eval(input: &str) -> Object {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        evaluator::eval(&program, &new_env())
+    }
+
+    #[test]
+    fn test_eval_integer_expression() {
+        let tests = [
+            ("5", 5),
+            ("-10", -10),
+            ("5 + 5 + 5 + 5 - 10", 10),
+            ("2 * 2 * 2 * 2 * 2", 32),
+            ("-50 + 100 + -50", 0),
+            ("20 + 2 * -10", 0),
+            ("50 / 2 * 2 + 10", 60),
+            ("3 * (3 * 3) + 10", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+            ("7 % 3", 1),
+            ("-7 % 3", -1),
+            ("1 + 10 % 4 * 2", 5),
+            ("2 ** 10", 1024),
+            ("2 ** 3 ** 2", 512),
+            ("-2 ** 2", -4),
+            ("(-2) ** 3", -8),
+            ("5 ** 0", 1),
+            ("1 ** 9999999999", 1),
+            ("-1 ** 9999999999", -1),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Integer(expected),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_float_expression() {
+        let tests = [
+            ("3.5", 3.5),
+            ("-0.5", -0.5),
+            ("1e3", 1000.0),
+            ("1.5 + 2.25", 3.75),
+            ("1 + 0.5", 1.5),
+            ("0.5 * 4", 2.0),
+            ("7 / 2.0", 3.5),
+            ("2.5 - 1", 1.5),
+            ("1.0 / 0", f64::INFINITY),
+            ("7.5 % 2", 1.5),
+            ("2 ** 0.5 ** 2", 2f64.powf(0.25)),
+            ("4.0 ** -1", 0.25),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), Object::Float(expected), "evaluating {}", input);
+        }
+
+        let tests = [
+            ("1.5 < 2", true),
+            ("2 > 1.5", true),
+            ("1 == 1.0", true),
+            ("0.1 + 0.2 == 0.3", false),
+            ("2.0 != 2", false),
+            ("let nan = 0.0 / 0.0; nan == nan", false),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Boolean(expected),
+                "evaluating {}",
+                input
+            );
+        }
+
+        // The result of an operation on floats stays a float.
+        assert_eq!(eval("0.5 * 4").to_string(), "2.0");
+        // Integers are divided as integers.
+        assert_eq!(eval("7 / 2"), Object::Integer(3));
+    }
+
+    #[test]
+    fn test_eval_boolean_expression() {
+        let tests = [
+            ("true", true),
+            ("false", false),
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("true == false", false),
+            ("(1 < 2) == true", true),
+            ("(1 > 2) != false", false),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Boolean(expected),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_bang_operator() {
+        let tests = [
+            ("!true", false),
+            ("!false", true),
+            ("!5", false),
+            ("!!true", true),
+            ("!!false", false),
+            ("!!5", true),
+            ("!0", false),
+            ("!\"\"", false),
+            ("let a; !a", true),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Boolean(expected),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_if_else_expressions() {
+        let tests = [
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 < 2) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+            ("let a; if (a) { 10 } else { 20 }", Object::Integer(20)),
+            ("1 < 2 ? 10 : 20", Object::Integer(10)),
+            ("false ? 10 : 20", Object::Integer(20)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_if_block_scope() {
+        assert_eq!(
+            eval("let x = 1; if (true) { let x = 2; let y = x; } x"),
+            Object::Integer(1)
+        );
+        assert_eq!(
+            eval("if (true) { let y = 2; } y"),
+            Object::Error("identifier not found: y".to_string())
+        );
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let tests = [
+            ("let a = 5; a;", Object::Integer(5)),
+            ("let a = 5 * 5; a;", Object::Integer(25)),
+            ("let a = 5; let b = a; b;", Object::Integer(5)),
+            (
+                "let a = 5; let b = a; let c = a + b + 5; c;",
+                Object::Integer(15),
+            ),
+            ("let a; a;", Object::Null),
+            ("const a = 2; a * a;", Object::Integer(4)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_environment_persists_between_programs() {
+        let env = new_env();
+        let program = Parser::new(Lexer::new("let x = 5;")).parse_program();
+        evaluator::eval(&program, &env);
+
+        assert_eq!(env.borrow().get("x"), Some(Object::Integer(5)));
+        let program = Parser::new(Lexer::new("x * 2")).parse_program();
+        assert_eq!(evaluator::eval(&program, &env), Object::Integer(10));
+    }
+
+    #[test]
+    fn test_enclosed_environment() {
+        let global = new_env();
+        global.borrow_mut().set("x", Object::Integer(1));
+        global.borrow_mut().set("y", Object::Integer(2));
+
+        let mut local = Environment::new_enclosed(Rc::clone(&global));
+        local.set("x", Object::Integer(10));
+        local.set("z", Object::Integer(30));
+
+        assert_eq!(local.get("x"), Some(Object::Integer(10)));
+        assert_eq!(local.get("y"), Some(Object::Integer(2)));
+        assert_eq!(local.get("z"), Some(Object::Integer(30)));
+        assert_eq!(global.borrow().get("x"), Some(Object::Integer(1)));
+        assert_eq!(global.borrow().get("z"), None);
+    }
+
+    #[test]
+    fn test_function_application() {
+        let tests = [
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x; }(5)", 5),
+            // The closure keeps the scope of the call that created it.
+            (
+                "let adder = fn(x) { fn(y) { x + y } }; let addTwo = adder(2); addTwo(3);",
+                5,
+            ),
+            // Parameters and locals shadow the globals without changing them.
+            ("let x = 1; let f = fn(x) { let y = x; y }; f(7) + x", 8),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Integer(expected),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_function_locals_do_not_leak() {
+        assert_eq!(
+            eval("let f = fn() { let local = 1; local }; f(); local"),
+            Object::Error("identifier not found: local".to_string())
+        );
+    }
+
+    #[test]
+    fn test_function_object() {
+        let Object::Function(func) = eval("fn(x) { x + 2; };") else {
+            panic!("expected a function");
+        };
+        assert_eq!(func.parameters.len(), 1);
+        assert_eq!(func.parameters[0].to_string(), "x");
+        assert_eq!(func.body.to_string(), "(x + 2)");
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let tests = [
+            ("return 10;", 10),
+            ("return 10; 9;", 10),
+            ("return 2 * 5; 9;", 10),
+            ("9; return 2 * 5; 9;", 10),
+            ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", 10),
+            ("let f = fn() { return 1; 2 }; f()", 1),
+            // A return leaves the nested blocks, up to the function call.
+            (
+                "let f = fn() { let g = fn() { return 1; }; g(); 3 }; f()",
+                3,
+            ),
+            (
+                "let f = fn() { let g = fn() { return 1; }; return g() + 1; 3 }; f()",
+                2,
+            ),
+            (
+                "let f = fn(x) { let g = fn() { fn() { return x; }() * 2 }; return g(); 0 }; f(4)",
+                8,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Integer(expected),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_string_expressions() {
+        let tests = [
+            (r#""Hello World!""#, Object::Str("Hello World!".to_string())),
+            (
+                r#""Hello" + " " + "World!""#,
+                Object::Str("Hello World!".to_string()),
+            ),
+            (
+                r#"let greet = fn(name) { "Hi " + name }; greet("you")"#,
+                Object::Str("Hi you".to_string()),
+            ),
+            (r#""a\tb""#, Object::Str("a\tb".to_string())),
+            (r#""abc" == "abc""#, Object::Boolean(true)),
+            (r#""abc" == "abd""#, Object::Boolean(false)),
+            (r#""abc" != "abd""#, Object::Boolean(true)),
+            (r#""abc" < "abd""#, Object::Boolean(true)),
+            (r#""abc" > "abd""#, Object::Boolean(false)),
+            (r#""ab" < "abc""#, Object::Boolean(true)),
+            ("\"\" < \"a\"", Object::Boolean(true)),
+            (r#""Z" < "a""#, Object::Boolean(true)),
+            (r#""b" > "abc""#, Object::Boolean(true)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_array_literals() {
+        let result = eval("[1, 2 * 2, 3 + 3]");
+
+        assert_eq!(
+            result,
+            Object::Array(vec![
+                Object::Integer(1),
+                Object::Integer(4),
+                Object::Integer(6)
+            ])
+        );
+        assert_eq!(result.to_string(), "[1, 4, 6]");
+    }
+
+    #[test]
+    fn test_array_index_expressions() {
+        let tests = [
+            ("[1, 2, 3][0]", Object::Integer(1)),
+            ("[1, 2, 3][1]", Object::Integer(2)),
+            ("[1, 2, 3][2]", Object::Integer(3)),
+            ("let i = 0; [1][i];", Object::Integer(1)),
+            ("[1, 2, 3][1 + 1];", Object::Integer(3)),
+            ("let myArray = [1, 2, 3]; myArray[2];", Object::Integer(3)),
+            (
+                "let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];",
+                Object::Integer(6),
+            ),
+            ("let a = [[1], [2, 3]]; a[1][0]", Object::Integer(2)),
+            ("[1, 2, 3][3]", Object::Null),
+            ("[1, 2, 3][-1]", Object::Null),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        let error = |message: &str| Object::Error(message.to_string());
+        let array =
+            |values: &[i64]| Object::Array(values.iter().map(|&v| Object::Integer(v)).collect());
+        let tests = [
+            (r#"len("")"#, Object::Integer(0)),
+            (r#"len("four")"#, Object::Integer(4)),
+            (r#"len("héhé")"#, Object::Integer(4)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            (
+                "len(1)",
+                error("argument to `len` not supported, got INTEGER"),
+            ),
+            (
+                r#"len("one", "two")"#,
+                error("wrong number of arguments: want=1, got=2"),
+            ),
+            ("first([1, 2, 3])", Object::Integer(1)),
+            ("first([])", Object::Null),
+            (
+                "first(1)",
+                error("argument to `first` must be ARRAY, got INTEGER"),
+            ),
+            ("last([1, 2, 3])", Object::Integer(3)),
+            ("last([])", Object::Null),
+            (
+                "last(1)",
+                error("argument to `last` must be ARRAY, got INTEGER"),
+            ),
+            ("rest([1, 2, 3])", array(&[2, 3])),
+            ("rest([])", Object::Null),
+            ("push([], 1)", array(&[1])),
+            ("let a = [1]; push(a, 2); a", array(&[1])),
+            (
+                "push(1, 1)",
+                error("argument to `push` must be ARRAY, got INTEGER"),
+            ),
+            (
+                "push([])",
+                error("wrong number of arguments: want=2, got=1"),
+            ),
+            // A let binding shadows the builtin.
+            ("let len = fn(x) { 42 }; len([])", Object::Integer(42)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+        assert_eq!(eval("len").to_string(), "builtin function len");
+    }
+
+    #[test]
+    fn test_string_builtins() {
+        let error = |message: &str| Object::Error(message.to_string());
+        let string = |value: &str| Object::Str(value.to_string());
+        let strings = |values: &[&str]| Object::Array(values.iter().map(|v| string(v)).collect());
+        let tests = [
+            (r#"split("a,b,,c", ",")"#, strings(&["a", "b", "", "c"])),
+            (r#"split("abc", "")"#, strings(&["a", "b", "c"])),
+            (r#"split("", ",")"#, strings(&[""])),
+            (r#"join(["a", "b", "c"], ", ")"#, string("a, b, c")),
+            (r#"join([1, true], "-")"#, string("1-true")),
+            (r#"join([], "-")"#, string("")),
+            (r#"join(split("a b", " "), "_")"#, string("a_b")),
+            (r#"upper("Hello")"#, string("HELLO")),
+            (r#"lower("Hello")"#, string("hello")),
+            (r#"trim("  hi	
+")"#, string("hi")),
+            (r#"contains("monkey", "key")"#, Object::Boolean(true)),
+            (r#"contains("monkey", "ape")"#, Object::Boolean(false)),
+            (r#"replace("a-b-c", "-", "+")"#, string("a+b+c")),
+            (
+                r#"split(1, ",")"#,
+                error("arguments to `split` must be STRING, STRING, got INTEGER, STRING"),
+            ),
+            (
+                r#"join("abc", ",")"#,
+                error("arguments to `join` must be ARRAY, STRING, got STRING, STRING"),
+            ),
+            ("upper(1)", error("argument to `upper` must be STRING, got INTEGER")),
+            ("lower([])", error("argument to `lower` must be STRING, got ARRAY")),
+            ("trim(true)", error("argument to `trim` must be STRING, got BOOLEAN")),
+            (
+                r#"contains("a", 1)"#,
+                error("arguments to `contains` must be STRING, STRING, got STRING, INTEGER"),
+            ),
+            (
+                r#"replace("a", "b", 3)"#,
+                error("arguments to `replace` must be STRING, STRING, STRING, got STRING, STRING, INTEGER"),
+            ),
+            (r#"replace("a", "b")"#, error("wrong number of arguments: want=3, got=2")),
+            (r#"upper("a", "b")"#, error("wrong number of arguments: want=1, got=2")),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_higher_order_builtins() {
+        let error = |message: &str| Object::Error(message.to_string());
+        let array =
+            |values: &[i64]| Object::Array(values.iter().map(|&v| Object::Integer(v)).collect());
+        let tests = [
+            ("map([1, 2, 3], fn(x) { x * 2 })", array(&[2, 4, 6])),
+            ("map([], fn(x) { x * 2 })", array(&[])),
+            ("map([[1], [2, 3]], len)", array(&[1, 2])),
+            ("filter([1, 2, 3, 4], fn(x) { x > 2 })", array(&[3, 4])),
+            ("filter([1, 2], fn(x) { if (x == 1) { return 0; } })", array(&[1])),
+            ("reduce([1, 2, 3, 4], 0, fn(acc, x) { acc + x })", Object::Integer(10)),
+            ("reduce([], 7, fn(acc, x) { acc + x })", Object::Integer(7)),
+            (
+                "let sum = fn(xs) { reduce(xs, 0, fn(a, b) { a + b }) }; sum(map(filter([1, 2, 3, 4], fn(x) { x > 1 }), fn(x) { x * x }))",
+                Object::Integer(29),
+            ),
+            // The closures see the bindings of their scope.
+            ("let k = 3; map([1, 2], fn(x) { x * k })", array(&[3, 6])),
+            ("map([1, true], fn(x) { -x })", error("unknown operator: -BOOLEAN")),
+            ("map([1], fn(x, y) { x })", error("wrong number of arguments: want=2, got=1")),
+            (
+                "map(fn(x) { x }, [1])",
+                error("arguments to `map` must be ARRAY, FUNCTION, got FUNCTION, ARRAY"),
+            ),
+            (
+                "filter([1], 2)",
+                error("arguments to `filter` must be ARRAY, FUNCTION, got ARRAY, INTEGER"),
+            ),
+            (
+                "reduce(1, 0, len)",
+                error("arguments to `reduce` must be ARRAY, ANY, FUNCTION, got INTEGER, INTEGER, BUILTIN"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_hash_builtins() {
+        let tests = [
+            (r#"keys({"b": 1, "a": 2, 3: 3})"#, r#"[3, a, b]"#),
+            (r#"values({"b": 1, "a": 2, 3: 3})"#, "[3, 2, 1]"),
+            ("keys({})", "[]"),
+            (r#"has_key({"a": 1}, "a")"#, "true"),
+            (r#"has_key({"a": 1}, "b")"#, "false"),
+            (r#"delete({"a": 1, "b": 2}, "a")"#, "{b: 2}"),
+            (r#"delete({"a": 1}, "z")"#, "{a: 1}"),
+            (r#"let h = {"a": 1}; delete(h, "a"); h"#, "{a: 1}"),
+            (
+                r#"merge({"a": 1, "b": 2}, {"b": 3, "c": 4})"#,
+                "{a: 1, b: 3, c: 4}",
+            ),
+            (r#"let h = {"a": 1}; merge(h, {"b": 2}); h"#, "{a: 1}"),
+            (r#"has_key({}, [1])"#, "ERROR: unusable as hash key: ARRAY"),
+            (
+                r#"delete({}, fn() {})"#,
+                "ERROR: unusable as hash key: FUNCTION",
+            ),
+            (
+                "keys([1])",
+                "ERROR: argument to `keys` must be HASH, got ARRAY",
+            ),
+            (
+                "values(1)",
+                "ERROR: argument to `values` must be HASH, got INTEGER",
+            ),
+            (
+                r#"merge({}, [])"#,
+                "ERROR: arguments to `merge` must be HASH, HASH, got HASH, ARRAY",
+            ),
+            (
+                "has_key({})",
+                "ERROR: wrong number of arguments: want=2, got=1",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input).to_string(), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_type_builtin() {
+        let tests = [
+            ("type(1)", "INTEGER"),
+            ("type(1.5)", "FLOAT"),
+            ("type(true)", "BOOLEAN"),
+            (r#"type("a")"#, "STRING"),
+            ("type([])", "ARRAY"),
+            ("type({})", "HASH"),
+            ("type(range(2))", "RANGE"),
+            ("let a; type(a)", "NULL"),
+            ("type(fn(x) { x })", "FUNCTION"),
+            ("type(len)", "BUILTIN"),
+            ("type(type(1))", "STRING"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Str(expected.to_string()),
+                "evaluating {}",
+                input
+            );
+        }
+        assert_eq!(
+            eval("type()"),
+            Object::Error("wrong number of arguments: want=1, got=0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_inspect_functions() {
+        let tests = [
+            ("fn(a, b) { a + b }", "fn(a, b) { (a + b) }"),
+            ("fn() { 1 }", "fn() { 1 }"),
+            ("len", "builtin function len"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input).to_string(), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_inspect_builtin() {
+        let tests = [
+            ("inspect(1)", "1"),
+            ("inspect(2.0)", "2.0"),
+            (r#"inspect("a \"b\"\n")"#, r#""a \"b\"\n""#),
+            ("inspect([])", "[]"),
+            ("inspect({})", "{}"),
+            (
+                r#"inspect([1, "2", true, [], {}])"#,
+                r#"[1, "2", true, [], {}]"#,
+            ),
+            (r#"inspect({"b": 2, "a": "1"})"#, r#"{"a": "1", "b": 2}"#),
+            ("inspect(fn(x) { x })", "fn(x) { x }"),
+            (
+                r#"inspect([1, [2, 3], {"a": [4], "b": false}])"#,
+                "[\n  1,\n  [2, 3],\n  {\n    \"a\": [4],\n    \"b\": false,\n  },\n]",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Str(expected.to_string()),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_assert_builtin() {
+        let tests = [
+            (r#"assert(1 < 2, "math")"#, Object::Null),
+            ("assert(0)", Object::Null),
+            (
+                "let x = 1;\nassert(x == 2, \"x is not 2\"); 3",
+                Object::Error("line 2, column 7: assertion failed: x is not 2".to_string()),
+            ),
+            (
+                "let f = fn() { assert(false) }; f()",
+                Object::Error("line 1, column 22: assertion failed".to_string()),
+            ),
+            (
+                "assert(true, 1)",
+                Object::Error(
+                    "arguments to `assert` must be any, STRING, got BOOLEAN, INTEGER".to_string(),
+                ),
+            ),
+            (
+                "assert()",
+                Object::Error("wrong number of arguments: want=2, got=0".to_string()),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_error_and_try_builtins() {
+        let tests = [
+            (r#"error("boom"); 1"#, Object::Error("boom".to_string())),
+            (
+                r#"let r = try(fn() { error("boom"); 1 }); [r["ok"], r["message"]]"#,
+                Object::Array(vec![
+                    Object::Boolean(false),
+                    Object::Str("boom".to_string()),
+                ]),
+            ),
+            (
+                "let r = try(fn() { 1 / 0 }); r",
+                eval(r#"{"ok": false, "message": "division by zero"}"#),
+            ),
+            (
+                "let r = try(fn() { 1 + 2 }); [r[\"ok\"], r[\"value\"]]",
+                Object::Array(vec![Object::Boolean(true), Object::Integer(3)]),
+            ),
+            // Errors raised in nested calls are caught too, and the program
+            // goes on after try.
+            (
+                r#"let check = fn(x) { if (x < 0) { error("negative") } x };
+                   let r = try(fn() { check(-1) }); if (r["ok"]) { 1 } else { 2 }"#,
+                Object::Integer(2),
+            ),
+            (
+                "try(1)",
+                Object::Error("argument to `try` must be FUNCTION, got INTEGER".to_string()),
+            ),
+            (
+                "error(1)",
+                Object::Error("argument to `error` must be STRING, got INTEGER".to_string()),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_sort_builtin() {
+        let tests = [
+            ("sort([3, 1, 2])", "[1, 2, 3]"),
+            (r#"sort(["b", "c", "a"])"#, "[a, b, c]"),
+            ("sort([])", "[]"),
+            ("let a = [2, 1]; sort(a); a", "[2, 1]"),
+            ("sort([3, 1, 2], fn(a, b) { b - a })", "[3, 2, 1]"),
+            ("sort([3, 1, 2], fn(a, b) { a > b })", "[3, 2, 1]"),
+            // Stable, the pairs with the same first element keep their order.
+            (
+                "sort([[2, 1], [1, 2], [2, 3], [1, 4]], fn(a, b) { a[0] - b[0] })",
+                "[[1, 2], [1, 4], [2, 1], [2, 3]]",
+            ),
+            // An inconsistent comparator gives some order, without failing.
+            ("len(sort([1, 2, 3, 4, 5], fn(a, b) { true }))", "5"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input).to_string(), expected, "evaluating {}", input);
+        }
+
+        let tests = [
+            (
+                r#"sort([1, "a"])"#,
+                "elements to `sort` must all be INTEGER or all be STRING",
+            ),
+            (
+                r#"sort([2, 1], fn(a, b) { "a" })"#,
+                "comparator of `sort` must return INTEGER or BOOLEAN, got STRING",
+            ),
+            (
+                "sort([2, 1], fn(a, b) { a + true })",
+                "type mismatch: INTEGER + BOOLEAN",
+            ),
+            (
+                "sort([2, 1], fn(a) { a })",
+                "wrong number of arguments: want=1, got=2",
+            ),
+            ("sort(1)", "argument to `sort` must be ARRAY, got INTEGER"),
+            (
+                "sort([], 1)",
+                "arguments to `sort` must be ARRAY, FUNCTION, got ARRAY, INTEGER",
+            ),
+            ("sort()", "wrong number of arguments: want=2, got=0"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Error(expected.to_string()),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_builtins() {
+        let tests = [
+            ("json_parse(\" 42 \")", "42"),
+            ("json_parse(\"-1.5e2\")", "-150.0"),
+            (
+                "json_parse(\"12345678901234567890\")",
+                "1.2345678901234567e19",
+            ),
+            (
+                r#"json_parse("[true, false, null]")"#,
+                "[true, false, null]",
+            ),
+            (
+                r#"json_parse("{\"b\": [1, {\"c\": \"x\"}], \"a\": {}}")["b"][1]["c"]"#,
+                "x",
+            ),
+            (
+                r#"json_parse("\"\\u00e9\\ud83d\\ude00\\n\"")"#,
+                "\u{e9}\u{1f600}\n",
+            ),
+            (
+                r#"json_stringify({"b": [1, 2.5, json_parse("null")], "a": "q\"\n"})"#,
+                r#"{"a":"q\"\n","b":[1,2.5,null]}"#,
+            ),
+            (
+                r#"json_stringify({1: true, false: []})"#,
+                r#"{"1":true,"false":[]}"#,
+            ),
+            (r#"json_stringify("\t")"#, r#""\t""#),
+            (
+                r#"let s = "{\"k\":[1,-2,{\"x\":\"\\u0001\"}]}"; json_stringify(json_parse(s)) == s"#,
+                "true",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input).to_string(), expected, "evaluating {}", input);
+        }
+
+        let deep = format!(r#"json_parse("{}")"#, "[".repeat(200));
+        let tests = [
+            (
+                r#"json_parse("[1, 2")"#,
+                "invalid JSON: line 1, column 6: expected ',' or ']'",
+            ),
+            (
+                r#"json_parse("{1: 2}")"#,
+                "invalid JSON: line 1, column 2: expected a string key",
+            ),
+            (
+                r#"json_parse("[1]\n x")"#,
+                "invalid JSON: line 2, column 2: unexpected trailing characters",
+            ),
+            (
+                r#"json_parse("01")"#,
+                "invalid JSON: line 1, column 2: unexpected trailing characters",
+            ),
+            (
+                r#"json_parse("tru")"#,
+                "invalid JSON: line 1, column 1: unexpected character 't'",
+            ),
+            (&deep, "invalid JSON: line 1, column 130: too deeply nested"),
+            (
+                "json_stringify(fn() { 1 })",
+                "cannot convert FUNCTION to JSON",
+            ),
+            ("json_stringify([0.0 / 0.0])", "cannot convert NaN to JSON"),
+            (
+                "json_parse(1)",
+                "argument to `json_parse` must be STRING, got INTEGER",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Error(expected.to_string()),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_puts_writes_to_the_evaluator_output() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut evaluator = Evaluator::with_output(output.clone());
+        let program = Parser::new(Lexer::new(
+            r#"puts("hello", 1 + 2, [true, "x"]); puts(); puts(fn(x) { x })"#,
+        ))
+        .parse_program();
+
+        assert_eq!(evaluator.eval(&program, &new_env()), Object::Null);
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "hello\n3\n[true, x]\nfn(x) { x }\n"
+        );
+    }
+
+    #[test]
+    fn test_recursion_depth_limit() {
+        let input = "let f = fn(n) { f(n + 1) + 1 }; f(0)";
+        assert_eq!(
+            eval(input),
+            Object::Error("maximum recursion depth exceeded".to_string())
+        );
+
+        let program = Parser::new(Lexer::new(
+            "let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }; sum(10)",
+        ))
+        .parse_program();
+        let mut evaluator = Evaluator::new();
+        assert_eq!(evaluator.eval(&program, &new_env()), Object::Integer(55));
+
+        evaluator.set_max_depth(10);
+        assert_eq!(
+            evaluator.eval(&program, &new_env()),
+            Object::Error("maximum recursion depth exceeded".to_string())
+        );
+
+        // The depth is back to 0 after the error, calls still work.
+        evaluator.set_max_depth(11);
+        assert_eq!(evaluator.eval(&program, &new_env()), Object::Integer(55));
+    }
+
+    #[test]
+    fn test_steps() {
+        let tests = [("1 + 2", 4), ("let a = 1; a", 4), ("fn(x) { x }(2)", 6)];
+
+        for (input, expected) in tests {
+            let program = Parser::new(Lexer::new(input)).parse_program();
+            let mut evaluator = Evaluator::new();
+            evaluator.eval(&program, &new_env());
+            assert_eq!(evaluator.steps(), expected, "evaluating {}", input);
+
+            // The steps add up from an evaluation to the next.
+            evaluator.eval(&program, &new_env());
+            assert_eq!(evaluator.steps(), 2 * expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_interrupt_flag() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut evaluator = Evaluator::new();
+        evaluator.set_interrupt_flag(Arc::clone(&flag));
+        // The flag is set by the program itself, as a Ctrl+C handler would
+        // while it runs. stop gives back its argument.
+        let stop = Arc::clone(&flag);
+        evaluator.register_builtin("stop", move |args: &[Object]| -> Object {
+            stop.store(true, Ordering::Relaxed);
+            args.first().cloned().unwrap_or(Object::Null)
+        });
+
+        let tests = [
+            ("1; 2", Object::Integer(2)),
+            ("stop(); 3", Object::Error("interrupted".to_string())),
+            // try can't catch it, the next statement stops too.
+            (
+                "let r = try(fn() { stop(); 1 }); 4",
+                Object::Error("interrupted".to_string()),
+            ),
+            (
+                "let f = fn(n) { if (n == 3) { stop() }; f(n + 1) }; f(0)",
+                Object::Error("interrupted".to_string()),
+            ),
+            // Loops and functions without statements stop too.
+            (
+                "for (i in stop(range(0, 3000000))) {}",
+                Object::Error("interrupted".to_string()),
+            ),
+            (
+                "map(stop([1, 2]), fn(x) {})",
+                Object::Error("interrupted".to_string()),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            flag.store(false, Ordering::Relaxed);
+            let program = Parser::new(Lexer::new(input)).parse_program();
+            assert_eq!(
+                evaluator.eval(&program, &new_env()),
+                expected,
+                "evaluating {}",
+                input
+            );
+        }
+
+        // The evaluator doesn't clear the flag.
+        let program = Parser::new(Lexer::new("5")).parse_program();
+        assert_eq!(
+            evaluator.eval(&program, &new_env()),
+            Object::Error("interrupted".to_string())
+        );
+    }
+
+    #[test]
+    fn test_register_builtin() {
+        let program = Parser::new(Lexer::new(
+            r#"let twice = fn(x) { x * 2 }; add(twice(3), 4, 5) + len("ab")"#,
+        ))
+        .parse_program();
+
+        let mut evaluator = Evaluator::new();
+        evaluator.register_builtin("add", |args: &[Object]| -> Object {
+            let mut sum = 0;
+            for arg in args {
+                match arg {
+                    Object::Integer(value) => sum += value,
+                    arg => {
+                        return Object::Error(format!("cannot add {}", arg.type_name()));
+                    }
+                }
+            }
+            Object::Integer(sum)
+        });
+
+        assert_eq!(evaluator.eval(&program, &new_env()), Object::Integer(17));
+        assert_eq!(
+            evaluator.eval(
+                &Parser::new(Lexer::new("add(1, true)")).parse_program(),
+                &new_env()
+            ),
+            Object::Error("cannot add BOOLEAN".to_string())
+        );
+
+        // The registry belongs to the evaluator, the others don't see it.
+        assert_eq!(
+            Evaluator::new().eval(&program, &new_env()),
+            Object::Error("identifier not found: add".to_string())
+        );
+
+        // A standard builtin can be replaced.
+        let counter = Rc::new(RefCell::new(0));
+        let calls = counter.clone();
+        evaluator.register_builtin("len", move |_: &[Object]| {
+            *calls.borrow_mut() += 1;
+            Object::Integer(0)
+        });
+        assert_eq!(evaluator.eval(&program, &new_env()), Object::Integer(15));
+        assert_eq!(*counter.borrow(), 1);
+    }
+
+    #[test]
+    fn test_collect_garbage_frees_cycles() {
+        let mut evaluator = Evaluator::new();
+        let env = new_env();
+        let run = |evaluator: &mut Evaluator, input: &str| {
+            evaluator.eval(&Parser::new(Lexer::new(input)).parse_program(), &env)
+        };
+
+        // Each call of f makes a scope holding g, which holds the scope.
+        run(
+            &mut evaluator,
+            "let f = fn() { let g = fn(n) { if (n > 0) { g(n - 1) } else { 0 } }; g(2) }; f(); f();",
+        );
+        // The scopes of the calls to g and of the ifs are already dropped,
+        // only the ones of the calls to f are left in a cycle.
+        assert_eq!(evaluator.collect_garbage(&[&env]), 2);
+        assert_eq!(evaluator.collect_garbage(&[&env]), 0);
+
+        // A closure bound in the roots still works after a collection.
+        run(
+            &mut evaluator,
+            "let adder = fn(x) { fn(y) { x + y } }; let addTwo = adder(2);",
+        );
+        assert_eq!(evaluator.collect_garbage(&[&env]), 0);
+        assert_eq!(run(&mut evaluator, "addTwo(3)"), Object::Integer(5));
+        assert_eq!(run(&mut evaluator, "f()"), Object::Integer(0));
+    }
+
+    #[test]
+    fn test_assignments() {
+        let tests = [
+            ("let x = 1; x = x + 1; x", 2),
+            ("let x = 1; x = 5", 5),
+            ("let x = 1; let y = x = 3; x + y", 6),
+            ("let x; x = 4; x", 4),
+            // The binding of the enclosing scope is changed.
+            ("let n = 0; let inc = fn() { n = n + 1 }; inc(); inc(); n", 2),
+            ("let n = 0; if (true) { n = 7; } n", 7),
+            // Unless it is shadowed.
+            ("let n = 0; let f = fn(n) { n = n + 1; n }; f(10) + n", 11),
+            (
+                "let counter = fn() { let c = 0; fn() { c = c + 1 } }; let next = counter(); next(); next()",
+                2,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Integer(expected),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let result = eval(
+            r#"let two = "two"; {"one": 10 - 9, two: 1 + 1, "thr" + "ee": 6 / 2, 4: 4, true: 5, false: 6}"#,
+        );
+
+        assert_eq!(
+            result.to_string(),
+            "{4: 4, false: 6, true: 5, one: 1, three: 3, two: 2}"
+        );
+        let Object::Hash(pairs) = result else {
+            panic!("expected a hash");
+        };
+        assert_eq!(
+            pairs.get(&HashKey::Str("one".to_string())),
+            Some(&Object::Integer(1))
+        );
+        assert_eq!(
+            pairs.get(&HashKey::Boolean(false)),
+            Some(&Object::Integer(6))
+        );
+    }
+
+    #[test]
+    fn test_hash_index_expressions() {
+        let tests = [
+            (r#"{"foo": 5}["foo"]"#, Object::Integer(5)),
+            (r#"{"foo": 5}["bar"]"#, Object::Null),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Object::Integer(5)),
+            (r#"{}["foo"]"#, Object::Null),
+            ("{5: 5}[5]", Object::Integer(5)),
+            ("{true: 5}[true]", Object::Integer(5)),
+            ("{1: 1, 1: 2}[1]", Object::Integer(2)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_for_in_statements() {
+        let tests = [
+            ("let sum = 0; for (x in [1, 2, 3]) { sum = sum + x; } sum", Object::Integer(6)),
+            ("let sum = 0; for (i in range(5)) { sum = sum + i; } sum", Object::Integer(10)),
+            ("let sum = 0; for (i in range(3, 1)) { sum = sum + i; } sum", Object::Integer(0)),
+            (
+                r#"let s = ""; for (p in {"a": 1, "b": 2}) { s = s + p[0]; } s"#,
+                Object::Str("ab".to_string()),
+            ),
+            (
+                r#"let n = 0; for (p in {"a": 1, "b": 2}) { n = n + p[1]; } n"#,
+                Object::Integer(3),
+            ),
+            ("for (x in []) { x } ", Object::Null),
+            // A return stops the loop and the function.
+            (
+                "let find = fn(xs) { for (x in xs) { if (x > 1) { return x; } } -1 }; find([0, 5, 7])",
+                Object::Integer(5),
+            ),
+            // Each iteration has its own scope, closures keep their item.
+            (
+                "let fs = []; for (x in [1, 2]) { fs = push(fs, fn() { x }); } fs[0]() + fs[1]() * 10",
+                Object::Integer(21),
+            ),
+            ("for (x in [1]) { let y = x; } x", Object::Error("identifier not found: x".to_string())),
+            ("len(range(2, 7))", Object::Integer(5)),
+            ("len(range(7, 2))", Object::Integer(0)),
+            (
+                "len(range(9223372036854775807, -2))",
+                Object::Integer(0),
+            ),
+            (
+                "len(range(-2, 9223372036854775807))",
+                Object::Error("integer overflow: len(range(-2, 9223372036854775807))".to_string()),
+            ),
+            (r#"len({"a": 1})"#, Object::Integer(1)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input), expected, "evaluating {}", input);
+        }
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let tests = [
+            ("foo", "identifier not found: foo"),
+            ("let a = foo; a", "identifier not found: foo"),
+            ("foo; 5", "identifier not found: foo"),
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
+            (
+                "if (10 > 1) { true + false; }",
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            (
+                "if (10 > 1) { if (10 > 1) { return true + false; } return 1; }",
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            ("1 / 0", "division by zero"),
+            ("1 % 0", "modulo by zero"),
+            ("2 ** -1", "negative exponent: 2 ** -1"),
+            ("2 ** 64", "integer overflow: 2 ** 64"),
+            ("true % 2", "type mismatch: BOOLEAN % INTEGER"),
+            (r#""a" ** "b""#, "unknown operator: STRING ** STRING"),
+            (r#""a" - "b""#, "unknown operator: STRING - STRING"),
+            (r#""a" + 1"#, "type mismatch: STRING + INTEGER"),
+            (r#"1 < "a""#, "type mismatch: INTEGER < STRING"),
+            (r#""a" > 1.5"#, "type mismatch: STRING > FLOAT"),
+            ("true < false", "unknown operator: BOOLEAN < BOOLEAN"),
+            ("1.5 + true", "type mismatch: FLOAT + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            (
+                "let f = fn(x) { x }; f(1, 2)",
+                "wrong number of arguments: want=1, got=2",
+            ),
+            ("5(1)", "not a function: INTEGER"),
+            ("1[0]", "index operator not supported: INTEGER"),
+            ("x = 1", "assignment to undeclared identifier: x"),
+            (
+                "let f = fn() { y = 1 }; f()",
+                "assignment to undeclared identifier: y",
+            ),
+            ("const c = 1; c = 2", "assignment to constant: c"),
+            (
+                "let a = [1]; a[0] = 2",
+                "unsupported assignment target: Index",
+            ),
+            ("[1, foo, 2]", "identifier not found: foo"),
+            (
+                r#"{"name": "Monkey"}[fn(x) { x }];"#,
+                "unusable as hash key: FUNCTION",
+            ),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("for (x in 5) { x }", "cannot iterate over INTEGER"),
+            (
+                "for (x in [1, 2]) { x + true }",
+                "type mismatch: INTEGER + BOOLEAN",
+            ),
+            (
+                r#"range("a")"#,
+                "argument to `range` must be INTEGER, got STRING",
+            ),
+            (
+                "let f = fn() { return foo; 1 }; f()",
+                "identifier not found: foo",
+            ),
+            (
+                "9223372036854775807 + 1",
+                "integer overflow: 9223372036854775807 + 1",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Error(expected.to_string()),
+                "evaluating {}",
+                input
+            );
+        }
+        assert_eq!(eval("foo").to_string(), "ERROR: identifier not found: foo");
+    }
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "io")]
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use redhowler::interpreter::environment::Environment;
+    use redhowler::interpreter::evaluator;
+    use redhowler::interpreter::lexer::Lexer;
+    use redhowler::interpreter::object::Object;
+    use redhowler::interpreter::parser::Parser;
+
+    fn eval(input: &str) -> Object {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+        let env = Rc::new(RefCell::new(Environment::new()));
+        evaluator::eval(&program, &env)
+    }
+
+    #[test]
+    fn test_write_and_read_file() {
+        let dir = std::env::temp_dir().join(format!("redhowler-io-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.txt");
+        let path = path.to_str().unwrap();
+
+        let input = format!(
+            r#"let path = "{}"; write_file(path, "a\nb"); split(read_file(path), "\n")"#,
+            path
+        );
+        let result = eval(&input);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            result,
+            Object::Array(vec![
+                Object::Str("a".to_string()),
+                Object::Str("b".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_io_errors() {
+        let missing = std::env::temp_dir().join("redhowler-io-missing/none.txt");
+        let missing = missing.to_str().unwrap();
+
+        let result = eval(&format!(r#"read_file("{}")"#, missing));
+        assert!(
+            matches!(&result, Object::Error(msg) if msg.starts_with(&format!("could not read {}: ", missing))),
+            "{}",
+            result
+        );
+        let result = eval(&format!(r#"write_file("{}", "x")"#, missing));
+        assert!(
+            matches!(&result, Object::Error(msg) if msg.starts_with(&format!("could not write {}: ", missing))),
+            "{}",
+            result
+        );
+
+        let tests = [
+            (
+                "read_file(1)",
+                "argument to `read_file` must be STRING, got INTEGER",
+            ),
+            (
+                r#"write_file("a", 1)"#,
+                "arguments to `write_file` must be STRING, STRING, got STRING, INTEGER",
+            ),
+            ("read_line(1)", "wrong number of arguments: want=0, got=1"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(
+                eval(input),
+                Object::Error(expected.to_string()),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+}
@@ -2,7 +2,7 @@
 mod tests {
 
     use redhowler::interpreter::lexer::Lexer;
-    use redhowler::interpreter::token::{Token, TokenType};
+    use redhowler::interpreter::token::{Token, TokenType, Trivia, TriviaKind};
 
     #[test]
     fn test_whitespace() {
@@ -22,42 +22,15 @@ mod tests {
         let input = "=+(){},;";
 
         let tests = vec![
-            Token {
-                token_type: TokenType::Assign,
-                literal: String::from("="),
-            },
-            Token {
-                token_type: TokenType::Plus,
-                literal: String::from("+"),
-            },
-            Token {
-                token_type: TokenType::LParen,
-                literal: String::from("("),
-            },
-            Token {
-                token_type: TokenType::RParen,
-                literal: String::from(")"),
-            },
-            Token {
-                token_type: TokenType::LBrace,
-                literal: String::from("{"),
-            },
-            Token {
-                token_type: TokenType::RBrace,
-                literal: String::from("}"),
-            },
-            Token {
-                token_type: TokenType::Comma,
-                literal: String::from(","),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::EOF,
-                literal: String::from("\0"),
-            },
+            Token::new(TokenType::Assign, "="),
+            Token::new(TokenType::Plus, "+"),
+            Token::new(TokenType::LParen, "("),
+            Token::new(TokenType::RParen, ")"),
+            Token::new(TokenType::LBrace, "{"),
+            Token::new(TokenType::RBrace, "}"),
+            Token::new(TokenType::Comma, ","),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::EOF, "\0"),
         ];
 
         let mut l = Lexer::new(input);
@@ -80,7 +53,7 @@ mod tests {
             };
 
             let result = add(five, ten);
-            !-/*5;
+            !-/ *5;
             5 < 10 > 5;
 
             if (5 < 10) {
@@ -93,309 +66,538 @@ mod tests {
             10 != 9;
             ";
         let tests = vec![
-            Token {
-                token_type: TokenType::Let,
-                literal: String::from("let"),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("five"),
-            },
-            Token {
-                token_type: TokenType::Assign,
-                literal: String::from("="),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("5"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::Let,
-                literal: String::from("let"),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("ten"),
-            },
-            Token {
-                token_type: TokenType::Assign,
-                literal: String::from("="),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("10"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::Let,
-                literal: String::from("let"),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("add"),
-            },
-            Token {
-                token_type: TokenType::Assign,
-                literal: String::from("="),
-            },
-            Token {
-                token_type: TokenType::Function,
-                literal: String::from("fn"),
-            },
-            Token {
-                token_type: TokenType::LParen,
-                literal: String::from("("),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("x"),
-            },
-            Token {
-                token_type: TokenType::Comma,
-                literal: String::from(","),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("y"),
-            },
-            Token {
-                token_type: TokenType::RParen,
-                literal: String::from(")"),
-            },
-            Token {
-                token_type: TokenType::LBrace,
-                literal: String::from("{"),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("x"),
-            },
-            Token {
-                token_type: TokenType::Plus,
-                literal: String::from("+"),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("y"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::RBrace,
-                literal: String::from("}"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::Let,
-                literal: String::from("let"),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("result"),
-            },
-            Token {
-                token_type: TokenType::Assign,
-                literal: String::from("="),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("add"),
-            },
-            Token {
-                token_type: TokenType::LParen,
-                literal: String::from("("),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("five"),
-            },
-            Token {
-                token_type: TokenType::Comma,
-                literal: String::from(","),
-            },
-            Token {
-                token_type: TokenType::Ident,
-                literal: String::from("ten"),
-            },
-            Token {
-                token_type: TokenType::RParen,
-                literal: String::from(")"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::Bang,
-                literal: String::from("!"),
-            },
-            Token {
-                token_type: TokenType::Minus,
-                literal: String::from("-"),
-            },
-            Token {
-                token_type: TokenType::Slash,
-                literal: String::from("/"),
-            },
-            Token {
-                token_type: TokenType::Asterisk,
-                literal: String::from("*"),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("5"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("5"),
-            },
-            Token {
-                token_type: TokenType::LT,
-                literal: String::from("<"),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("10"),
-            },
-            Token {
-                token_type: TokenType::GT,
-                literal: String::from(">"),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("5"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::If,
-                literal: String::from("if"),
-            },
-            Token {
-                token_type: TokenType::LParen,
-                literal: String::from("("),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("5"),
-            },
-            Token {
-                token_type: TokenType::LT,
-                literal: String::from("<"),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("10"),
-            },
-            Token {
-                token_type: TokenType::RParen,
-                literal: String::from(")"),
-            },
-            Token {
-                token_type: TokenType::LBrace,
-                literal: String::from("{"),
-            },
-            Token {
-                token_type: TokenType::Return,
-                literal: String::from("return"),
-            },
-            Token {
-                token_type: TokenType::True,
-                literal: String::from("true"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::RBrace,
-                literal: String::from("}"),
-            },
-            Token {
-                token_type: TokenType::Else,
-                literal: String::from("else"),
-            },
-            Token {
-                token_type: TokenType::LBrace,
-                literal: String::from("{"),
-            },
-            Token {
-                token_type: TokenType::Return,
-                literal: String::from("return"),
-            },
-            Token {
-                token_type: TokenType::False,
-                literal: String::from("false"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::RBrace,
-                literal: String::from("}"),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("10"),
-            },
-            Token {
-                token_type: TokenType::Equal,
-                literal: String::from("=="),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("10"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("10"),
-            },
-            Token {
-                token_type: TokenType::NotEqual,
-                literal: String::from("!="),
-            },
-            Token {
-                token_type: TokenType::Int,
-                literal: String::from("9"),
-            },
-            Token {
-                token_type: TokenType::Semicolon,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: TokenType::EOF,
-                literal: String::from("\0"),
-            },
+            Token::new(TokenType::Let, "let"),
+            Token::new(TokenType::Ident, "five"),
+            Token::new(TokenType::Assign, "="),
+            Token::new(TokenType::Int, "5"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::Let, "let"),
+            Token::new(TokenType::Ident, "ten"),
+            Token::new(TokenType::Assign, "="),
+            Token::new(TokenType::Int, "10"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::Let, "let"),
+            Token::new(TokenType::Ident, "add"),
+            Token::new(TokenType::Assign, "="),
+            Token::new(TokenType::Function, "fn"),
+            Token::new(TokenType::LParen, "("),
+            Token::new(TokenType::Ident, "x"),
+            Token::new(TokenType::Comma, ","),
+            Token::new(TokenType::Ident, "y"),
+            Token::new(TokenType::RParen, ")"),
+            Token::new(TokenType::LBrace, "{"),
+            Token::new(TokenType::Ident, "x"),
+            Token::new(TokenType::Plus, "+"),
+            Token::new(TokenType::Ident, "y"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::RBrace, "}"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::Let, "let"),
+            Token::new(TokenType::Ident, "result"),
+            Token::new(TokenType::Assign, "="),
+            Token::new(TokenType::Ident, "add"),
+            Token::new(TokenType::LParen, "("),
+            Token::new(TokenType::Ident, "five"),
+            Token::new(TokenType::Comma, ","),
+            Token::new(TokenType::Ident, "ten"),
+            Token::new(TokenType::RParen, ")"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::Bang, "!"),
+            Token::new(TokenType::Minus, "-"),
+            Token::new(TokenType::Slash, "/"),
+            Token::new(TokenType::Asterisk, "*"),
+            Token::new(TokenType::Int, "5"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::Int, "5"),
+            Token::new(TokenType::LT, "<"),
+            Token::new(TokenType::Int, "10"),
+            Token::new(TokenType::GT, ">"),
+            Token::new(TokenType::Int, "5"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::If, "if"),
+            Token::new(TokenType::LParen, "("),
+            Token::new(TokenType::Int, "5"),
+            Token::new(TokenType::LT, "<"),
+            Token::new(TokenType::Int, "10"),
+            Token::new(TokenType::RParen, ")"),
+            Token::new(TokenType::LBrace, "{"),
+            Token::new(TokenType::Return, "return"),
+            Token::new(TokenType::True, "true"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::RBrace, "}"),
+            Token::new(TokenType::Else, "else"),
+            Token::new(TokenType::LBrace, "{"),
+            Token::new(TokenType::Return, "return"),
+            Token::new(TokenType::False, "false"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::RBrace, "}"),
+            Token::new(TokenType::Int, "10"),
+            Token::new(TokenType::Equal, "=="),
+            Token::new(TokenType::Int, "10"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::Int, "10"),
+            Token::new(TokenType::NotEqual, "!="),
+            Token::new(TokenType::Int, "9"),
+            Token::new(TokenType::Semicolon, ";"),
+            Token::new(TokenType::EOF, "\0"),
         ];
 
-        let mut l = Lexer::new(&input);
+        let mut l = Lexer::new(input);
         for tt in tests {
             let tok = l.next_token();
             assert_eq!(tok.literal, tt.literal);
             assert_eq!(tok.token_type, tt.token_type);
         }
     }
+
+    #[test]
+    fn test_string_literals() {
+        let input = r#""foobar" "foo bar" "" "a\n\tb" "say \"hi\"" "back\\slash""#;
+
+        let tests = [
+            (TokenType::String, "foobar"),
+            (TokenType::String, "foo bar"),
+            (TokenType::String, ""),
+            (TokenType::String, "a\n\tb"),
+            (TokenType::String, "say \"hi\""),
+            (TokenType::String, "back\\slash"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_string_invalid_escape() {
+        // An unknown escape produces an Illegal token holding the escape, and
+        // the rest of the string is skipped so lexing can continue.
+        let input = r#""a\qb\"c" 5 "never closed"#;
+
+        let tests = [
+            (TokenType::Illegal, "\\q"),
+            (TokenType::Int, "5"),
+            (TokenType::Illegal, "\"never closed"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_float_literals() {
+        let input = "3.14 0.5 10 1e10 2.5E-3 6e+2 3. 7e";
+
+        let tests = [
+            (TokenType::Float, "3.14"),
+            (TokenType::Float, "0.5"),
+            (TokenType::Int, "10"),
+            (TokenType::Float, "1e10"),
+            (TokenType::Float, "2.5E-3"),
+            (TokenType::Float, "6e+2"),
+            (TokenType::Int, "3"),
+            (TokenType::Dot, "."),
+            (TokenType::Int, "7"),
+            (TokenType::Ident, "e"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_line_comments() {
+        let input = "
+            // A comment on its own line
+            let x = 5; // a trailing comment
+            x / 2 //no space and EOF";
+
+        let tests = [
+            (TokenType::Let, "let"),
+            (TokenType::Ident, "x"),
+            (TokenType::Assign, "="),
+            (TokenType::Int, "5"),
+            (TokenType::Semicolon, ";"),
+            (TokenType::Ident, "x"),
+            (TokenType::Slash, "/"),
+            (TokenType::Int, "2"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_modulo_and_power() {
+        let tests = [
+            (TokenType::Int, "7"),
+            (TokenType::Percent, "%"),
+            (TokenType::Int, "2"),
+            (TokenType::Power, "**"),
+            (TokenType::Int, "3"),
+            (TokenType::Asterisk, "*"),
+            (TokenType::Int, "4"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new("7 % 2 ** 3 * 4");
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let input = "
+            /* A block comment
+               on several lines */
+            let /* inline */ x = 5 * /**/ 2;
+            /* outer /* nested */ still a comment */ x
+            /* never closed";
+
+        let tests = [
+            (TokenType::Let, "let"),
+            (TokenType::Ident, "x"),
+            (TokenType::Assign, "="),
+            (TokenType::Int, "5"),
+            (TokenType::Asterisk, "*"),
+            (TokenType::Int, "2"),
+            (TokenType::Semicolon, ";"),
+            (TokenType::Ident, "x"),
+            (TokenType::Illegal, "/*"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_radix_integer_literals() {
+        let input = "0xFF 0Xab 0b1010 0o755 0 0x 0b12";
+
+        let tests = [
+            (TokenType::Int, "0xFF"),
+            (TokenType::Int, "0Xab"),
+            (TokenType::Int, "0b1010"),
+            (TokenType::Int, "0o755"),
+            (TokenType::Int, "0"),
+            (TokenType::Illegal, "0x"),
+            (TokenType::Int, "0b1"),
+            (TokenType::Int, "2"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let input = "a && b || c & d | e";
+
+        let tests = [
+            (TokenType::Ident, "a"),
+            (TokenType::And, "&&"),
+            (TokenType::Ident, "b"),
+            (TokenType::Or, "||"),
+            (TokenType::Ident, "c"),
+            (TokenType::Illegal, "&"),
+            (TokenType::Ident, "d"),
+            (TokenType::Pipe, "|"),
+            (TokenType::Ident, "e"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_brackets() {
+        let input = "[1, 2][0]";
+
+        let tests = [
+            (TokenType::LBracket, "["),
+            (TokenType::Int, "1"),
+            (TokenType::Comma, ","),
+            (TokenType::Int, "2"),
+            (TokenType::RBracket, "]"),
+            (TokenType::LBracket, "["),
+            (TokenType::Int, "0"),
+            (TokenType::RBracket, "]"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_hash_literal_tokens() {
+        let input = r#"{"a": 1, "b": 2}"#;
+
+        let tests = [
+            (TokenType::LBrace, "{"),
+            (TokenType::String, "a"),
+            (TokenType::Colon, ":"),
+            (TokenType::Int, "1"),
+            (TokenType::Comma, ","),
+            (TokenType::String, "b"),
+            (TokenType::Colon, ":"),
+            (TokenType::Int, "2"),
+            (TokenType::RBrace, "}"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_token_positions() {
+        let input = "let x = \"hi\";\n  x == 10 /* unclosed";
+
+        // (token type, line, column, start, end)
+        let tests = [
+            (TokenType::Let, 1, 1, 0, 3),
+            (TokenType::Ident, 1, 5, 4, 5),
+            (TokenType::Assign, 1, 7, 6, 7),
+            (TokenType::String, 1, 9, 8, 12),
+            (TokenType::Semicolon, 1, 13, 12, 13),
+            (TokenType::Ident, 2, 3, 16, 17),
+            (TokenType::Equal, 2, 5, 18, 20),
+            (TokenType::Int, 2, 8, 21, 23),
+            (TokenType::Illegal, 2, 11, 24, 26),
+            (TokenType::EOF, 2, 22, 35, 35),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, line, column, start, end) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!((tok.position.line, tok.position.column), (line, column));
+            assert_eq!((tok.span.start, tok.span.end), (start, end));
+        }
+    }
+
+    #[test]
+    fn test_lexer_errors() {
+        let input = "let @ = \"a\\qb\";\n0x é \"open";
+
+        let mut l = Lexer::new(input);
+        loop {
+            let tok = l.next_token();
+            if tok.token_type == TokenType::EOF {
+                break;
+            }
+        }
+
+        let errors: Vec<String> = l.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "line 1, column 5: unexpected character '@'",
+                "line 1, column 9: unknown escape sequence '\\q'",
+                "line 2, column 1: missing digits after '0x'",
+                "line 2, column 4: unexpected character 'é'",
+                "line 2, column 6: unterminated string",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trivia() {
+        let input = "let x = 5; // five\n/* doc */ x";
+
+        let mut l = Lexer::with_trivia(input);
+        let tokens: Vec<Token> = (0..7).map(|_| l.next_token()).collect();
+
+        // (kind, text) of each trivia.
+        fn pieces(trivia: &[Trivia]) -> Vec<(TriviaKind, &str)> {
+            trivia.iter().map(|t| (t.kind, t.text.as_str())).collect()
+        }
+
+        assert!(tokens[0].leading_trivia.is_empty());
+        assert_eq!(
+            pieces(&tokens[0].trailing_trivia),
+            [(TriviaKind::Whitespace, " ")]
+        );
+        assert_eq!(
+            pieces(&tokens[4].trailing_trivia),
+            [
+                (TriviaKind::Whitespace, " "),
+                (TriviaKind::LineComment, "// five"),
+            ]
+        );
+        assert_eq!(tokens[5].literal, "x");
+        assert_eq!(
+            pieces(&tokens[5].leading_trivia),
+            [
+                (TriviaKind::Whitespace, "\n"),
+                (TriviaKind::BlockComment, "/* doc */"),
+                (TriviaKind::Whitespace, " "),
+            ]
+        );
+        assert!(tokens[5].trailing_trivia.is_empty());
+        assert_eq!(tokens[6].token_type, TokenType::EOF);
+
+        // All the source is kept, so it can be rebuilt from the tokens.
+        let mut rebuilt = String::new();
+        for tok in tokens.iter() {
+            tok.leading_trivia
+                .iter()
+                .for_each(|t| rebuilt.push_str(&t.text));
+            rebuilt.push_str(&input[tok.span.start..tok.span.end]);
+            tok.trailing_trivia
+                .iter()
+                .for_each(|t| rebuilt.push_str(&t.text));
+        }
+        assert_eq!(rebuilt, input);
+
+        // Without trivia mode whitespace is not kept.
+        let tok = Lexer::new(input).next_token();
+        assert!(tok.leading_trivia.is_empty() && tok.trailing_trivia.is_empty());
+    }
+
+    #[test]
+    fn test_leading_comments() {
+        let input = "
+            // Doc comment of x
+            // on two lines.
+            let x = 5; // Trailing comment of the semicolon
+            /* Doc comment of y */
+            let y = x;";
+
+        let mut l = Lexer::new(input);
+        let tokens: Vec<Token> = (0..11).map(|_| l.next_token()).collect();
+
+        let comments =
+            |tok: &Token| -> Vec<String> { tok.leading_comments().map(String::from).collect() };
+
+        assert_eq!(tokens[0].literal, "let");
+        assert_eq!(
+            comments(&tokens[0]),
+            ["// Doc comment of x", "// on two lines."]
+        );
+        assert!(comments(&tokens[1]).is_empty());
+        assert_eq!(tokens[4].literal, ";");
+        assert_eq!(
+            tokens[4].trailing_trivia[0].text,
+            "// Trailing comment of the semicolon"
+        );
+        assert_eq!(tokens[5].literal, "let");
+        assert_eq!(comments(&tokens[5]), ["/* Doc comment of y */"]);
+    }
+
+    #[test]
+    fn test_ternary_tokens() {
+        let input = "a ? b : c";
+
+        let tests = [
+            (TokenType::Ident, "a"),
+            (TokenType::Question, "?"),
+            (TokenType::Ident, "b"),
+            (TokenType::Colon, ":"),
+            (TokenType::Ident, "c"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_identifiers_with_underscores() {
+        let types_and_literals: Vec<(TokenType, String)> = Lexer::new("has_key _x a_ let_")
+            .map(|t| (t.token_type, t.literal))
+            .collect();
+
+        assert_eq!(
+            types_and_literals,
+            [
+                (TokenType::Ident, "has_key".to_string()),
+                (TokenType::Ident, "_x".to_string()),
+                (TokenType::Ident, "a_".to_string()),
+                (TokenType::Ident, "let_".to_string()),
+                (TokenType::EOF, "\0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_increment_decrement_tokens() {
+        let input = "i++; j--; a + +b; a - -b";
+
+        let tests = [
+            (TokenType::Ident, "i"),
+            (TokenType::PlusPlus, "++"),
+            (TokenType::Semicolon, ";"),
+            (TokenType::Ident, "j"),
+            (TokenType::MinusMinus, "--"),
+            (TokenType::Semicolon, ";"),
+            (TokenType::Ident, "a"),
+            (TokenType::Plus, "+"),
+            (TokenType::Plus, "+"),
+            (TokenType::Ident, "b"),
+            (TokenType::Semicolon, ";"),
+            (TokenType::Ident, "a"),
+            (TokenType::Minus, "-"),
+            (TokenType::Minus, "-"),
+            (TokenType::Ident, "b"),
+            (TokenType::EOF, "\0"),
+        ];
+
+        let mut l = Lexer::new(input);
+        for (token_type, literal) in tests {
+            let tok = l.next_token();
+            assert_eq!(tok.token_type, token_type);
+            assert_eq!(tok.literal, literal);
+        }
+    }
 }
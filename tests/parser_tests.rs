@@ -2,9 +2,13 @@
 mod tests {
 
     use redhowler::interpreter::ast;
-    use redhowler::interpreter::ast::{ExpressionStatement, LetStatement, PrefixExpression};
+    use redhowler::interpreter::ast::Node;
+    use redhowler::interpreter::ast::{Expression, Statement};
     use redhowler::interpreter::lexer::Lexer;
-    use redhowler::interpreter::parser::Parser;
+    use redhowler::interpreter::parser::{
+        Parser, ParserError, ParserWarning, Precedence, DEFAULT_MAX_DEPTH,
+    };
+    use redhowler::interpreter::token::{Position, Span, Token, TokenType, Trivia};
 
     #[test]
     fn test_operator_precedence_parsing() {
@@ -43,6 +47,18 @@ mod tests {
                 input: "a + b / c",
                 expected: "(a + (b / c))",
             },
+            OperatorPrecedenceTest {
+                input: "a * b % c",
+                expected: "((a * b) % c)",
+            },
+            OperatorPrecedenceTest {
+                input: "a * b ** c ** d",
+                expected: "(a * (b ** (c ** d)))",
+            },
+            OperatorPrecedenceTest {
+                input: "-a ** b",
+                expected: "(-(a ** b))",
+            },
             OperatorPrecedenceTest {
                 input: "a + b * c + d / e - f",
                 expected: "(((a + (b * c)) + (d / e)) - f)",
@@ -63,6 +79,143 @@ mod tests {
                 input: "3 + 4 * 5 == 3 * 1 + 4 * 5",
                 expected: "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
             },
+            OperatorPrecedenceTest {
+                input: "1 + (2 + 3) + 4",
+                expected: "((1 + (2 + 3)) + 4)",
+            },
+            OperatorPrecedenceTest {
+                input: "(5 + 5) * 2",
+                expected: "((5 + 5) * 2)",
+            },
+            OperatorPrecedenceTest {
+                input: "2 / (5 + 5)",
+                expected: "(2 / (5 + 5))",
+            },
+            OperatorPrecedenceTest {
+                input: "-(5 + 5)",
+                expected: "(-(5 + 5))",
+            },
+            OperatorPrecedenceTest {
+                input: "!(a == b)",
+                expected: "(!(a == b))",
+            },
+            OperatorPrecedenceTest {
+                input: "a && (b || c)",
+                expected: "(a && (b || c))",
+            },
+            OperatorPrecedenceTest {
+                input: "a + add(b * c) + d",
+                expected: "((a + add((b * c))) + d)",
+            },
+            OperatorPrecedenceTest {
+                input: "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+                expected: "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+            },
+            OperatorPrecedenceTest {
+                input: "-f()",
+                expected: "(-f())",
+            },
+            OperatorPrecedenceTest {
+                input: "a * [1, 2, 3, 4][b * c] * d",
+                expected: "((a * ([1, 2, 3, 4][(b * c)])) * d)",
+            },
+            OperatorPrecedenceTest {
+                input: "add(a * b[2], b[1], 2 * [1, 2][1])",
+                expected: "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
+            },
+            OperatorPrecedenceTest {
+                input: "-a[0]",
+                expected: "(-(a[0]))",
+            },
+            OperatorPrecedenceTest {
+                input: "f(x)[0][1]",
+                expected: "((f(x)[0])[1])",
+            },
+            OperatorPrecedenceTest {
+                input: "i++",
+                expected: "(i++)",
+            },
+            OperatorPrecedenceTest {
+                input: "-i-- * 2",
+                expected: "((-(i--)) * 2)",
+            },
+            OperatorPrecedenceTest {
+                input: "a[0]++ + b.c--",
+                expected: "(((a[0])++) + ((b.c)--))",
+            },
+            OperatorPrecedenceTest {
+                input: "i++ - 1",
+                expected: "((i++) - 1)",
+            },
+            // Before an operand -- is two minus signs.
+            OperatorPrecedenceTest {
+                input: "--5",
+                expected: "(-(-5))",
+            },
+            OperatorPrecedenceTest {
+                input: "!--5 * 2",
+                expected: "((!(-(-5))) * 2)",
+            },
+            OperatorPrecedenceTest {
+                input: "a - --b[0]",
+                expected: "(a - (-(-(b[0]))))",
+            },
+            OperatorPrecedenceTest {
+                input: "hash.keys",
+                expected: "(hash.keys)",
+            },
+            OperatorPrecedenceTest {
+                input: "arr.len() + 1",
+                expected: "((arr.len()) + 1)",
+            },
+            OperatorPrecedenceTest {
+                input: "-a.b * c.d(1, 2 * 3)",
+                expected: "((-(a.b)) * (c.d(1, (2 * 3))))",
+            },
+            OperatorPrecedenceTest {
+                input: "a.b.c(x).d",
+                expected: "(((a.b).c(x)).d)",
+            },
+            OperatorPrecedenceTest {
+                input: "a[0].len()",
+                expected: "((a[0]).len())",
+            },
+            OperatorPrecedenceTest {
+                input: "f(x).y",
+                expected: "(f(x).y)",
+            },
+            OperatorPrecedenceTest {
+                input: "a ? b : c",
+                expected: "(a ? b : c)",
+            },
+            OperatorPrecedenceTest {
+                input: "a < b || c ? x + 1 : y * 2",
+                expected: "(((a < b) || c) ? (x + 1) : (y * 2))",
+            },
+            OperatorPrecedenceTest {
+                input: "a ? b : c ? d : e",
+                expected: "(a ? b : (c ? d : e))",
+            },
+            OperatorPrecedenceTest {
+                input: "a ? b ? c : d : e",
+                expected: "(a ? (b ? c : d) : e)",
+            },
+            OperatorPrecedenceTest {
+                input: "f(a ? 1 : 2, b)",
+                expected: "f((a ? 1 : 2), b)",
+            },
+            OperatorPrecedenceTest {
+                input: "a == b && c < d || e",
+                expected: "(((a == b) && (c < d)) || e)",
+            },
+            OperatorPrecedenceTest {
+                input: "a || b && c",
+                expected: "(a || (b && c))",
+            },
+            OperatorPrecedenceTest {
+                input: "a && b && c || d || e",
+                expected: "((((a && b) && c) || d) || e)",
+            },
         ];
 
         for tt in tests.iter() {
@@ -76,7 +229,7 @@ mod tests {
             p.errors.iter().for_each(|e| eprintln!("{}", e));
             assert!(p.errors.is_empty());
 
-            assert_eq!(program.string(), tt.expected);
+            assert_eq!(program.to_string(), tt.expected);
         }
     }
     #[test]
@@ -89,7 +242,7 @@ mod tests {
             right_value: i64,
         }
 
-        let infix_tests = vec![
+        let infix_tests = [
             InfixTest {
                 input: "5 + 5;",
                 left_value: 5,
@@ -153,24 +306,17 @@ mod tests {
 
             assert_eq!(program.statements.len(), 1);
 
-            let stmt = program.statements.get(0).unwrap();
-            if let Some(expr_stmt) = stmt.as_any().downcast_ref::<ExpressionStatement>() {
-                if let Some(infix_expr) = expr_stmt
-                    .expression
-                    .as_any()
-                    .downcast_ref::<ast::InfixExpression>()
-                {
-                    let left = infix_expr
-                        .left
-                        .as_any()
-                        .downcast_ref::<ast::IntegerLiteral>();
-                    let right = infix_expr
-                        .right
-                        .as_any()
-                        .downcast_ref::<ast::IntegerLiteral>();
+            let stmt = program.statements.first().unwrap();
+            if let Statement::Expression(expr_stmt) = stmt {
+                if let Expression::Infix(infix_expr) = expr_stmt.expression.as_ref() {
+                    let (Expression::IntegerLiteral(left), Expression::IntegerLiteral(right)) =
+                        (infix_expr.left.as_ref(), infix_expr.right.as_ref())
+                    else {
+                        panic!("Expected IntegerLiteral operands");
+                    };
                     assert_eq!(infix_expr.operator, tt.operator);
-                    assert_eq!(left.unwrap().value(), tt.left_value);
-                    assert_eq!(right.unwrap().value(), tt.right_value);
+                    assert_eq!(left.value(), tt.left_value);
+                    assert_eq!(right.value(), tt.right_value);
                 } else {
                     panic!("Expected InfixExpression");
                 }
@@ -189,7 +335,7 @@ mod tests {
             value: i64,
         }
 
-        let prefix_tests = vec![
+        let prefix_tests = [
             PrefixTest {
                 input: "!5;",
                 operator: "!",
@@ -215,13 +361,9 @@ mod tests {
 
             assert_eq!(program.statements.len(), 1);
 
-            let stmt = program.statements.get(0).unwrap();
-            if let Some(expr_stmt) = stmt.as_any().downcast_ref::<ExpressionStatement>() {
-                if let Some(prefix_expr) = expr_stmt
-                    .expression
-                    .as_any()
-                    .downcast_ref::<PrefixExpression>()
-                {
+            let stmt = program.statements.first().unwrap();
+            if let Statement::Expression(expr_stmt) = stmt {
+                if let Expression::Prefix(prefix_expr) = expr_stmt.expression.as_ref() {
                     assert_eq!(prefix_expr.operator, tt.operator);
                     //assert_eq!(prefix_expr.right., tt.value);
                 } else {
@@ -249,10 +391,51 @@ mod tests {
 
         assert_eq!(program.statements.len(), 1);
 
-        let stmt = program.statements.get(0).unwrap();
+        let stmt = program.statements.first().unwrap();
         assert_eq!(stmt.token_literal(), "5");
     }
 
+    #[test]
+    fn test_radix_integer_literals() {
+        let tests = [
+            ("0xFF;", 255),
+            ("0b1010;", 10),
+            ("0o755;", 493),
+            ("42;", 42),
+        ];
+
+        for (input, expected) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+
+            let program = p.parse_program();
+
+            p.errors.iter().for_each(|e| eprintln!("{}", e));
+            assert!(p.errors.is_empty());
+
+            let stmt = program.statements.first().unwrap();
+            let int_lit = stmt
+                .as_expression_statement()
+                .and_then(|stmt| stmt.expression.as_integer_literal())
+                .expect("Expected IntegerLiteral");
+            assert_eq!(int_lit.value(), expected);
+        }
+    }
+
+    #[test]
+    fn test_illegal_token_error() {
+        let l = Lexer::new("let x = 5; @");
+        let mut p = Parser::new(l);
+
+        p.parse_program();
+
+        assert_eq!(p.errors.len(), 1);
+        assert_eq!(
+            p.errors[0].to_string(),
+            "line 1, column 12: unexpected character '@'"
+        );
+    }
+
     #[test]
     fn test_identifier_expression() {
         let input = "foobar;";
@@ -269,7 +452,7 @@ mod tests {
 
         assert_eq!(program.statements.len(), 1);
 
-        let stmt = program.statements.get(0).unwrap();
+        let stmt = program.statements.first().unwrap();
         assert_eq!(stmt.token_literal(), "foobar");
     }
 
@@ -294,6 +477,321 @@ mod tests {
         assert_eq!(program.statements.len(), 3);
     }
 
+    #[test]
+    fn test_let_statement_values() {
+        let tests = [
+            ("let x = 5;", "x", "5"),
+            ("let y = x;", "y", "x"),
+            ("let foobar = 5 * (a + b);", "foobar", "(5 * (a + b))"),
+            ("let z = -1", "z", "(-1)"),
+        ];
+
+        for (input, name, value) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+
+            let program = p.parse_program();
+
+            p.errors.iter().for_each(|e| eprintln!("{}", e));
+            assert!(p.errors.is_empty());
+            assert_eq!(program.statements.len(), 1);
+
+            let stmt = program.statements.first().unwrap();
+            let Statement::Let(let_stmt) = stmt else {
+                panic!("Expected LetStatement");
+            };
+            assert_eq!(let_stmt.name(), name);
+            assert_eq!(let_stmt.value().unwrap().to_string(), value);
+        }
+    }
+
+    #[test]
+    fn test_return_statement_values() {
+        let tests = [
+            ("return 5;", "5"),
+            ("return x;", "x"),
+            ("return add(1, 2);", "add(1, 2)"),
+            ("return a + b", "(a + b)"),
+        ];
+
+        for (input, value) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+
+            let program = p.parse_program();
+
+            p.errors.iter().for_each(|e| eprintln!("{}", e));
+            assert!(p.errors.is_empty());
+            assert_eq!(program.statements.len(), 1);
+
+            let stmt = program.statements.first().unwrap();
+            let Statement::Return(ret_stmt) = stmt else {
+                panic!("Expected ReturnStatement");
+            };
+            assert_eq!(ret_stmt.return_value.to_string(), value);
+        }
+    }
+
+    #[test]
+    fn test_call_expression() {
+        let l = Lexer::new("add(1, 2 * 3, 4 + 5);");
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        p.errors.iter().for_each(|e| eprintln!("{}", e));
+        assert!(p.errors.is_empty());
+        assert_eq!(program.statements.len(), 1);
+
+        let stmt = program.statements.first().unwrap();
+        let call = stmt
+            .as_expression_statement()
+            .and_then(|stmt| stmt.expression.as_call())
+            .expect("Expected CallExpression");
+        assert_eq!(call.function.to_string(), "add");
+        let args: Vec<String> = call.arguments.iter().map(|a| a.to_string()).collect();
+        assert_eq!(args, ["1", "(2 * 3)", "(4 + 5)"]);
+    }
+
+    #[test]
+    fn test_array_literals() {
+        let tests = [
+            ("[1, 2 * 2, 3 + 3]", vec!["1", "(2 * 2)", "(3 + 3)"]),
+            ("[]", vec![]),
+            ("[a, [b], f(c)]", vec!["a", "[b]", "f(c)"]),
+        ];
+
+        for (input, expected) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+
+            let program = p.parse_program();
+
+            p.errors.iter().for_each(|e| eprintln!("{}", e));
+            assert!(p.errors.is_empty());
+            assert_eq!(program.statements.len(), 1);
+
+            let stmt = program.statements.first().unwrap();
+            let array = stmt
+                .as_expression_statement()
+                .and_then(|stmt| stmt.expression.as_array_literal())
+                .expect("Expected ArrayLiteral");
+            let elements: Vec<String> = array.elements.iter().map(|e| e.to_string()).collect();
+            assert_eq!(elements, expected);
+        }
+    }
+
+    #[test]
+    fn test_index_expression() {
+        let l = Lexer::new("myArray[1 + 1]");
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        p.errors.iter().for_each(|e| eprintln!("{}", e));
+        assert!(p.errors.is_empty());
+        assert_eq!(program.statements.len(), 1);
+
+        let stmt = program.statements.first().unwrap();
+        let index_expr = stmt
+            .as_expression_statement()
+            .and_then(|stmt| stmt.expression.as_index())
+            .expect("Expected IndexExpression");
+        assert_eq!(index_expr.left.to_string(), "myArray");
+        assert_eq!(index_expr.index.to_string(), "(1 + 1)");
+    }
+
+    #[test]
+    fn test_for_in_statement() {
+        let l = Lexer::new("for (x in [1, 2]) { let y = x * 2; puts(y); }");
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        p.errors.iter().for_each(|e| eprintln!("{}", e));
+        assert!(p.errors.is_empty());
+        assert_eq!(program.statements.len(), 1);
+
+        let stmt = program.statements.first().unwrap();
+        let Statement::ForIn(for_stmt) = stmt else {
+            panic!("Expected ForInStatement");
+        };
+        assert_eq!(for_stmt.variable.value(), "x");
+        assert_eq!(for_stmt.iterable.to_string(), "[1, 2]");
+        assert_eq!(for_stmt.body.statements.len(), 2);
+        assert_eq!(for_stmt.body.to_string(), "let y = (x * 2);puts(y)");
+    }
+
+    #[test]
+    fn test_for_in_statement_errors() {
+        let tests = [
+            (
+                "for x in xs { x }",
+                "line 1, column 5: Expected next token to be `(`, got identifier instead",
+            ),
+            (
+                "for (x of xs) { x }",
+                "line 1, column 8: Expected next token to be keyword `in`, got identifier instead",
+            ),
+            (
+                "for (x in xs) { x",
+                "line 1, column 15: Expected `}` to close the block, got end of input instead",
+            ),
+        ];
+
+        for (input, error) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+
+            p.parse_program();
+
+            assert_eq!(
+                p.errors.first().map(|e| e.to_string()).as_deref(),
+                Some(error)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parser_error_kinds() {
+        let tests = [
+            (
+                "let x = ;",
+                ParserError::NoPrefixParseFn {
+                    token: TokenType::Semicolon,
+                    position: Position { line: 1, column: 9 },
+                },
+            ),
+            (
+                "99999999999999999999",
+                ParserError::InvalidIntegerLiteral {
+                    literal: String::from("99999999999999999999"),
+                    position: Position { line: 1, column: 1 },
+                },
+            ),
+        ];
+
+        for (input, error) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+
+            p.parse_program();
+
+            assert_eq!(p.errors, [error]);
+        }
+
+        let mut p = Parser::new(Lexer::new("\"abc"));
+        p.parse_program();
+        match p.errors.as_slice() {
+            [ParserError::Lexer(err)] => assert_eq!(err.message, "unterminated string"),
+            errors => panic!("Expected a single lexer error, got {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn test_parse_result() {
+        let program = Parser::new(Lexer::new("let x = 5; x + 1")).parse();
+        assert_eq!(program.unwrap().to_string(), "let x = 5;(x + 1)");
+
+        let errors = Parser::new(Lexer::new("let = 5; let y 1;"))
+            .parse()
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_nesting_too_deep() {
+        let deep =
+            |n: usize, open: &str, close: &str| format!("{}1{};", open.repeat(n), close.repeat(n));
+
+        // Up to the default limit it parses fine.
+        let input = deep(DEFAULT_MAX_DEPTH - 1, "(", ")");
+        assert!(Parser::new(Lexer::new(&input)).parse().is_ok());
+        let input = format!("{}1;", "1 + ".repeat(DEFAULT_MAX_DEPTH - 2));
+        assert!(Parser::new(Lexer::new(&input)).parse().is_ok());
+
+        // Far above it we get a single error instead of a stack overflow.
+        let inputs = [
+            deep(100_000, "(", ")"),
+            deep(100_000, "-", ""),
+            deep(100_000, "[", "]"),
+            deep(100_000, "for (x in y) {", "}"),
+            // Left associative chains nest too, one level per operator.
+            deep(200_000, "1 + ", ""),
+            format!("a{};", "[0]".repeat(100_000)),
+            format!("f{};", "()".repeat(100_000)),
+        ];
+        for input in inputs.iter() {
+            let errors = Parser::new(Lexer::new(input)).parse().err().unwrap();
+            assert_eq!(errors.len(), 1);
+            assert!(matches!(
+                errors[0],
+                ParserError::NestingTooDeep {
+                    max_depth: DEFAULT_MAX_DEPTH,
+                    ..
+                }
+            ));
+        }
+
+        // The limit can be changed.
+        let mut p = Parser::new(Lexer::new("((1))"));
+        p.set_max_depth(2);
+        p.parse_program();
+        assert_eq!(p.errors.len(), 1);
+        assert_eq!(
+            p.errors[0].to_string(),
+            "line 1, column 3: Nesting is too deep, the limit is 2 levels"
+        );
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let input = "
+            let = 5;
+            let y = 10;
+            let 838383
+            return y;
+            for (x in xs) { let = 1; x }
+            z
+        ";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        let expected = [
+            (TokenType::Assign, 2, 17),
+            (TokenType::Int, 4, 17),
+            (TokenType::Assign, 6, 33),
+        ];
+        assert_eq!(p.errors.len(), expected.len());
+        for (err, (got, line, column)) in p.errors.iter().zip(expected) {
+            assert_eq!(
+                *err,
+                ParserError::UnexpectedToken {
+                    expected: TokenType::Ident,
+                    got,
+                    position: Position { line, column },
+                }
+            );
+        }
+        assert_eq!(program.to_string(), "let y = 10;return y;for (x in xs) xz");
+
+        // The lexer reports the illegal character, the parser skips it.
+        let mut p = Parser::new(Lexer::new("@; 1"));
+        let program = p.parse_program();
+        assert_eq!(
+            p.errors
+                .iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<_>>(),
+            ["line 1, column 1: unexpected character '@'"]
+        );
+        assert_eq!(program.to_string(), "1");
+    }
+
     #[test]
     fn test_let_statements() {
         let input = "
@@ -314,18 +812,594 @@ mod tests {
 
         assert_eq!(program.statements.len(), 3);
 
-        let expected_identifiers = vec!["x", "y", "foobar"];
+        let expected_identifiers = ["x", "y", "foobar"];
         program
             .statements
             .iter()
             .zip(expected_identifiers.iter())
             .for_each(|(stmt, expected_ident)| {
                 assert_eq!(stmt.token_literal(), "let");
-                if let Some(let_stmt) = stmt.as_any().downcast_ref::<LetStatement>() {
+                if let Statement::Let(let_stmt) = stmt {
                     assert_eq!(let_stmt.name(), *expected_ident);
                 } else {
                     panic!("Expected LetStatement");
                 }
             });
     }
+
+    #[test]
+    fn test_const_statements() {
+        let input = "
+            const x = 5;
+            let y = x;
+            const answer = 6 * 7
+        ";
+
+        let l = Lexer::new(input);
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+        p.errors.iter().for_each(|e| eprintln!("{}", e));
+        assert!(p.errors.is_empty());
+
+        assert_eq!(program.statements.len(), 3);
+        assert!(matches!(program.statements[1], Statement::Let(_)));
+
+        let expected = [(0, "x", "5"), (2, "answer", "(6 * 7)")];
+        for (index, name, value) in expected {
+            let stmt = &program.statements[index];
+            assert_eq!(stmt.token_literal(), "const");
+            let Statement::Const(const_stmt) = stmt else {
+                panic!("Expected ConstStatement");
+            };
+            assert_eq!(const_stmt.name(), name);
+            assert_eq!(const_stmt.value().to_string(), value);
+        }
+    }
+
+    #[test]
+    fn test_const_statement_requires_value() {
+        let l = Lexer::new("const x; const y = 1;");
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+
+        assert_eq!(p.errors.len(), 1);
+        assert_eq!(program.statements.len(), 1);
+        assert_eq!(program.to_string(), "const y = 1;");
+    }
+
+    #[test]
+    fn test_member_requires_name() {
+        let l = Lexer::new("a.1");
+        let mut p = Parser::new(l);
+
+        p.parse_program();
+
+        assert!(matches!(
+            &p.errors[0],
+            ParserError::UnexpectedToken {
+                expected: TokenType::Ident,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_macro_literal_parsing() {
+        let l = Lexer::new("macro(x, y) { x + y; }");
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+        p.errors.iter().for_each(|e| eprintln!("{}", e));
+        assert!(p.errors.is_empty());
+        assert_eq!(program.statements.len(), 1);
+
+        let macro_lit = &program.statements[0]
+            .as_expression_statement()
+            .and_then(|stmt| stmt.expression.as_macro_literal())
+            .expect("Expected MacroLiteral");
+
+        let params: Vec<&str> = macro_lit.parameters.iter().map(|p| p.value()).collect();
+        assert_eq!(params, ["x", "y"]);
+        assert_eq!(macro_lit.body.statements.len(), 1);
+        assert_eq!(macro_lit.body.to_string(), "(x + y)");
+    }
+
+    #[test]
+    fn test_macro_parameters() {
+        let tests = [
+            ("macro() { 1 }", "macro() 1"),
+            ("macro(a) { a }", "macro(a) a"),
+            ("macro(a, b, c) { a }", "macro(a, b, c) a"),
+        ];
+
+        for (input, expected) in tests {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            let program = p.parse_program();
+            assert!(p.errors.is_empty(), "{}: {:?}", input, p.errors);
+            assert_eq!(program.to_string(), expected);
+        }
+
+        for input in ["macro(a, 1) { a }", "macro(a b) { a }", "macro(a) a"] {
+            let l = Lexer::new(input);
+            let mut p = Parser::new(l);
+            p.parse_program();
+            assert!(!p.errors.is_empty(), "{} should not parse", input);
+        }
+    }
+
+    #[test]
+    fn test_quote_unquote_parsing() {
+        let l = Lexer::new("quote(unquote(x) + 1)");
+        let mut p = Parser::new(l);
+
+        let program = p.parse_program();
+        assert!(p.errors.is_empty());
+        assert_eq!(program.to_string(), "quote((unquote(x) + 1))");
+
+        let quote = &program.statements[0]
+            .as_expression_statement()
+            .and_then(|stmt| stmt.expression.as_call())
+            .expect("Expected CallExpression");
+        assert_eq!(quote.function_name(), Some("quote"));
+
+        let Expression::Infix(sum) = &quote.arguments[0] else {
+            panic!("Expected InfixExpression");
+        };
+        let Expression::Call(unquote) = sum.left.as_ref() else {
+            panic!("Expected CallExpression");
+        };
+        assert_eq!(unquote.function_name(), Some("unquote"));
+    }
+
+    #[test]
+    fn test_parse_hand_built_tokens() {
+        let tokens = vec![
+            Token::new(TokenType::Let, "let"),
+            Token::new(TokenType::Ident, "x"),
+            Token::new(TokenType::Assign, "="),
+            Token::new(TokenType::Int, "1"),
+            Token::new(TokenType::Plus, "+"),
+            Token::new(TokenType::Int, "2"),
+            Token::new(TokenType::Semicolon, ";"),
+        ];
+
+        // There is no EOF token, the end of the iterator is the end of input.
+        let program = Parser::from_tokens(tokens.into_iter()).parse().unwrap();
+        assert_eq!(program.to_string(), "let x = (1 + 2);");
+    }
+
+    #[test]
+    fn test_hand_built_illegal_token() {
+        let tokens = vec![
+            Token::new(TokenType::Int, "1"),
+            Token::new(TokenType::Plus, "+"),
+            Token::new(TokenType::Illegal, "@"),
+        ];
+
+        let errors = Parser::from_tokens(tokens.into_iter())
+            .parse()
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors[0],
+            ParserError::NoPrefixParseFn {
+                token: TokenType::Illegal,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_import_statements() {
+        let input = r#"
+            import "math";
+            import "std/collections/list"
+            import "../shared";
+        "#;
+
+        let program = Parser::new(Lexer::new(input)).parse().ok().unwrap();
+        assert_eq!(program.statements.len(), 3);
+
+        let expected = ["math", "std/collections/list", "../shared"];
+        for (stmt, path) in program.statements.iter().zip(expected) {
+            let Statement::Import(import) = stmt else {
+                panic!("Expected ImportStatement");
+            };
+            assert_eq!(import.path(), path);
+        }
+        assert_eq!(
+            program.to_string(),
+            r#"import "math";import "std/collections/list";import "../shared";"#
+        );
+    }
+
+    #[test]
+    fn test_malformed_import_paths() {
+        for path in ["", "/abs", "dir/", "a//b"] {
+            let input = format!("import \"{}\"; let x = 1;", path);
+            let mut p = Parser::new(Lexer::new(&input));
+            let program = p.parse_program();
+
+            assert_eq!(
+                p.errors,
+                [ParserError::InvalidImportPath {
+                    path: path.to_string(),
+                    position: Position { line: 1, column: 8 },
+                }]
+            );
+            assert_eq!(program.to_string(), "let x = 1;");
+        }
+
+        let mut p = Parser::new(Lexer::new("import math;"));
+        p.parse_program();
+        assert_eq!(
+            p.errors[0].to_string(),
+            "line 1, column 8: Expected next token to be string, got identifier instead"
+        );
+    }
+
+    #[test]
+    fn test_register_infix_operators() {
+        // A new binary operator.
+        let mut p = Parser::new(Lexer::new("a = b + 1 == c"));
+        p.register_infix(
+            TokenType::Assign,
+            Precedence::Ternary,
+            Parser::parse_infix_expression,
+        );
+        let program = p.parse().ok().unwrap();
+        assert_eq!(program.to_string(), "(a = ((b + 1) == c))");
+
+        // A built-in operator with another precedence.
+        let mut p = Parser::new(Lexer::new("1 + 2 * 3"));
+        p.register_infix(
+            TokenType::Asterisk,
+            Precedence::LessGreater,
+            Parser::parse_infix_expression,
+        );
+        let program = p.parse().ok().unwrap();
+        assert_eq!(program.to_string(), "((1 + 2) * 3)");
+
+        // An operator with its own parsing function: a range `a : b` where
+        // both ends are sums. It can be a closure.
+        let mut p = Parser::new(Lexer::new("0 : n - 1 < m"));
+        let operator = "..".to_string();
+        p.register_infix(
+            TokenType::Colon,
+            Precedence::LessGreater,
+            move |parser, left| {
+                let builder = ast::InfixExpressionBuilder::new(parser.cur_token())
+                    .operator(operator.clone())
+                    .left(left);
+                parser.next_token();
+                let builder = builder.right(parser.parse_expression(Precedence::LessGreater)?);
+                Some(Expression::Infix(builder.build()))
+            },
+        );
+        let program = p.parse().ok().unwrap();
+        assert_eq!(program.to_string(), "((0 .. (n - 1)) < m)");
+    }
+
+    #[test]
+    fn test_let_without_initializer() {
+        let mut p = Parser::new(Lexer::new("let x; let y = x;"));
+        let program = p.parse_program();
+
+        assert!(p.errors.is_empty());
+        assert_eq!(program.statements.len(), 2);
+
+        let Statement::Let(let_stmt) = &program.statements[0] else {
+            panic!("Expected LetStatement");
+        };
+        assert_eq!(let_stmt.name(), "x");
+        assert!(let_stmt.value().is_none());
+        assert_eq!(program.to_string(), "let x;let y = x;");
+
+        // Without semicolon an initializer is expected.
+        let mut p = Parser::new(Lexer::new("let x 5;"));
+        p.parse_program();
+        assert!(matches!(
+            p.errors[0],
+            ParserError::UnexpectedToken {
+                expected: TokenType::Assign,
+                got: TokenType::Int,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_failed_operand_before_operator() {
+        // The right operand of the first operator fails to parse while more
+        // operators follow.
+        let inputs = [
+            "1 + ) * 2",
+            "a + 99999999999999999999 * 2",
+            "f(1 + ) + 2",
+            "a + (1 * ) * 3",
+            "x + [1, ] * 2",
+            "1 ? 2 : ) + 3",
+        ];
+
+        for input in inputs {
+            let mut p = Parser::new(Lexer::new(input));
+            p.parse_program();
+            assert!(!p.errors.is_empty(), "{} should not parse", input);
+        }
+    }
+
+    #[test]
+    fn test_function_literal_parsing() {
+        let tests = [
+            ("fn() { 1 }", "fn() 1"),
+            ("fn(x) { x * 2 }", "fn(x) (x * 2)"),
+            (
+                "fn(x, y) { let z = x; return z + y; }",
+                "fn(x, y) let z = x;return (z + y);",
+            ),
+            ("fn(x) { x }(5)", "fn(x) x(5)"),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input));
+            let program = p.parse_program();
+            assert!(p.errors.is_empty(), "{}: {:?}", input, p.errors);
+            assert_eq!(program.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_lambda_parsing() {
+        let tests = [
+            ("|x| x * 2", "fn(x) (x * 2)"),
+            ("|x, y| x + y", "fn(x, y) (x + y)"),
+            ("|| 42", "fn() 42"),
+            ("|x| { let y = x; y }", "fn(x) let y = x;y"),
+            ("map(arr, |x| x + 1)", "map(arr, fn(x) (x + 1))"),
+            ("a || |x| x", "(a || fn(x) x)"),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input));
+            let program = p.parse_program();
+            assert!(p.errors.is_empty(), "{}: {:?}", input, p.errors);
+            assert_eq!(program.to_string(), expected);
+        }
+
+        // A lambda is a function literal.
+        let program = Parser::new(Lexer::new("|a, b| a")).parse().ok().unwrap();
+        let function = &program.statements[0]
+            .as_expression_statement()
+            .and_then(|stmt| stmt.expression.as_function_literal())
+            .expect("Expected FunctionLiteral");
+        let params: Vec<&str> = function.parameters.iter().map(|p| p.value()).collect();
+        assert_eq!(params, ["a", "b"]);
+        assert_eq!(function.body.statements.len(), 1);
+
+        for input in ["|x x", "|1| x", "|x|"] {
+            let mut p = Parser::new(Lexer::new(input));
+            p.parse_program();
+            assert!(!p.errors.is_empty(), "{} should not parse", input);
+        }
+    }
+
+    #[test]
+    fn test_if_expression_parsing() {
+        let tests = [
+            ("if (x < y) { x }", "if(x < y) x"),
+            ("if (x < y) { x } else { y }", "if(x < y) xelse y"),
+            (
+                "let max = if (a > b) { a } else { b };",
+                "let max = if(a > b) aelse b;",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input));
+            let program = p.parse_program();
+            assert!(p.errors.is_empty(), "{}: {:?}", input, p.errors);
+            assert_eq!(program.to_string(), expected);
+        }
+
+        let program = Parser::new(Lexer::new("if (ok) { 1 }"))
+            .parse()
+            .ok()
+            .unwrap();
+        let if_expr = &program.statements[0]
+            .as_expression_statement()
+            .and_then(|stmt| stmt.expression.as_if())
+            .expect("Expected IfExpression");
+        assert_eq!(if_expr.condition.to_string(), "ok");
+        assert!(if_expr.alternative.is_none());
+    }
+
+    #[test]
+    fn test_assign_expression_parsing() {
+        let tests = [
+            ("x = 5", "(x = 5)"),
+            ("x = y = z + 1", "(x = (y = (z + 1)))"),
+            ("a[0] = b ? 1 : 2", "((a[0]) = (b ? 1 : 2))"),
+            ("obj.field = 1;", "((obj.field) = 1)"),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input));
+            let program = p.parse_program();
+            assert!(p.errors.is_empty(), "{}: {:?}", input, p.errors);
+            assert_eq!(program.to_string(), expected);
+        }
+
+        let mut p = Parser::new(Lexer::new("1 + x = 2"));
+        p.parse_program();
+        assert_eq!(
+            p.errors,
+            [ParserError::InvalidAssignmentTarget {
+                target: "(1 + x)".to_string(),
+                position: Position { line: 1, column: 7 },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_assignment_in_condition_warning() {
+        let mut p = Parser::new(Lexer::new("if (x = 5) { x }"));
+        p.parse_program();
+
+        assert!(p.errors.is_empty());
+        assert_eq!(
+            p.warnings,
+            [ParserWarning::AssignmentInCondition {
+                position: Position { line: 1, column: 7 },
+            }]
+        );
+        assert_eq!(
+            p.warnings[0].to_string(),
+            "line 1, column 7: Assignment used as a condition, did you mean `==`?"
+        );
+
+        // Comparisons and assignments outside of the condition are fine.
+        let mut p = Parser::new(Lexer::new("if (x == 5) { x = 6 }"));
+        p.parse_program();
+        assert!(p.errors.is_empty());
+        assert!(p.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_boolean_expression() {
+        let mut p = Parser::new(Lexer::new("true; !false == (1 < 2);"));
+        let program = p.parse_program();
+
+        assert!(p.errors.is_empty());
+        assert_eq!(program.to_string(), "true((!false) == (1 < 2))");
+
+        let Statement::Expression(stmt) = &program.statements[0] else {
+            panic!("expected an expression statement");
+        };
+        let Expression::Boolean(lit) = &*stmt.expression else {
+            panic!("expected a boolean, got {}", stmt.expression);
+        };
+        assert!(lit.value());
+    }
+
+    #[test]
+    fn test_float_literal_expression() {
+        let mut p = Parser::new(Lexer::new("3.25; 2.5E-3;"));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty());
+
+        let values: Vec<f64> = program
+            .statements
+            .iter()
+            .filter_map(|stmt| stmt.as_expression_statement())
+            .filter_map(|stmt| stmt.expression.as_float_literal())
+            .map(|lit| lit.value())
+            .collect();
+        assert_eq!(values, [3.25, 0.0025]);
+        assert_eq!(program.to_string(), "3.252.5E-3");
+    }
+
+    #[test]
+    fn test_string_literal_expression() {
+        let mut p = Parser::new(Lexer::new(r#"let s = "hello \"world\"\n";"#));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty());
+
+        let lit = program.statements[0]
+            .as_let()
+            .and_then(|stmt| stmt.value())
+            .and_then(|value| value.as_string_literal())
+            .expect("expected a string literal");
+        assert_eq!(lit.value(), "hello \"world\"\n");
+        // It is written back quoted and escaped.
+        assert_eq!(program.to_string(), r#"let s = "hello \"world\"\n";"#);
+    }
+
+    #[test]
+    fn test_hash_literal_expression() {
+        let tests = [
+            (
+                r#"{"one": 1, "two": 2 * 2}"#,
+                r#"{"one": 1, "two": (2 * 2)}"#,
+            ),
+            ("{}", "{}"),
+            ("{1: true, x: fn(a) { a },}", "{1: true, x: fn(a) a}"),
+            ("{a: {b: c}}[a]", "({a: {b: c}}[a])"),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input));
+            let program = p.parse_program();
+            assert!(p.errors.is_empty(), "{:?}", p.errors);
+            assert_eq!(program.to_string(), expected);
+        }
+
+        let mut p = Parser::new(Lexer::new("{1: 2, 3}"));
+        p.parse_program();
+        assert_eq!(
+            p.errors[0].to_string(),
+            "line 1, column 9: Expected next token to be `:`, got `}` instead"
+        );
+    }
+
+    #[test]
+    fn test_statement_spans() {
+        let input = "let x = 1;\nfor (i in xs) { puts(i) }\n  x + 2 ";
+        let mut p = Parser::new(Lexer::new(input));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty());
+
+        let texts: Vec<&str> = program
+            .statements
+            .iter()
+            .map(|stmt| {
+                let span = stmt.span();
+                &input[span.start..span.end]
+            })
+            .collect();
+        assert_eq!(texts, ["let x = 1;", "for (i in xs) { puts(i) }", "x + 2"]);
+
+        // Statements nested in a block have their span too.
+        let for_stmt = program.statements[1].as_for_in().unwrap();
+        assert_eq!(
+            for_stmt.body.statements[0].span(),
+            Span { start: 27, end: 34 }
+        );
+
+        assert_eq!(program.statement_at(0).unwrap().kind(), "Let");
+        assert_eq!(program.statement_at(9).unwrap().kind(), "Let");
+        assert_eq!(program.statement_at(28).unwrap().kind(), "ForIn");
+        assert_eq!(program.statement_at(42).unwrap().kind(), "Expression");
+        // Between statements and after the end of the input.
+        assert!(program.statement_at(10).is_none());
+        assert!(program.statement_at(37).is_none());
+        assert!(program.statement_at(100).is_none());
+    }
+
+    #[test]
+    fn test_comments_in_ast() {
+        let input = "// doc\nlet x = 1; // one\nif (x) {\n  x\n  // left\n}\n/* end */";
+        let mut p = Parser::new(Lexer::new(input));
+        let program = p.parse_program();
+        assert!(p.errors.is_empty());
+
+        let texts = |comments: &[Trivia]| -> Vec<String> {
+            comments.iter().map(|c| c.text.clone()).collect()
+        };
+        assert_eq!(
+            texts(program.statements[0].comments()),
+            ["// doc", "// one"]
+        );
+        assert!(program.statements[1].comments().is_empty());
+
+        let if_expr = program.statements[1]
+            .as_expression_statement()
+            .and_then(|stmt| stmt.expression.as_if())
+            .unwrap();
+        assert_eq!(texts(&if_expr.consequence.comments), ["// left"]);
+        assert_eq!(texts(&program.comments), ["/* end */"]);
+    }
 }
@@ -0,0 +1,78 @@
+#![cfg(feature = "rand")]
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use redhowler::interpreter::environment::Environment;
+    use redhowler::interpreter::evaluator::Evaluator;
+    use redhowler::interpreter::lexer::Lexer;
+    use redhowler::interpreter::object::Object;
+    use redhowler::interpreter::parser::Parser;
+
+    fn eval_seeded(input: &str, seed: u64) -> Object {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+        let mut evaluator = Evaluator::new();
+        evaluator.seed_rand(seed);
+        let env = Rc::new(RefCell::new(Environment::new()));
+        evaluator.eval(&program, &env)
+    }
+
+    #[test]
+    fn test_same_seed_same_numbers() {
+        let input = "[rand(), rand_int(0, 1000), rand(), rand_int(-5, 5)]";
+
+        let first = eval_seeded(input, 42);
+        assert_eq!(eval_seeded(input, 42), first);
+        assert_ne!(eval_seeded(input, 43), first);
+    }
+
+    #[test]
+    fn test_numbers_in_range() {
+        let input = r#"
+            for (i in range(1000)) {
+                let f = rand();
+                let n = rand_int(-3, 4);
+                assert(!(f < 0), "rand() below 0");
+                assert(f < 1, "rand() not below 1");
+                assert(n > -4, "rand_int() below low");
+                assert(n < 4, "rand_int() not below high");
+            }
+            true"#;
+        assert_eq!(eval_seeded(input, 7), Object::Boolean(true));
+
+        // Every value of the range comes out.
+        let input = "let seen = {}; for (i in range(200)) { seen = merge(seen, {rand_int(0, 5): true}) } len(seen)";
+        assert_eq!(eval_seeded(input, 7), Object::Integer(5));
+
+        let input = "rand_int(-9223372036854775807 - 1, 9223372036854775807)";
+        assert!(matches!(eval_seeded(input, 7), Object::Integer(_)));
+    }
+
+    #[test]
+    fn test_rand_errors() {
+        let tests = [
+            ("rand(1)", "wrong number of arguments: want=0, got=1"),
+            ("rand_int(3, 3)", "empty range: rand_int(3, 3)"),
+            (
+                r#"rand_int(1, "a")"#,
+                "arguments to `rand_int` must be INTEGER, INTEGER, got INTEGER, STRING",
+            ),
+            ("rand_int(1)", "wrong number of arguments: want=2, got=1"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval_seeded(input, 0),
+                Object::Error(expected.to_string()),
+                "evaluating {}",
+                input
+            );
+        }
+    }
+}
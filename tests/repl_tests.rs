@@ -0,0 +1,250 @@
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use redhowler::interpreter::environment::Environment;
+    use redhowler::interpreter::lexer::Lexer;
+    use redhowler::interpreter::object::Object;
+    use redhowler::interpreter::parser::Parser;
+    use redhowler::repl::{self, completion, highlight, history};
+
+    // The errors of the input as the REPL shows them.
+    fn format_errors(input: &str) -> String {
+        let mut parser = Parser::new(Lexer::new(input));
+        parser.parse_program();
+        assert!(!parser.errors.is_empty(), "no error in {}", input);
+        parser
+            .errors
+            .iter()
+            .map(|err| repl::format_parser_error(input, err))
+            .collect()
+    }
+
+    #[test]
+    fn test_format_parser_error() {
+        let tests = [
+            (
+                "let = 5;",
+                "let = 5;\n    ^\nline 1, column 5: Expected next token to be identifier, got `=` instead\n",
+            ),
+            (
+                "let x 12345;",
+                "let x 12345;\n      ^~~~~\nline 1, column 7: Expected next token to be `=`, got integer instead\n",
+            ),
+            (
+                "1;\n\tlet 99",
+                "\tlet 99\n\t    ^~\nline 2, column 6: Expected next token to be identifier, got integer instead\n",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(format_errors(input), expected, "formatting {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_complete() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer.borrow_mut().set("first_name", Object::Null);
+        outer.borrow_mut().set("count", Object::Null);
+        let mut env = Environment::new_enclosed(outer);
+        env.set("counter", Object::Null);
+
+        let tests = [
+            // Keywords, builtins and bound names.
+            ("le", 2, 0, vec!["len", "let"]),
+            ("let x = fir", 11, 8, vec!["first", "first_name"]),
+            ("co", 2, 0, vec!["const", "contains", "count", "counter"]),
+            // Only the part before the cursor is completed.
+            ("cou + 1", 3, 0, vec!["count", "counter"]),
+            ("puts(coun)", 9, 5, vec!["count", "counter"]),
+            ("zzz", 3, 0, vec![]),
+            // Nothing to complete after a space or a number.
+            ("let ", 4, 4, vec![]),
+            ("12", 2, 2, vec![]),
+        ];
+
+        for (line, pos, start, expected) in tests {
+            assert_eq!(
+                completion::complete(line, pos, &env),
+                (start, expected.iter().map(|s| s.to_string()).collect()),
+                "completing {:?}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_highlight() {
+        let tests = [
+            (
+                "let s = \"hi\"; // done",
+                "\x1b[35mlet\x1b[0m s = \x1b[32m\"hi\"\x1b[0m; // done",
+            ),
+            (
+                "if (x > 1.5) { true }",
+                "\x1b[35mif\x1b[0m (x > \x1b[33m1.5\x1b[0m) { \x1b[35mtrue\x1b[0m }",
+            ),
+            ("[1, @]", "[\x1b[33m1\x1b[0m, \x1b[31m@\x1b[0m]"),
+            ("", ""),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                highlight::highlight(input),
+                expected,
+                "highlighting {:?}",
+                input
+            );
+        }
+        assert_eq!(highlight::error("oops"), "\x1b[31moops\x1b[0m");
+    }
+
+    // The transcript of a session given the lines of input.
+    fn transcript(input: &str) -> String {
+        let mut output = Vec::new();
+        repl::start(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_session() {
+        let tests = [
+            // The bindings are kept from a line to the next.
+            ("let x = 5;\nx * 2\n", ">> >> 10\n>> \n"),
+            (
+                "let f = fn(n) { if (n < 1) { 0 } else { f(n - 1) } };\nf(3)\n\"s\"\n",
+                ">> >> 0\n>> \"s\"\n>> \n",
+            ),
+            // The output of puts comes before the value.
+            (
+                "puts(\"a\"); [1, 2]\n",
+                ">> a\n[1, 2]\n>> \n",
+            ),
+            ("1 + true\n", ">> ERROR: type mismatch: INTEGER + BOOLEAN\n>> \n"),
+            (
+                "let = 1;\n",
+                ">> Woops! We ran into some monkey business here!\nlet = 1;\n    ^\nline 1, column 5: Expected next token to be identifier, got `=` instead\n>> \n",
+            ),
+            // q; ends the session, the lines after it are not read.
+            (
+                "1\nq;\n2\n",
+                ">> 1\n>> May your trip be as enjoyable as finding extra bananas at the bottom of the bag!",
+            ),
+            ("", ">> \n"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(transcript(input), expected, "running {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_mode_command() {
+        let tests = [
+            (":mode\n", ">> eval\n>> \n"),
+            (
+                ":mode tokens\nlet x\n:mode\n",
+                ">> >> 1:1 Let \"let\"\n1:5 Ident \"x\"\n>> tokens\n>> \n",
+            ),
+            (
+                ":mode tokens\n@\n",
+                ">> >> 1:1 Illegal \"@\"\nError: line 1, column 1: unexpected character '@'\n>> \n",
+            ),
+            (":mode ast\n1 + 2 * 3\n", ">> >> (1 + (2 * 3))\n>> \n"),
+            // The lines parsed in ast mode are not evaluated.
+            (
+                ":mode ast\nlet y = 1;\n:mode eval\ny\n",
+                ">> >> let y = 1;\n>> >> ERROR: identifier not found: y\n>> \n",
+            ),
+            (
+                ":mode jit\n:what\n",
+                ">> unknown mode: jit (expected tokens, ast or eval)\n>> unknown command: :what\n>> \n",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(transcript(input), expected, "running {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_history_command() {
+        let tests = [
+            (
+                "1\n\n1\nlet a = 2;\n:history\n",
+                ">> 1\n>> >> 1\n>> >>     1  1\n    2  let a = 2;\n    3  :history\n>> \n",
+            ),
+            (
+                "1\n2\n3\n:history 2\n",
+                ">> 1\n>> 2\n>> 3\n>>     3  3\n    4  :history 2\n>> \n",
+            ),
+            (":history x\n", ">> usage: :history [count]\n>> \n"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(transcript(input), expected, "running {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_history_path() {
+        assert_eq!(
+            history::path_in(Some("/state".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/state/redhowler/history"))
+        );
+        assert_eq!(
+            history::path_in(Some("state".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/home/me/.redhowler_history"))
+        );
+        assert_eq!(
+            history::path_in(None, Some("/home/me".into())),
+            Some(PathBuf::from("/home/me/.redhowler_history"))
+        );
+        assert_eq!(history::path_in(None, None), None);
+    }
+
+    #[test]
+    fn test_history_file() {
+        let dir = std::env::temp_dir().join(format!("redhowler-history-{}", std::process::id()));
+        let path = dir.join("state").join("history");
+        assert_eq!(history::load(&path).unwrap(), Vec::<String>::new());
+
+        let mut entries = Vec::new();
+        for i in 0..history::MAX_ENTRIES + 5 {
+            history::add(&mut entries, &format!("let a = {};", i));
+            history::add(&mut entries, &format!("let a = {};", i));
+            history::add(&mut entries, " ");
+        }
+        assert_eq!(entries.len(), history::MAX_ENTRIES);
+        assert_eq!(entries[0], "let a = 5;");
+
+        history::save(&path, &entries).unwrap();
+        assert_eq!(history::load(&path).unwrap(), entries);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_time_command() {
+        // The time changes from a run to the next, the steps don't.
+        let output = transcript(":time let a = 1; a + 2\n:mode ast\n:time a\n");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 5, "{:?}", output);
+        assert_eq!(lines[0], ">> 3");
+        assert!(lines[1].starts_with("time: "), "{:?}", lines[1]);
+        assert!(lines[1].ends_with(", 6 steps"), "{:?}", lines[1]);
+        // :time evaluates in every mode.
+        assert_eq!(lines[2], ">> >> 1");
+        assert!(lines[3].ends_with(", 2 steps"), "{:?}", lines[3]);
+
+        assert_eq!(
+            transcript(":time\n:time let\n"),
+            ">> usage: :time <code>\n>> Woops! We ran into some monkey business here!\nlet\n   ^\nline 1, column 4: Expected next token to be identifier, got end of input instead\n>> \n"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "serde")]
+
+#[cfg(test)]
+mod tests {
+
+    use redhowler::interpreter::ast::Program;
+    use redhowler::interpreter::lexer::Lexer;
+    use redhowler::interpreter::parser::Parser;
+    use redhowler::interpreter::token::{Token, TokenType};
+
+    #[test]
+    fn test_token_round_trip() {
+        let tokens: Vec<Token> = Lexer::new("let x = 5; // five").collect();
+
+        let json = serde_json::to_string(&tokens).unwrap();
+        let back: Vec<Token> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(back, tokens);
+    }
+
+    #[test]
+    fn test_token_json() {
+        let tok = Lexer::new("let").next_token();
+
+        let json = serde_json::to_value(&tok).unwrap();
+
+        assert_eq!(json["token_type"], "Let");
+        assert_eq!(json["literal"], "let");
+        assert_eq!(json["span"]["end"], 3);
+        assert_eq!(json["position"]["column"], 1);
+
+        let ty: TokenType = serde_json::from_str("\"Ident\"").unwrap();
+        assert_eq!(ty, TokenType::Ident);
+    }
+
+    #[test]
+    fn test_program_json() {
+        let mut parser = Parser::new(Lexer::new("let x = 1 + y;"));
+        let program = parser.parse_program();
+
+        let json = serde_json::to_value(&program).unwrap();
+
+        let value = &json["statements"][0]["Let"]["value"]["Infix"];
+        assert_eq!(value["operator"], "+");
+        assert_eq!(value["left"]["IntegerLiteral"]["value"], 1);
+        assert_eq!(value["right"]["Identifier"]["value"], "y");
+    }
+
+    #[test]
+    fn test_program_round_trip() {
+        let mut parser = Parser::new(Lexer::new("let f = fn(a) { a[0] ? a.len() : -1 };"));
+        let program = parser.parse_program();
+
+        let json = serde_json::to_string(&program).unwrap();
+        let back: Program = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(back, program);
+    }
+}
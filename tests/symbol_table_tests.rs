@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod tests {
+
+    use redhowler::compiler::symbol_table::{Symbol, SymbolScope, SymbolTable};
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            scope,
+            index,
+        }
+    }
+
+    #[test]
+    fn test_define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 0));
+        assert_eq!(local.define("d"), symbol("d", SymbolScope::Local, 1));
+
+        let mut nested = SymbolTable::new_enclosed(local);
+        assert_eq!(nested.define("e"), symbol("e", SymbolScope::Local, 0));
+
+        let tests = [
+            symbol("a", SymbolScope::Global, 0),
+            symbol("b", SymbolScope::Global, 1),
+            symbol("c", SymbolScope::Free, 0),
+            symbol("d", SymbolScope::Free, 1),
+            symbol("e", SymbolScope::Local, 0),
+        ];
+        for expected in tests {
+            assert_eq!(nested.resolve(&expected.name), Some(expected));
+        }
+        assert_eq!(nested.resolve("f"), None);
+
+        // The free symbols are given as the enclosing function sees them.
+        assert_eq!(
+            nested.free_symbols(),
+            [
+                symbol("c", SymbolScope::Local, 0),
+                symbol("d", SymbolScope::Local, 1)
+            ]
+        );
+        assert_eq!(nested.num_definitions(), 1);
+
+        let local = nested.into_outer().unwrap();
+        assert!(local.free_symbols().is_empty());
+        assert!(local.into_outer().unwrap().into_outer().is_none());
+    }
+
+    #[test]
+    fn test_free_symbols_through_several_functions() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+        let mut first = SymbolTable::new_enclosed(global);
+        first.define("b");
+        let second = SymbolTable::new_enclosed(first);
+        let mut third = SymbolTable::new_enclosed(second);
+
+        // b is captured by second, from which third captures it.
+        assert_eq!(third.resolve("b"), Some(symbol("b", SymbolScope::Free, 0)));
+        assert_eq!(third.free_symbols(), [symbol("b", SymbolScope::Free, 0)]);
+        assert_eq!(
+            third.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+
+        let second = third.into_outer().unwrap();
+        assert_eq!(second.free_symbols(), [symbol("b", SymbolScope::Local, 0)]);
+    }
+
+    #[test]
+    fn test_define_builtins() {
+        let mut global = SymbolTable::new();
+        global.define_builtin(0, "len");
+        global.define_builtin(5, "puts");
+        let mut local = SymbolTable::new_enclosed(global);
+        local.define("len");
+
+        assert_eq!(
+            local.resolve("puts"),
+            Some(symbol("puts", SymbolScope::Builtin, 5))
+        );
+        // Shadowed by the local.
+        assert_eq!(
+            local.resolve("len"),
+            Some(symbol("len", SymbolScope::Local, 0))
+        );
+        assert!(local.free_symbols().is_empty());
+    }
+
+    #[test]
+    fn test_define_function_name() {
+        let mut global = SymbolTable::new();
+        global.define("f");
+        let mut function = SymbolTable::new_enclosed(global);
+        function.define_function_name("f");
+        let mut inner = SymbolTable::new_enclosed(function);
+
+        assert_eq!(inner.resolve("f"), Some(symbol("f", SymbolScope::Free, 0)));
+        let mut function = inner.into_outer().unwrap();
+        assert_eq!(
+            function.resolve("f"),
+            Some(symbol("f", SymbolScope::Function, 0))
+        );
+
+        // A parameter with the same name shadows it.
+        function.define("f");
+        assert_eq!(
+            function.resolve("f"),
+            Some(symbol("f", SymbolScope::Local, 0))
+        );
+    }
+
+    #[test]
+    fn test_shadowing_and_redefinition() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+        global.define("b");
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.num_definitions(), 2);
+
+        let mut local = SymbolTable::new_enclosed(global);
+        local.define("a");
+        assert_eq!(local.resolve("a"), Some(symbol("a", SymbolScope::Local, 0)));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        global.enter_block();
+        // A name of the block hides the one outside, and is reused in it.
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 1));
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 1));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 2));
+        global.leave_block();
+
+        assert_eq!(
+            global.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(global.resolve("b"), None);
+        // The indexes of the block are not given again.
+        assert_eq!(global.define("c"), symbol("c", SymbolScope::Global, 3));
+    }
+}
@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+
+    use redhowler::interpreter::lexer::Lexer;
+    use redhowler::interpreter::token::{Token, TokenType};
+    use redhowler::interpreter::token_stream::TokenStream;
+
+    #[test]
+    fn test_lexer_iterator() {
+        let types: Vec<TokenType> = Lexer::new("let x = 5;").map(|t| t.token_type).collect();
+
+        assert_eq!(
+            types,
+            [
+                TokenType::Let,
+                TokenType::Ident,
+                TokenType::Assign,
+                TokenType::Int,
+                TokenType::Semicolon,
+                TokenType::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut stream = TokenStream::new(Lexer::new("a + b"));
+
+        assert_eq!(stream.peek().literal, "a");
+        assert_eq!(stream.peek_nth(0).literal, "a");
+        assert_eq!(stream.peek_nth(2).literal, "b");
+        assert_eq!(stream.peek_nth(5).token_type, TokenType::EOF);
+
+        // Peeking doesn't consume anything.
+        assert_eq!(stream.next_token().literal, "a");
+        assert_eq!(stream.peek().literal, "+");
+        assert_eq!(stream.next_token().literal, "+");
+        assert_eq!(stream.next_token().literal, "b");
+
+        // Once exhausted the stream keeps returning EOF.
+        for _ in 0..3 {
+            assert_eq!(stream.next_token().token_type, TokenType::EOF);
+        }
+    }
+
+    #[test]
+    fn test_hand_built_tokens() {
+        let tokens = vec![
+            Token::new(TokenType::Int, "1"),
+            Token::new(TokenType::Plus, "+"),
+        ];
+        let mut stream = TokenStream::new(tokens.into_iter());
+
+        assert_eq!(stream.peek_nth(1).token_type, TokenType::Plus);
+        assert_eq!(stream.peek_nth(2).token_type, TokenType::EOF);
+        assert_eq!(stream.next_token().token_type, TokenType::Int);
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+
+    use redhowler::interpreter::token::{TokenType, KEYWORDS};
+
+    #[test]
+    fn test_token_type_display() {
+        let tests = [
+            (TokenType::Ident, "identifier"),
+            (TokenType::Int, "integer"),
+            (TokenType::EOF, "end of input"),
+            (TokenType::Plus, "`+`"),
+            (TokenType::NotEqual, "`!=`"),
+            (TokenType::LBrace, "`{`"),
+            (TokenType::Let, "keyword `let`"),
+            (TokenType::Function, "keyword `fn`"),
+        ];
+
+        for (token_type, expected) in tests {
+            assert_eq!(token_type.to_string(), expected);
+        }
+
+        assert_eq!(
+            format!("expected an {}, got {}", TokenType::Ident, TokenType::Let),
+            "expected an identifier, got keyword `let`"
+        );
+    }
+
+    #[test]
+    fn test_token_type_categories() {
+        assert!(TokenType::Let.is_keyword());
+        assert!(!TokenType::Let.is_operator());
+        assert!(TokenType::Equal.is_operator());
+        assert!(TokenType::Or.is_operator());
+        assert!(!TokenType::Comma.is_operator());
+        assert!(TokenType::Comma.is_delimiter());
+        assert!(TokenType::String.is_literal());
+        assert!(!TokenType::Ident.is_literal());
+
+        // Booleans are both keywords and literals.
+        assert!(TokenType::True.is_keyword() && TokenType::True.is_literal());
+    }
+
+    #[test]
+    fn test_lookup_ident() {
+        assert_eq!(TokenType::lookup_ident("let"), TokenType::Let);
+        assert_eq!(TokenType::lookup_ident("fn"), TokenType::Function);
+        assert_eq!(TokenType::lookup_ident("return"), TokenType::Return);
+        assert_eq!(TokenType::lookup_ident("lets"), TokenType::Ident);
+        assert_eq!(TokenType::lookup_ident("Let"), TokenType::Ident);
+
+        for (keyword, token_type) in KEYWORDS.iter() {
+            assert!(token_type.is_keyword());
+            assert_eq!(token_type.symbol(), Some(*keyword));
+        }
+    }
+}
@@ -0,0 +1,250 @@
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use redhowler::compiler::Compiler;
+    use redhowler::interpreter::ast::Program;
+    use redhowler::interpreter::environment::Environment;
+    use redhowler::interpreter::evaluator::{self, Evaluator};
+    use redhowler::interpreter::lexer::Lexer;
+    use redhowler::interpreter::object::Object;
+    use redhowler::interpreter::parser::Parser;
+    use redhowler::vm::Vm;
+
+    fn parse(input: &str) -> Program {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        program
+    }
+
+    fn run(input: &str) -> Object {
+        let mut compiler = Compiler::new();
+        if let Err(err) = compiler.compile(&parse(input)) {
+            panic!("compiling {}: {}", input, err);
+        }
+        Vm::new(compiler.bytecode()).run()
+    }
+
+    #[test]
+    fn test_expressions() {
+        let tests = [
+            ("1 + 2 * 3", "7"),
+            ("2 ** 3 % 5", "3"),
+            ("-(1.5 + 1)", "-2.5"),
+            ("!(1 < 2) == false", "true"),
+            (r#""a" + "b" < "b""#, "true"),
+            ("[1, 2 + 3][1]", "5"),
+            (r#"{"a": 1, true: 2}[true]"#, "2"),
+            ("[1][5]", "null"),
+            ("let a = 1; let b = a + 1; b * 10", "20"),
+            ("let x; x", "null"),
+            ("if (1 < 2) { 10 } else { 20 }", "10"),
+            ("if (false) { 10 } else { 20 } + 1", "21"),
+            ("if (false) { 10 }", "null"),
+            ("if (true) { }", "null"),
+            ("1; if (true) { let y = 2; }", "null"),
+            ("if (0) { 1 } else { 2 }", "1"),
+            ("if (if (false) { 10 }) { 1 } else { 2 }", "2"),
+            ("true ? 1 : 2", "1"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input).to_string(), expected, "running {}", input);
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let tests = [
+            ("let f = fn(a, b) { a + b }; f(1, 2)", "3"),
+            ("let f = fn() { return 1; 2 }; f()", "1"),
+            ("let f = fn() { }; f()", "null"),
+            ("let f = fn() { let a = 1; }; f()", "null"),
+            (
+                "let g = 10; let f = fn(a) { let b = a * 2; b + g }; f(1) + f(2)",
+                "26",
+            ),
+            ("let f = fn(a) { fn() { a } }; f(4)()", "4"),
+            // Builtins, and a builtin calling a closure.
+            (r#"len("four") + first([1])"#, "5"),
+            ("let k = 3; map([1, 2], fn(x) { x * k })", "[3, 6]"),
+            ("reduce([1, 2, 3], 0, fn(acc, x) { acc + x })", "6"),
+            ("type(fn() { 1 })", "FUNCTION"),
+            ("fn(x, y) { x + y }", "fn(x, y) { ... }"),
+            ("return 5; 6", "5"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input).to_string(), expected, "running {}", input);
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let tests = [
+            (
+                "let adder = fn(a) { fn(b) { fn(c) { a + b + c } } }; adder(1)(2)(3)",
+                "6",
+            ),
+            (
+                "let make = fn(a, b) { let c = a + b; fn(d) { let e = d + c; fn(f) { e + f } } };
+                 let inner = make(1, 2)(3); inner(4)",
+                "10",
+            ),
+            (
+                "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(15)",
+                "610",
+            ),
+            (
+                "let wrapper = fn() {
+                     let countdown = fn(x) { if (x == 0) { 0 } else { countdown(x - 1) } };
+                     countdown(3)
+                 };
+                 wrapper()",
+                "0",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input).to_string(), expected, "running {}", input);
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let tests = [
+            ("1 + true", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("1 / 0; 2", "division by zero"),
+            ("1[0]", "index operator not supported: INTEGER"),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("1()", "not a function: INTEGER"),
+            ("fn(a) { a }()", "wrong number of arguments: want=1, got=0"),
+            ("len(1)", "argument to `len` not supported, got INTEGER"),
+            (
+                "let f = fn() { f() }; f()",
+                "maximum recursion depth exceeded",
+            ),
+            (
+                "map([1], fn(x) { x + true })",
+                "type mismatch: INTEGER + BOOLEAN",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                run(input),
+                Object::Error(expected.to_string()),
+                "running {}",
+                input
+            );
+        }
+    }
+
+    // Operands that don't fit in their encoding are refused by the compiler
+    // instead of being truncated.
+    #[test]
+    fn test_operand_limits() {
+        // Identifiers can't contain digits, local i is named after i in base 26.
+        let name = |i: usize| {
+            format!(
+                "v{}{}",
+                (b'a' + (i / 26) as u8) as char,
+                (b'a' + (i % 26) as u8) as char
+            )
+        };
+        let locals = |count: usize| {
+            let lets: String = (0..count)
+                .map(|i| format!("let {} = {};", name(i), i))
+                .collect();
+            format!("let f = fn() {{ {} {} }}; f()", lets, name(43))
+        };
+        let xs = |count: usize| vec!["x"; count].join(", ");
+        let args = |count: usize| vec!["1"; count].join(", ");
+
+        // The largest operands still work.
+        assert_eq!(run(&locals(256)).to_string(), "43");
+        assert_eq!(
+            run(&format!("let x = 1; len([{}])", xs(65535))).to_string(),
+            "65535"
+        );
+
+        let tests = [
+            (locals(257), "too many locals"),
+            (format!("let x = 1; [{}]", xs(70000)), "too many elements"),
+            (
+                format!(
+                    "[{}]",
+                    (0..70000)
+                        .map(|i| i.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                "too many constants",
+            ),
+            (format!("len({})", args(256)), "too many arguments"),
+        ];
+
+        for (input, expected) in tests {
+            let mut compiler = Compiler::new();
+            assert_eq!(
+                compiler.compile(&parse(&input)),
+                Err(expected.to_string()),
+                "compiling {}...",
+                &input[..40]
+            );
+        }
+    }
+
+    #[test]
+    fn test_builtins_write_to_the_evaluator_output() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut compiler = Compiler::new();
+        compiler
+            .compile(&parse(r#"puts("hi", [1]); map([1, 2], puts)"#))
+            .unwrap();
+
+        let evaluator = Evaluator::with_output(output.clone());
+        let result = Vm::with_evaluator(compiler.bytecode(), evaluator).run();
+
+        assert_eq!(result.to_string(), "[null, null]");
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "hi\n[1]\n1\n2\n"
+        );
+    }
+
+    // The programs give the same value with both backends.
+    #[test]
+    fn test_agrees_with_the_evaluator() {
+        let programs = [
+            "let a = [1, 2.5, \"s\", {\"k\": [true]}]; a",
+            "let f = fn(x) { fn(y) { x * y } }; let g = f(3); [g(2), g(4)]",
+            "let compose = fn(f, g) { fn(x) { g(f(x)) } }; compose(fn(x) { x + 1 }, fn(x) { x * 2 })(5)",
+            "let x = 1; let f = fn() { let x = 2; x }; [f(), x]",
+            "sort([3, 1, 2], fn(a, b) { b - a })",
+            "let r = try(fn() { error(\"no\") }); r",
+            "json_stringify({\"a\": [1, 2]})",
+            "\"a\" + 1",
+            "9223372036854775807 + 1",
+            "let f = fn(a, b) { a }; f(1)",
+            "let max = fn(a, b) { if (a > b) { a } else { b } }; [max(1, 2), max(4, 3)]",
+            "let f = fn(n) { if (n) { \"yes\" } }; [f(0), f(1)]",
+            // The closures see a variable defined again after they were made.
+            "let x = 1; let f = fn() { x }; let x = 2; f()",
+            "let g = fn() { let x = 1; let f = fn() { x }; let x = 2; f() }; g()",
+            "let g = fn() { let x = 1; let f = fn() { fn() { x } }; let x = 3; f()() }; g()",
+            "let f = fn(x) { let h = fn() { x }; let x = x + 1; h() }; f(1)",
+            "let make = fn(n) { let x = n; fn() { x } }; [make(1)(), make(2)()]",
+        ];
+
+        for input in programs {
+            let env = Rc::new(RefCell::new(Environment::new()));
+            let expected = evaluator::eval(&parse(input), &env);
+            assert_eq!(run(input), expected, "running {}", input);
+        }
+    }
+}